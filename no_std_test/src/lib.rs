@@ -0,0 +1,16 @@
+//! Floaout without `std`, which decodes bubble files into samples.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use floaout::bub::{BubBlock, BubReader};
+use floaout::io::Result;
+
+/// Blocks of the bubble file in `bytes`, with samples decoded as `f32`
+pub fn decode_blocks(bytes: &[u8]) -> Result<Vec<BubBlock<f32>>> {
+    BubReader::new(bytes, Vec::new())?
+        .decoded_blocks()
+        .collect()
+}
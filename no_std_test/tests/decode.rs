@@ -0,0 +1,42 @@
+use floaout::bub::BubBlockSamples;
+use floaout::io::{ErrorKind, Result};
+use floaout_no_std_test::decode_blocks;
+
+#[test]
+fn decode_lpcm() -> Result<()> {
+    let blocks = decode_blocks(include_bytes!("../../tests/lpcm_test.bub"))?;
+
+    let expects = [
+        (1, b"1 2 3 X<3 0.1*N".to_vec(), vec![1.0, 1.0]),
+        (3, b"1 2 3 X<3 1".to_vec(), vec![0.3]),
+        (5, b"0 0 0 0==0 1".to_vec(), vec![0.4]),
+        (6, b"0 0 n X>=3 -z".to_vec(), vec![1.0]),
+    ];
+    assert_eq!(blocks.len(), expects.len());
+    for (block, expect) in blocks.iter().zip(expects) {
+        assert_eq!(block.summary.head_absolute_frame, expect.0);
+        assert_eq!(block.summary.bub_fns, expect.1);
+        assert_eq!(block.summary.samples, BubBlockSamples::Lpcm);
+        assert_eq!(block.samples, expect.2);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn expr_needs_std() {
+    let e = decode_blocks(include_bytes!("../../tests/expr_test.bub")).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Unsupported);
+}
+
+#[test]
+fn broken_bubble() {
+    let mut v = include_bytes!("../../tests/lpcm_test.bub").to_vec();
+    let len = v.len();
+    v[len - 6] ^= 1;
+    let e = decode_blocks(&v).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+
+    let e = decode_blocks(&v[..len - 1]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,109 @@
+use crate::io::{Error, ErrorKind, Result};
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+
+/// Writes bits from the most significant bit.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    /// Number of bits used in the last byte
+    bits_in_last_byte: u32,
+}
+
+#[cfg(feature = "std")]
+impl BitWriter {
+    pub(crate) const fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            bits_in_last_byte: 8,
+        }
+    }
+
+    pub(crate) fn write_bit(&mut self, bit: bool) {
+        if self.bits_in_last_byte == 8 {
+            self.bytes.push(0);
+            self.bits_in_last_byte = 0;
+        }
+        if bit {
+            *self.bytes.last_mut().expect("pushed") |= 0x80 >> self.bits_in_last_byte;
+        }
+        self.bits_in_last_byte += 1;
+    }
+
+    /// Write `len` (0 ~ 64) low bits of `value`.
+    pub(crate) fn write_bits(&mut self, value: u64, len: u32) {
+        for i in (0..len).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads bits from the most significant bit.
+#[derive(Clone, Debug)]
+pub(crate) struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// Position in bits
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Result<bool> {
+        let byte = self.bytes.get(self.pos / 8).ok_or_else(|| {
+            Error::new(ErrorKind::UnexpectedEof, "compressed samples are too short")
+        })?;
+        let bit = byte & (0x80 >> (self.pos % 8)) != 0;
+        self.pos += 1;
+        Ok(bit)
+    }
+
+    /// Number of bits which are not read yet
+    pub(crate) fn remaining_bits(&self) -> usize {
+        (self.bytes.len() * 8).saturating_sub(self.pos)
+    }
+
+    /// Read `len` (0 ~ 64) bits.
+    pub(crate) fn read_bits(&mut self, len: u32) -> Result<u64> {
+        let mut value = 0;
+        for _ in 0..len {
+            value = value << 1 | self.read_bit()? as u64;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read() -> Result<()> {
+        let mut bit_writer = BitWriter::new();
+        bit_writer.write_bit(true);
+        bit_writer.write_bits(0b0110, 4);
+        bit_writer.write_bits(u64::MAX, 64);
+        bit_writer.write_bits(0, 0);
+        let bytes = bit_writer.into_bytes();
+        assert_eq!(bytes.len(), 9);
+        assert_eq!(bytes[0], 0b1011_0111);
+
+        let mut bit_reader = BitReader::new(&bytes);
+        assert!(bit_reader.read_bit()?);
+        assert_eq!(bit_reader.read_bits(4)?, 0b0110);
+        assert_eq!(bit_reader.read_bits(64)?, u64::MAX);
+        assert_eq!(bit_reader.read_bits(0)?, 0);
+        // Padding
+        assert_eq!(bit_reader.read_bits(3)?, 0);
+        assert!(bit_reader.read_bit().is_err());
+
+        Ok(())
+    }
+}
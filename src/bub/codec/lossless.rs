@@ -0,0 +1,140 @@
+//! Lossless compression with a fixed linear predictor and Rice coding of the residual.
+
+use crate::bub::codec::rice::{self, unzigzag};
+use crate::bub::codec::BitReader;
+#[cfg(feature = "std")]
+use crate::bub::codec::{rice::zigzag, BitWriter};
+use crate::io::{Error, ErrorKind, Result};
+use alloc::format;
+use alloc::vec::Vec;
+
+/// Max order of the fixed linear predictor
+pub(crate) const MAX_PREDICTOR_ORDER: u8 = 4;
+/// Number of residuals sharing a Rice parameter
+const PARTITION_LEN: usize = 256;
+
+/// `history` is the previous values. The last one is the latest.
+fn predict(order: u8, history: &[i64]) -> i64 {
+    let x = |i: usize| history[history.len() - i];
+    match order {
+        0 => 0,
+        1 => x(1),
+        2 => x(1).wrapping_mul(2).wrapping_sub(x(2)),
+        3 => x(1).wrapping_sub(x(2)).wrapping_mul(3).wrapping_add(x(3)),
+        4 => x(1)
+            .wrapping_add(x(3))
+            .wrapping_mul(4)
+            .wrapping_sub(x(2).wrapping_mul(6))
+            .wrapping_sub(x(4)),
+        _ => unreachable!(),
+    }
+}
+
+/// Lower the order until there is enough history.
+fn warm_up_order(order: u8, history_len: usize) -> u8 {
+    if history_len < order as usize {
+        history_len as u8
+    } else {
+        order
+    }
+}
+
+#[cfg(feature = "std")]
+fn residuals(values: &[i64], order: u8) -> Vec<u64> {
+    (0..values.len())
+        .map(|i| zigzag(values[i].wrapping_sub(predict(warm_up_order(order, i), &values[..i]))))
+        .collect()
+}
+
+/// Returns the predictor order and the compressed bytes.
+#[cfg(feature = "std")]
+pub(crate) fn encode(values: &[i64]) -> (u8, Vec<u8>) {
+    let (order, residuals) = (0..=MAX_PREDICTOR_ORDER)
+        .map(|order| (order, residuals(values, order)))
+        .min_by_key(|(_, residuals)| residuals.iter().map(|r| *r as u128).sum::<u128>())
+        .expect("not empty");
+
+    let mut bit_writer = BitWriter::new();
+    for partition in residuals.chunks(PARTITION_LEN) {
+        rice::write_partition(&mut bit_writer, partition);
+    }
+
+    (order, bit_writer.into_bytes())
+}
+
+/// Decode `len` values.
+pub(crate) fn decode(order: u8, bytes: &[u8], len: usize) -> Result<Vec<i64>> {
+    if MAX_PREDICTOR_ORDER < order {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("predictor order {} is not supported", order),
+        ));
+    }
+
+    let mut bit_reader = BitReader::new(bytes);
+    // Each value has at least a bit.
+    if bit_reader.remaining_bits() < len {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} samples can't be decoded from {} bytes",
+                len,
+                bytes.len()
+            ),
+        ));
+    }
+    let mut values = Vec::with_capacity(len);
+    while values.len() < len {
+        let partition_len = PARTITION_LEN.min(len - values.len());
+        for residual in rice::read_partition(&mut bit_reader, partition_len)? {
+            let prediction = predict(warm_up_order(order, values.len()), &values);
+            values.push(unzigzag(residual).wrapping_add(prediction));
+        }
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_and_decode() -> Result<()> {
+        let sine: Vec<i64> = (0..1000)
+            .map(|n| ((n as f64 * 0.05).sin() * 30000.0) as i64)
+            .collect();
+        let mut seed = 1u64;
+        let noise: Vec<i64> = (0..700)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                seed as i64
+            })
+            .collect();
+        let values_list = [
+            vec![],
+            vec![0],
+            vec![i64::MIN, i64::MAX, 0, -1, 1],
+            sine.clone(),
+            noise,
+        ];
+
+        for values in values_list {
+            let (order, bytes) = encode(&values);
+            assert_eq!(decode(order, &bytes, values.len())?, values);
+        }
+
+        // Smaller than 16-bit LPCM
+        let (order, bytes) = encode(&sine);
+        assert!(1 < order);
+        assert!(bytes.len() < sine.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn too_many_samples() {
+        let e = decode(0, &[0; 4], usize::MAX).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,406 @@
+//! Lossy compression with the MDCT (Modified Discrete Cosine Transform).
+//!
+//! Samples are split into frames which overlap by half with the sine window.
+//! Coefficients of each frame are quantized with a step common to the block,
+//! and the step is chosen to fit in the bits.
+
+use crate::bub::codec::rice::{self, unzigzag};
+use crate::bub::codec::BitReader;
+#[cfg(feature = "std")]
+use crate::bub::codec::{rice::zigzag, BitWriter};
+use crate::io::{Error, ErrorKind, Result};
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+use core::ops::{Add, Mul, Sub};
+
+/// Number of coefficients per frame
+const FRAME_LEN: usize = 512;
+/// Number of coefficients sharing a Rice parameter
+const PARTITION_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    /// e^(i * theta)
+    fn from_angle(theta: f64) -> Self {
+        Self {
+            re: libm::cos(theta),
+            im: libm::sin(theta),
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            re: self.re + rhs.re,
+            im: self.im + rhs.im,
+        }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            re: self.re - rhs.re,
+            im: self.im - rhs.im,
+        }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            re: self.re * rhs.re - self.im * rhs.im,
+            im: self.re * rhs.im + self.im * rhs.re,
+        }
+    }
+}
+
+/// MDCT of `2 * len` samples to `len` coefficients with the sine window.
+struct Mdct {
+    len: usize,
+    window: Vec<f64>,
+    /// Twiddles before and after the FFT of the DCT-IV
+    pre_twiddles: Vec<Complex>,
+    post_twiddles: Vec<Complex>,
+    fft_twiddles: Vec<Complex>,
+}
+
+impl Mdct {
+    /// `len` must be a multiple of 4 and `len / 2` must be a power of 2.
+    fn new(len: usize) -> Self {
+        let n = len as f64;
+        let half = len / 2;
+        Self {
+            len,
+            window: (0..2 * len)
+                .map(|i| libm::sin(PI * (i as f64 + 0.5) / (2.0 * n)))
+                .collect(),
+            pre_twiddles: (0..half)
+                .map(|i| Complex::from_angle(-PI * (i as f64 + 0.25) / n))
+                .collect(),
+            post_twiddles: (0..half)
+                .map(|i| Complex::from_angle(-PI * i as f64 / n))
+                .collect(),
+            fft_twiddles: (0..half / 2)
+                .map(|i| Complex::from_angle(-2.0 * PI * i as f64 / half as f64))
+                .collect(),
+        }
+    }
+
+    /// Radix-2 FFT
+    fn fft(&self, x: &mut [Complex]) {
+        let len = x.len();
+        // Bit reversal
+        let mut j = 0;
+        for i in 1..len {
+            let mut bit = len >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j |= bit;
+            if i < j {
+                x.swap(i, j);
+            }
+        }
+
+        let mut size = 2;
+        while size <= len {
+            let step = len / size;
+            for start in (0..len).step_by(size) {
+                for k in 0..size / 2 {
+                    let u = x[start + k];
+                    let v = x[start + k + size / 2] * self.fft_twiddles[k * step];
+                    x[start + k] = u + v;
+                    x[start + k + size / 2] = u - v;
+                }
+            }
+            size *= 2;
+        }
+    }
+
+    /// DCT-IV with the FFT of `len / 2`
+    fn dct4(&self, u: &[f64]) -> Vec<f64> {
+        let len = self.len;
+        let mut v: Vec<Complex> = (0..len / 2)
+            .map(|i| {
+                Complex {
+                    re: u[2 * i],
+                    im: u[len - 1 - 2 * i],
+                } * self.pre_twiddles[i]
+            })
+            .collect();
+        self.fft(&mut v);
+
+        let mut x = vec![0.0; len];
+        for (k, v) in v.into_iter().enumerate() {
+            let y = v * self.post_twiddles[k];
+            x[2 * k] = y.re;
+            x[len - 1 - 2 * k] = -y.im;
+        }
+        x
+    }
+
+    #[cfg(feature = "std")]
+    fn forward(&self, samples: &[f64]) -> Vec<f64> {
+        let half = self.len / 2;
+        let x = |i: usize| samples[i] * self.window[i];
+        // Fold (a, b, c, d) to (-c_r - d, a - b_r)
+        let u: Vec<f64> = (0..half)
+            .map(|i| -x(self.len + half - 1 - i) - x(self.len + half + i))
+            .chain((0..half).map(|i| x(i) - x(self.len - 1 - i)))
+            .collect();
+        self.dct4(&u)
+    }
+
+    /// Windowed samples to overlap and add
+    fn inverse(&self, coefficients: &[f64]) -> Vec<f64> {
+        let half = self.len / 2;
+        let scale = 2.0 / self.len as f64;
+        let u: Vec<f64> = self.dct4(coefficients).iter().map(|u| u * scale).collect();
+        // Unfold to (u2, -u2_r, -u1_r, -u1)
+        (0..half)
+            .map(|i| u[half + i])
+            .chain((0..half).map(|i| -u[self.len - 1 - i]))
+            .chain((0..half).map(|i| -u[half - 1 - i]))
+            .chain((0..half).map(|i| -u[i]))
+            .zip(&self.window)
+            .map(|(y, w)| y * w)
+            .collect()
+    }
+}
+
+/// Number of frames including the half frames at the both ends
+fn frames(len: usize) -> usize {
+    len.div_ceil(FRAME_LEN) + 1
+}
+
+fn step(gain: i8) -> f64 {
+    libm::exp2(gain as f64 / 4.0)
+}
+
+#[cfg(feature = "std")]
+fn quantize(coefficients: &[Vec<f64>], gain: i8) -> Vec<u8> {
+    let step = step(gain);
+    let mut bit_writer = BitWriter::new();
+    for coefficients in coefficients {
+        for partition in coefficients.chunks(PARTITION_LEN) {
+            let values: Vec<u64> = partition
+                .iter()
+                .map(|c| zigzag(libm::round(c / step) as i64))
+                .collect();
+            // Silent partition is a bit.
+            if values.iter().all(|v| *v == 0) {
+                bit_writer.write_bit(false);
+            } else {
+                bit_writer.write_bit(true);
+                rice::write_partition(&mut bit_writer, &values);
+            }
+        }
+    }
+    bit_writer.into_bytes()
+}
+
+#[cfg(feature = "std")]
+fn coefficients(samples: &[f64]) -> Vec<Vec<f64>> {
+    let mdct = Mdct::new(FRAME_LEN);
+    let frames = frames(samples.len());
+    let mut padded = vec![0.0; (frames + 1) * FRAME_LEN];
+    padded[FRAME_LEN..FRAME_LEN + samples.len()].copy_from_slice(samples);
+    (0..frames)
+        .map(|i| mdct.forward(&padded[i * FRAME_LEN..(i + 2) * FRAME_LEN]))
+        .collect()
+}
+
+/// Returns the compressed bytes with the gain.
+#[cfg(feature = "std")]
+pub(crate) fn encode_with_gain(samples: &[f64], gain: i8) -> Vec<u8> {
+    quantize(&coefficients(samples), gain)
+}
+
+/// Returns the gain and the compressed bytes which fit in `bits` if possible.
+/// The quantization step is `2^(gain / 4)`.
+#[cfg(feature = "std")]
+pub(crate) fn encode(samples: &[f64], bits: u64) -> (i8, Vec<u8>) {
+    let coefficients = coefficients(samples);
+
+    // Smallest gain which fits
+    let (mut low, mut high) = (i8::MIN, i8::MAX);
+    let mut compressed = quantize(&coefficients, high);
+    while low < high {
+        let middle = ((low as i16 + high as i16) >> 1) as i8;
+        let bytes = quantize(&coefficients, middle);
+        if bytes.len() as u64 * 8 <= bits {
+            high = middle;
+            compressed = bytes;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    (high, compressed)
+}
+
+/// Decode `len` samples.
+pub(crate) fn decode(gain: i8, bytes: &[u8], len: usize) -> Result<Vec<f64>> {
+    let mdct = Mdct::new(FRAME_LEN);
+    let step = step(gain);
+    let frames = frames(len);
+    let mut bit_reader = BitReader::new(bytes);
+    // Each partition has at least a bit.
+    let too_many_samples = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} samples can't be decoded from {} bytes",
+                len,
+                bytes.len()
+            ),
+        )
+    };
+    if bit_reader.remaining_bits() / (FRAME_LEN / PARTITION_LEN) < frames {
+        return Err(too_many_samples());
+    }
+    let padded_len = (frames + 1)
+        .checked_mul(FRAME_LEN)
+        .ok_or_else(too_many_samples)?;
+    let mut padded = vec![0.0; padded_len];
+    for i in 0..frames {
+        let mut coefficients = Vec::with_capacity(FRAME_LEN);
+        while coefficients.len() < FRAME_LEN {
+            if bit_reader.read_bit()? {
+                for value in rice::read_partition(&mut bit_reader, PARTITION_LEN)? {
+                    coefficients.push(unzigzag(value) as f64 * step);
+                }
+            } else {
+                coefficients.extend_from_slice(&[0.0; PARTITION_LEN]);
+            }
+        }
+
+        // Overlap and add
+        for (sample, y) in padded[i * FRAME_LEN..(i + 2) * FRAME_LEN]
+            .iter_mut()
+            .zip(mdct.inverse(&coefficients))
+        {
+            *sample += y;
+        }
+    }
+
+    padded.truncate(FRAME_LEN + len);
+    Ok(padded.split_off(FRAME_LEN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snr(expects: &[f64], samples: &[f64]) -> f64 {
+        let signal: f64 = expects.iter().map(|e| e * e).sum();
+        let noise: f64 = expects
+            .iter()
+            .zip(samples)
+            .map(|(e, s)| (e - s) * (e - s))
+            .sum();
+        10.0 * (signal / noise).log10()
+    }
+
+    #[test]
+    fn mdct() {
+        let len = 16;
+        let mdct = Mdct::new(len);
+        let samples: Vec<f64> = (0..2 * len).map(|i| (i as f64 * 0.7).sin()).collect();
+
+        // Definition
+        let coefficients = mdct.forward(&samples);
+        for (k, coefficient) in coefficients.iter().enumerate() {
+            let expect: f64 = (0..2 * len)
+                .map(|i| {
+                    samples[i]
+                        * mdct.window[i]
+                        * (PI / len as f64 * (i as f64 + 0.5 + len as f64 / 2.0) * (k as f64 + 0.5))
+                            .cos()
+                })
+                .sum();
+            assert!((coefficient - expect).abs() < 1e-9);
+        }
+
+        // Perfect reconstruction
+        let samples: Vec<f64> = (0..6 * len).map(|i| (i as f64 * 0.3).cos()).collect();
+        let mut reconstructed = vec![0.0; 6 * len];
+        for i in 0..5 {
+            let y = mdct.inverse(&mdct.forward(&samples[i * len..(i + 2) * len]));
+            for (sample, y) in reconstructed[i * len..(i + 2) * len].iter_mut().zip(y) {
+                *sample += y;
+            }
+        }
+        for i in len..5 * len {
+            assert!((reconstructed[i] - samples[i]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn encode_and_decode() -> Result<()> {
+        let samples_per_sec = 48000;
+        let mut seed = 3u32;
+        let samples: Vec<f64> = (0..samples_per_sec)
+            .map(|n| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let t = n as f64 / samples_per_sec as f64;
+                (2.0 * PI * 440.0 * t).sin() * 0.5
+                    + (2.0 * PI * 1250.0 * t).sin() * 0.2
+                    + ((seed >> 8) as f64 / (1 << 24) as f64 - 0.5) * 0.01
+            })
+            .collect();
+
+        let mut prev_snr = f64::MIN;
+        for bitrate in [16_000, 64_000, 256_000] {
+            let (gain, bytes) = encode(&samples, bitrate);
+            assert!(bytes.len() as u64 * 8 <= bitrate);
+            let decoded = decode(gain, &bytes, samples.len())?;
+            assert_eq!(decoded.len(), samples.len());
+
+            let snr = snr(&samples, &decoded);
+            assert!(prev_snr < snr);
+            prev_snr = snr;
+            if bitrate == 64_000 {
+                assert!(30.0 < snr, "{}", snr);
+            }
+        }
+        assert!(60.0 < prev_snr, "{}", prev_snr);
+
+        // Shorter than a frame
+        let (gain, bytes) = encode(&samples[..100], 1_000_000);
+        let decoded = decode(gain, &bytes, 100)?;
+        assert!(60.0 < snr(&samples[..100], &decoded));
+
+        Ok(())
+    }
+
+    #[test]
+    fn too_many_samples() -> Result<()> {
+        // Silent frames are 2 bytes.
+        let (gain, bytes) = encode(&[0.0; 2000], 1_000_000);
+        assert_eq!(bytes.len(), 2 * 5);
+        assert_eq!(decode(gain, &bytes, 2000)?, vec![0.0; 2000]);
+        for len in [2561, usize::MAX] {
+            let e = decode(gain, &bytes, len).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+pub(crate) use self::bits::BitReader;
+#[cfg(feature = "std")]
+pub(crate) use self::bits::BitWriter;
+
+pub(crate) mod lossless;
+pub(crate) mod mdct;
+
+mod bits;
+mod rice;
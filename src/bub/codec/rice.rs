@@ -0,0 +1,121 @@
+use crate::bub::codec::BitReader;
+#[cfg(feature = "std")]
+use crate::bub::codec::BitWriter;
+use crate::io::Result;
+use alloc::vec::Vec;
+
+/// Bits of a Rice parameter
+const RICE_PARAMETER_BITS: u32 = 6;
+/// Quotient from which the value is stored as raw 64 bits
+const ESCAPE: u64 = 16;
+
+/// 0, -1, 1, -2, ... to 0, 1, 2, 3, ...
+#[cfg(feature = "std")]
+pub(crate) const fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+pub(crate) const fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+#[cfg(feature = "std")]
+fn rice_bits(values: &[u64], rice_parameter: u32) -> u64 {
+    values
+        .iter()
+        .map(|value| {
+            let quotient = value >> rice_parameter;
+            if quotient < ESCAPE {
+                quotient + 1 + rice_parameter as u64
+            } else {
+                ESCAPE + 64
+            }
+        })
+        .sum()
+}
+
+#[cfg(feature = "std")]
+fn rice_parameter(values: &[u64]) -> u32 {
+    let mean = values.iter().map(|v| *v as u128).sum::<u128>() / values.len().max(1) as u128;
+    let estimate = 127 - mean.max(1).leading_zeros();
+    (estimate.saturating_sub(2)..=(estimate + 2).min(63))
+        .min_by_key(|rice_parameter| rice_bits(values, *rice_parameter))
+        .expect("not empty")
+}
+
+/// Write a Rice parameter and `values` coded with it.
+#[cfg(feature = "std")]
+pub(crate) fn write_partition(bit_writer: &mut BitWriter, values: &[u64]) {
+    let rice_parameter = rice_parameter(values);
+    bit_writer.write_bits(rice_parameter as u64, RICE_PARAMETER_BITS);
+    for value in values {
+        let quotient = value >> rice_parameter;
+        if quotient < ESCAPE {
+            for _ in 0..quotient {
+                bit_writer.write_bit(true);
+            }
+            bit_writer.write_bit(false);
+            bit_writer.write_bits(*value, rice_parameter);
+        } else {
+            for _ in 0..ESCAPE {
+                bit_writer.write_bit(true);
+            }
+            bit_writer.write_bits(*value, 64);
+        }
+    }
+}
+
+/// Read `len` values written by `write_partition`.
+pub(crate) fn read_partition(bit_reader: &mut BitReader, len: usize) -> Result<Vec<u64>> {
+    let rice_parameter = bit_reader.read_bits(RICE_PARAMETER_BITS)? as u32;
+    // Each value has at least a bit.
+    let mut values = Vec::with_capacity(len.min(bit_reader.remaining_bits()));
+    for _ in 0..len {
+        let mut quotient = 0;
+        while quotient < ESCAPE && bit_reader.read_bit()? {
+            quotient += 1;
+        }
+        values.push(if quotient < ESCAPE {
+            quotient << rice_parameter | bit_reader.read_bits(rice_parameter)?
+        } else {
+            bit_reader.read_bits(64)?
+        });
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zigzag_and_unzigzag() {
+        for n in [0, 1, -1, 2, -2, i64::MAX, i64::MIN] {
+            assert_eq!(unzigzag(zigzag(n)), n);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn write_and_read_partition() -> Result<()> {
+        let partitions = [vec![0, 1, 2, 3, 100], vec![u64::MAX, 0], vec![7; 40]];
+
+        let mut bit_writer = BitWriter::new();
+        for partition in &partitions {
+            write_partition(&mut bit_writer, partition);
+        }
+        let bytes = bit_writer.into_bytes();
+
+        let mut bit_reader = BitReader::new(&bytes);
+        for partition in &partitions {
+            assert_eq!(
+                &read_partition(&mut bit_reader, partition.len())?,
+                partition
+            );
+        }
+
+        Ok(())
+    }
+}
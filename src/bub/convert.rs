@@ -0,0 +1,512 @@
+//! Make bubble files from other formats.
+
+use crate::bub::functions::{
+    parse, uses_speaker_coords, BubFns, BubFnsAST, BubFnsInterpreter, BubFnsVariable,
+};
+use crate::bub::{
+    BubBlockSamples, BubBlockSummaries, BubFnsBlock, BubFrameWriter, BubMetadata, BubReader,
+    BubSampleKind, BubWriter,
+};
+use crate::wav::WavReader;
+use crate::{BubFnsCoord, Frame, FrameIOKind, Sample};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::ops::RangeInclusive;
+
+/// Channel of a WAV file to make a bubble from
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WavChannel {
+    /// 0-based index of the channel
+    Index(u16),
+    /// Average of all channels
+    Downmix,
+}
+
+impl WavChannel {
+    fn mono<S: Sample>(self, frame: Frame<S>) -> f64 {
+        match self {
+            Self::Index(i) => frame.0[i as usize].to_f64(),
+            Self::Downmix => {
+                let channels = frame.0.len() as f64;
+                frame.0.into_iter().map(S::to_f64).sum::<f64>() / channels
+            }
+        }
+    }
+}
+
+fn mono_samples<'a, R: Read + 'a>(
+    wav_reader: WavReader<R>,
+    channel: WavChannel,
+) -> Box<dyn Iterator<Item = Result<f64>> + 'a> {
+    macro_rules! mono_samples {
+        ($wav_frame_reader:expr) => {
+            Box::new($wav_frame_reader.map(move |frame| frame.map(|frame| channel.mono(frame))))
+        };
+    }
+
+    match wav_reader.into_wav_frame_reader_kind() {
+        FrameIOKind::F32LE(r) => mono_samples!(r),
+        FrameIOKind::F64LE(r) => mono_samples!(r),
+        FrameIOKind::I16LE(r) => mono_samples!(r),
+        FrameIOKind::I24LE(r) => mono_samples!(r),
+        FrameIOKind::I32LE(r) => mono_samples!(r),
+        FrameIOKind::U8(r) => mono_samples!(r),
+    }
+}
+
+fn check_segments(segments: &[(RangeInclusive<u64>, &[u8])], frames: u64) -> Result<()> {
+    if segments.is_empty() {
+        return Err(Error::new(ErrorKind::Other, "segments must not be empty"));
+    }
+
+    let mut last_frame = 0;
+    for (range, _) in segments {
+        if *range.start() <= last_frame || range.end() < range.start() || frames < *range.end() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "segment {:?} must be sorted, not overlapped and in 1..={}",
+                    range, frames
+                ),
+            ));
+        }
+        last_frame = *range.end();
+    }
+
+    Ok(())
+}
+
+fn write_segments<W: Write, S: Sample>(
+    mut bub_frame_writer: BubFrameWriter<W, S>,
+    samples: &mut dyn Iterator<Item = Result<f64>>,
+    segments: &[(RangeInclusive<u64>, &[u8])],
+) -> Result<W> {
+    let mut next_sample = || {
+        samples
+            .next()
+            .unwrap_or_else(|| Err(ErrorKind::UnexpectedEof.into()))
+    };
+
+    bub_frame_writer.pos = segments[0].0.start() - 1;
+    for _ in 0..bub_frame_writer.pos {
+        next_sample()?;
+    }
+
+    for (i, (range, bub_fns)) in segments.iter().enumerate() {
+        let samples = range
+            .clone()
+            .map(|_| next_sample().map(S::from_f64))
+            .collect::<Result<Vec<S>>>()?;
+        let next_head_relative_frame = segments
+            .get(i + 1)
+            .map(|(next_range, _)| next_range.start() - range.start() + 1);
+        // Frames between segments are stopped.
+        if let Some(next_head_relative_frame) = next_head_relative_frame {
+            for _ in samples.len() as u64 + 1..next_head_relative_frame {
+                next_sample()?;
+            }
+        }
+
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns,
+            next_head_relative_frame,
+            samples,
+        })?;
+    }
+
+    Ok(bub_frame_writer.inner)
+}
+
+/// Write a bubble from a channel of `wav_reader`.
+///
+/// `segments` are absolute frame ranges of the WAV file and their Bubble Functions in ascending order.
+/// Frames out of the segments are stopped.
+/// `frames` and `samples_per_sec` of `metadata` are set from the WAV file,
+/// and samples are converted to `metadata.lpcm_kind`.
+pub fn from_wav<R: Read, W: Write>(
+    wav_reader: WavReader<R>,
+    channel: WavChannel,
+    segments: &[(RangeInclusive<u64>, &[u8])],
+    mut metadata: BubMetadata,
+    writer: W,
+) -> Result<W> {
+    if let WavChannel::Index(i) = channel {
+        if wav_reader.metadata.channels() <= i {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "channel {} is out of {} channels",
+                    i,
+                    wav_reader.metadata.channels()
+                ),
+            ));
+        }
+    }
+    if let BubSampleKind::Expr(_) = metadata.bub_sample_kind {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "bubble sample kind must not be Expr",
+        ));
+    }
+    check_segments(segments, wav_reader.metadata.frames())?;
+
+    let first_head_absolute_frame = *segments[0].0.start();
+    metadata.frames = wav_reader.metadata.frames();
+    metadata.samples_per_sec = wav_reader.metadata.samples_per_sec();
+    metadata.head_absolute_frame = first_head_absolute_frame;
+    metadata.next_head_absolute_frame = Some(first_head_absolute_frame);
+
+    let mut samples = mono_samples(wav_reader, channel);
+    let bub_writer = BubWriter::new(writer, metadata)?;
+    match bub_writer.into_bub_frame_writer_kind() {
+        FrameIOKind::F32LE(w) => write_segments(w, &mut samples, segments),
+        FrameIOKind::F64LE(w) => write_segments(w, &mut samples, segments),
+        FrameIOKind::I16LE(w) => write_segments(w, &mut samples, segments),
+        FrameIOKind::I24LE(w) => write_segments(w, &mut samples, segments),
+        FrameIOKind::I32LE(w) => write_segments(w, &mut samples, segments),
+        FrameIOKind::U8(w) => write_segments(w, &mut samples, segments),
+    }
+}
+
+/// Coordinates at which expressions are evaluated by [`bake_expr`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ExprCoords {
+    /// Expressions must not use `X`, `Y`, `Z`, `x`, `y` or `z`.
+    #[default]
+    Reject,
+    /// Evaluate at the bubble absolute coordinates of the first `BubFn` which is not out of
+    /// its domain, like [`BubObjectReader`](crate::bub::BubObjectReader).
+    /// Domains and volumes which use speaker coordinates are regarded as in the domain.
+    BubPosition,
+}
+
+fn parse_or_invalid_data(input: &[u8], start_variable: &BubFnsVariable) -> Result<BubFnsAST> {
+    parse(input, start_variable).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("cannot parse `{}`", String::from_utf8_lossy(input)),
+        )
+    })
+}
+
+fn bake_blocks<R: Read, W: Write, S: Sample>(
+    blocks: BubBlockSummaries<R>,
+    coords: ExprCoords,
+    mut bub_frame_writer: BubFrameWriter<W, S>,
+) -> Result<W> {
+    let frames = blocks.metadata.frames as f64;
+    let samples_per_sec = blocks.metadata.samples_per_sec;
+    if let Some(first_head_absolute_frame) = blocks.metadata.next_head_absolute_frame {
+        bub_frame_writer.pos = first_head_absolute_frame - 1;
+    }
+
+    for block in blocks {
+        let block = block?;
+        let expr = match &block.samples {
+            BubBlockSamples::Expr(expr) => parse_or_invalid_data(expr, &BubFnsVariable::Sum)?,
+            _ => return Err(ErrorKind::InvalidData.into()),
+        };
+        if coords == ExprCoords::Reject && uses_speaker_coords(&expr) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "expression at frame {} uses speaker coordinates",
+                    block.head_absolute_frame
+                ),
+            ));
+        }
+        let bub_fns: BubFns = parse_or_invalid_data(&block.bub_fns, &BubFnsVariable::BubFns)?
+            .into_original()
+            .and_then(|output| output.into_bub_fns())
+            .ok_or(ErrorKind::InvalidData)?;
+
+        let samples = (block.head_absolute_frame..=block.foot_absolute_frame)
+            .map(|absolute_frame| {
+                let relative_frame = (absolute_frame - block.head_absolute_frame + 1) as f64;
+                let interpreter = match coords {
+                    ExprCoords::Reject => Some(BubFnsInterpreter::new(
+                        BubFnsCoord::default(),
+                        BubFnsCoord::default(),
+                        absolute_frame as f64,
+                        relative_frame,
+                        frames,
+                        samples_per_sec,
+                    )),
+                    ExprCoords::BubPosition => bub_fns
+                        .to_bub_absolute_coord_and_volume(
+                            absolute_frame as f64,
+                            relative_frame,
+                            frames,
+                            samples_per_sec,
+                        )
+                        .into_iter()
+                        .find(|(volume, _)| *volume != Some(0.0))
+                        .map(|(_, interpreter)| interpreter),
+                };
+                match interpreter {
+                    Some(interpreter) => interpreter
+                        .eval_sum(&expr)
+                        .map(S::from_f64)
+                        .map_err(|_| ErrorKind::InvalidData.into()),
+                    None => Ok(S::default()),
+                }
+            })
+            .collect::<Result<Vec<S>>>()?;
+
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: &block.bub_fns,
+            next_head_relative_frame: block.next_head_absolute_frame.map(
+                |next_head_absolute_frame| next_head_absolute_frame - block.head_absolute_frame + 1,
+            ),
+            samples,
+        })?;
+    }
+
+    Ok(bub_frame_writer.inner)
+}
+
+/// Write an `Lpcm` bubble which has the expressions of `bub_reader` rendered into samples.
+///
+/// Bubble Functions and blocks are the same as `bub_reader`, and samples are `metadata.lpcm_kind`.
+/// The result is the same as the `Expr` bubble if expressions don't depend on speaker coordinates.
+pub fn bake_expr<R: Read, W: Write>(
+    bub_reader: BubReader<R>,
+    coords: ExprCoords,
+    writer: W,
+) -> Result<W> {
+    if !matches!(bub_reader.metadata.bub_sample_kind, BubSampleKind::Expr(_)) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "bubble sample kind must be Expr",
+        ));
+    }
+
+    let mut metadata = bub_reader.metadata.clone();
+    metadata.bub_sample_kind = BubSampleKind::Lpcm;
+    let blocks = bub_reader.blocks();
+    let bub_writer = BubWriter::new(writer, metadata)?;
+    match bub_writer.into_bub_frame_writer_kind() {
+        FrameIOKind::F32LE(w) => bake_blocks(blocks, coords, w),
+        FrameIOKind::F64LE(w) => bake_blocks(blocks, coords, w),
+        FrameIOKind::I16LE(w) => bake_blocks(blocks, coords, w),
+        FrameIOKind::I24LE(w) => bake_blocks(blocks, coords, w),
+        FrameIOKind::I32LE(w) => bake_blocks(blocks, coords, w),
+        FrameIOKind::U8(w) => bake_blocks(blocks, coords, w),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubReader, BubState::*};
+    use crate::wav::{WavMetadata, WavWriter};
+    use crate::LpcmKind;
+
+    fn wav() -> Result<Vec<u8>> {
+        let metadata = WavMetadata::new(6, LpcmKind::I16LE, 2, 48000.0, Vec::new());
+        let wav_writer = WavWriter::new(Vec::new(), metadata)?;
+        let mut wav_frame_writer = wav_writer.into_wav_frame_writer_kind().into_i16_le()?;
+        for n in 1..=6 {
+            wav_frame_writer.write_frame(vec![n as f32 / 8.0, -0.5].into())?;
+        }
+        Ok(wav_frame_writer.inner)
+    }
+
+    #[test]
+    fn from_wav() -> Result<()> {
+        let wav = wav()?;
+        let segments: [(RangeInclusive<u64>, &[u8]); 2] =
+            [(2..=3, b"0 0 0 X<3 1"), (5..=5, b"0 0 0 X<3 0.5")];
+        let metadata = BubMetadata::new(
+            0,
+            1,
+            0.0,
+            LpcmKind::F32LE,
+            BubSampleKind::Lpcm,
+            String::from("From WAV"),
+        );
+
+        let v = super::from_wav(
+            WavReader::new(&wav[..])?,
+            WavChannel::Index(0),
+            &segments,
+            metadata.clone(),
+            Vec::new(),
+        )?;
+        assert!(BubReader::verify(&v[..])?.is_valid());
+
+        let bub_reader = BubReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?;
+        assert_eq!(bub_reader.metadata.frames, 6);
+        assert_eq!(bub_reader.metadata.samples_per_sec, 48000.0);
+        let mut bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_f32_le()?;
+        let expects = [
+            (Stopped, 0.0),
+            (Head, 0.25),
+            (Body, 0.375),
+            (Stopped, 0.0),
+            (Head, 0.3125),
+            (Ended, 0.0),
+        ];
+        for expect in expects {
+            let frame = bub_frame_reader.next().unwrap()?;
+            assert_eq!(bub_frame_reader.metadata.bub_state, expect.0);
+            assert_eq!(frame.0, [expect.1]);
+        }
+        assert!(bub_frame_reader.next().is_none());
+
+        // Downmix into LosslessLpcm
+        let mut metadata_lossless = metadata.clone();
+        metadata_lossless.lpcm_kind = LpcmKind::I16LE;
+        metadata_lossless.bub_sample_kind = BubSampleKind::LosslessLpcm;
+        let v = super::from_wav(
+            WavReader::new(&wav[..])?,
+            WavChannel::Downmix,
+            &[(1..=6, b"0 0 0 X<3 1")],
+            metadata_lossless,
+            Vec::new(),
+        )?;
+        let bub_reader = BubReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?;
+        let frames = bub_reader
+            .into_bub_frame_reader_kind(None)
+            .into_i16_le()?
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames, [-0.1875, -0.125, -0.0625, 0.0, 0.0625, 0.125]);
+
+        // Invalid segments and channel
+        for (segments, channel) in [
+            (&[][..], WavChannel::Index(0)),
+            (
+                &[(2..=3, &b"0 0 0 X<3 1"[..]), (3..=4, b"0 0 0 X<3 1")],
+                WavChannel::Index(0),
+            ),
+            (&[(5..=7, &b"0 0 0 X<3 1"[..])], WavChannel::Index(0)),
+            (&[(1..=6, &b"0 0 0 X<3 1"[..])], WavChannel::Index(2)),
+        ] {
+            assert!(super::from_wav(
+                WavReader::new(&wav[..])?,
+                channel,
+                segments,
+                metadata.clone(),
+                Vec::new(),
+            )
+            .is_err());
+        }
+
+        Ok(())
+    }
+
+    fn expr(bub_fns: &[u8], expressions: [&[u8]; 2]) -> Result<Vec<u8>> {
+        let metadata = BubMetadata::new(
+            8,
+            2,
+            48000.0,
+            LpcmKind::F64LE,
+            BubSampleKind::default_expr(),
+            String::from("Bake"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f64_le()?;
+        bub_frame_writer.pos = 1;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns,
+            foot_relative_frame: 2,
+            next_head_relative_frame: Some(4),
+            expression: expressions[0],
+        })?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns,
+            foot_relative_frame: 3,
+            next_head_relative_frame: None,
+            expression: expressions[1],
+        })?;
+        Ok(bub_frame_writer.inner)
+    }
+
+    #[test]
+    fn bake_expr() -> Result<()> {
+        let speakers_absolute_coord: Vec<BubFnsCoord> =
+            vec![(0.0, 0.0, 0.0).into(), (1.0, 0.0, 0.0).into()];
+        let v = expr(b"0 0 0 X<0.5 1 1 0 0 X>0.5 n/F", [b"0.1*N", b"sin(n/S)"])?;
+        let baked = super::bake_expr(
+            BubReader::new(&v[..], Vec::new())?,
+            ExprCoords::Reject,
+            Vec::new(),
+        )?;
+        assert!(BubReader::verify(&baked[..])?.is_valid());
+
+        let bub_reader = BubReader::new(&baked[..], Vec::new())?;
+        assert_eq!(bub_reader.metadata.bub_sample_kind, BubSampleKind::Lpcm);
+        let blocks = bub_reader.blocks().collect::<Result<Vec<_>>>()?;
+        let expr_blocks = BubReader::new(&v[..], Vec::new())?
+            .blocks()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(blocks.len(), 2);
+        for (block, expr_block) in blocks.iter().zip(&expr_blocks) {
+            assert_eq!(block.head_absolute_frame, expr_block.head_absolute_frame);
+            assert_eq!(block.foot_absolute_frame, expr_block.foot_absolute_frame);
+            assert_eq!(block.bub_fns, expr_block.bub_fns);
+        }
+
+        let render = |v: &[u8]| -> Result<Vec<Frame<f64>>> {
+            BubReader::new(v, speakers_absolute_coord.clone())?
+                .into_bub_frame_reader_kind(None)
+                .into_f64_le()?
+                .collect()
+        };
+        let expr_frames = render(&v)?;
+        let baked_frames = render(&baked)?;
+        assert_eq!(expr_frames.len(), 8);
+        for (expr_frame, baked_frame) in expr_frames.iter().zip(&baked_frames) {
+            for (expr_sample, baked_sample) in expr_frame.0.iter().zip(&baked_frame.0) {
+                assert!((expr_sample - baked_sample).abs() < 1e-12);
+            }
+        }
+
+        // Speaker coordinates
+        let v = expr(b"0.5 0 0 X<3 1", [b"x", b"X+1"])?;
+        assert!(super::bake_expr(
+            BubReader::new(&v[..], Vec::new())?,
+            ExprCoords::Reject,
+            Vec::new(),
+        )
+        .is_err());
+        let baked = super::bake_expr(
+            BubReader::new(&v[..], Vec::new())?,
+            ExprCoords::BubPosition,
+            Vec::new(),
+        )?;
+        let samples = BubReader::new(&baked[..], Vec::new())?
+            .into_bub_frame_reader_kind(None)
+            .into_f64_le()?
+            .into_bub_object_reader()
+            .map(|frame| frame.map(|frame| frame.sample))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(samples, [0.0, 0.0, 0.0, 0.0, 1.5, 1.5, 1.5, 0.0]);
+
+        // The first `BubFn` is out of its domain at the head of each block.
+        let v = expr(b"1 0 0 n>=2 1 2 0 0 0==0 1", [b"X", b"X"])?;
+        let baked = super::bake_expr(
+            BubReader::new(&v[..], Vec::new())?,
+            ExprCoords::BubPosition,
+            Vec::new(),
+        )?;
+        let samples = BubReader::new(&baked[..], Vec::new())?
+            .into_bub_frame_reader_kind(None)
+            .into_f64_le()?
+            .into_bub_object_reader()
+            .map(|frame| frame.map(|frame| frame.sample))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(samples, [0.0, 2.0, 1.0, 0.0, 2.0, 1.0, 1.0, 0.0]);
+
+        // Not Expr
+        let v = std::fs::read("tests/lpcm_test.bub")?;
+        assert!(super::bake_expr(
+            BubReader::new(&v[..], Vec::new())?,
+            ExprCoords::Reject,
+            Vec::new(),
+        )
+        .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use crate::bub::codec::{lossless, mdct};
+use crate::bub::io::{read_payload_and_calc_bytes, BubBlockHead};
+use crate::bub::{BubBlockSamples, BubMetadata, BubSampleKind};
+use crate::json::{sample_stats, Json};
+use crate::tags::{Tags, TAGS_SPEC_VERSION};
+use crate::utils::check_crc;
+use crate::verify::OffsetReader;
+use crate::{LpcmKind, Sample, CRC_32K_4_2};
+use mycrc::CRC;
+use std::io::{Read, Result};
+
+/// Decode LPCM or compressed samples of a block.
+fn decode(
+    samples: &BubBlockSamples,
+    payload: &[u8],
+    lpcm_kind: LpcmKind,
+    len: usize,
+) -> Result<Vec<f64>> {
+    match samples {
+        BubBlockSamples::Lpcm => {
+            let mut bytes = payload;
+            (0..len)
+                .map(|_| f64::read_lpcm(&mut bytes, lpcm_kind))
+                .collect()
+        }
+        BubBlockSamples::Expr(_) => Ok(Vec::new()),
+        BubBlockSamples::LosslessLpcm {
+            predictor_order, ..
+        } => Ok(lossless::decode(*predictor_order, payload, len)?
+            .into_iter()
+            .map(|n| lpcm_kind.i64_to_sample(n))
+            .collect()),
+        BubBlockSamples::Mdct { gain, .. } => mdct::decode(*gain, payload, len),
+    }
+}
+
+pub(crate) fn bub_sample_kind_name(bub_sample_kind: &BubSampleKind) -> &'static str {
+    match bub_sample_kind {
+        BubSampleKind::Lpcm => "Lpcm",
+        BubSampleKind::Expr(_) => "Expr",
+        BubSampleKind::LosslessLpcm => "LosslessLpcm",
+        BubSampleKind::Mdct => "Mdct",
+    }
+}
+
+pub(crate) fn tags_json(tags: &Tags) -> Json {
+    let mut json = Json::object();
+    for (key, value) in tags.iter() {
+        json.push(key.as_str(), value);
+    }
+    json
+}
+
+/// Read a CRC and push `crc_byte_offset` and `crc_ok`.
+pub(crate) fn push_crc<R: Read>(
+    json: &mut Json,
+    reader: &mut OffsetReader<R>,
+    crc: &mut CRC<u32>,
+) -> Result<bool> {
+    json.push("crc_byte_offset", reader.offset);
+    let is_error_free = check_crc(reader, crc)?;
+    json.push("crc_ok", is_error_free);
+
+    Ok(is_error_free)
+}
+
+/// Push a block to `block`, and returns the next head absolute frame.
+fn dump_block<R: Read>(
+    reader: &mut OffsetReader<R>,
+    crc: &mut CRC<u32>,
+    metadata: &BubMetadata,
+    head_absolute_frame: u64,
+    block: &mut Json,
+) -> Result<Option<u64>> {
+    block.push("byte_offset", reader.offset);
+    block.push("head_absolute_frame", head_absolute_frame);
+
+    let head = BubBlockHead::read_and_calc_bytes(reader, crc)?;
+    block.push(
+        "bub_fns",
+        String::from_utf8_lossy(&head.bub_fns).into_owned(),
+    );
+    block.push(
+        "foot_absolute_frame",
+        head.foot_absolute_frame(head_absolute_frame),
+    );
+    let next_head_absolute_frame = head.next_head_absolute_frame(head_absolute_frame);
+    block.push("next_head_absolute_frame", next_head_absolute_frame);
+
+    block.push("samples_byte_offset", reader.offset);
+    let samples = BubBlockSamples::read_and_calc_bytes(reader, &metadata.bub_sample_kind, crc)?;
+    match &samples {
+        BubBlockSamples::Lpcm => (),
+        BubBlockSamples::Expr(expr) => {
+            block.push("expr", String::from_utf8_lossy(expr).into_owned())
+        }
+        BubBlockSamples::LosslessLpcm {
+            predictor_order,
+            compressed_size,
+        } => {
+            block.push("predictor_order", *predictor_order as u64);
+            block.push("compressed_size", *compressed_size);
+        }
+        BubBlockSamples::Mdct {
+            gain,
+            compressed_size,
+        } => {
+            block.push("gain", *gain as i64);
+            block.push("compressed_size", *compressed_size);
+        }
+    }
+    let payload_size = samples.payload_size(metadata.lpcm_kind, head.foot_relative_frame)?;
+    let payload = read_payload_and_calc_bytes(reader, payload_size, crc)?;
+
+    // Samples are decoded only if the CRC is correct.
+    if push_crc(block, reader, crc)? && !matches!(samples, BubBlockSamples::Expr(_)) {
+        let decoded = head
+            .check_frames(head_absolute_frame, metadata.frames)
+            .and_then(|()| head.samples_len())
+            .and_then(|len| decode(&samples, &payload, metadata.lpcm_kind, len));
+        match decoded {
+            Ok(samples) => block.push("samples", sample_stats(samples)),
+            Err(e) => block.push("samples_error", e.to_string()),
+        }
+    }
+
+    Ok(next_head_absolute_frame)
+}
+
+/// Walk all blocks. Returns an error if the rest can't be read.
+fn dump_blocks<R: Read>(reader: &mut OffsetReader<R>, json: &mut Json) -> Result<()> {
+    let mut crc = CRC_32K_4_2;
+    let metadata = BubMetadata::read_and_calc_bytes(reader, &mut crc)?;
+    json.push("spec_version", metadata.spec_version as u64);
+    json.push("bub_id", metadata.bub_id.id.to_string());
+    json.push(
+        "rgb",
+        metadata
+            .bub_id
+            .rgb
+            .map(|rgb| vec![rgb.red as f64, rgb.green as f64, rgb.blue as f64]),
+    );
+    json.push("bub_version", metadata.bub_version as u64);
+    json.push("frames", metadata.frames);
+    json.push("first_head_absolute_frame", metadata.head_absolute_frame);
+    json.push("samples_per_sec", metadata.samples_per_sec);
+    json.push("lpcm_kind", format!("{:?}", metadata.lpcm_kind));
+    json.push(
+        "bub_sample_kind",
+        bub_sample_kind_name(&metadata.bub_sample_kind),
+    );
+    json.push("name", metadata.name.as_str());
+    json.push("header_crc_ok", check_crc(reader, &mut crc)?);
+    if metadata.spec_version >= TAGS_SPEC_VERSION {
+        let tags = Tags::read_and_calc_bytes(reader, &mut crc)?;
+        json.push("tags", tags_json(&tags));
+        json.push("tags_crc_ok", check_crc(reader, &mut crc)?);
+    }
+
+    let mut blocks = Vec::new();
+    let mut next_head_absolute_frame = metadata.next_head_absolute_frame;
+    while let Some(head_absolute_frame) = next_head_absolute_frame {
+        // Broken blocks are dumped as far as they can be read.
+        let mut block = Json::object();
+        let result = dump_block(reader, &mut crc, &metadata, head_absolute_frame, &mut block);
+        blocks.push(block);
+        match result {
+            Ok(next) => next_head_absolute_frame = next,
+            Err(e) => {
+                json.push("blocks", blocks);
+                return Err(e);
+            }
+        }
+    }
+    json.push("blocks", blocks);
+
+    Ok(())
+}
+
+pub(crate) fn dump<R: Read>(inner: R) -> Result<String> {
+    let mut reader = OffsetReader::new(inner);
+    let mut json = Json::object();
+
+    match dump_blocks(&mut reader, &mut json) {
+        Ok(()) => {
+            let trailing_bytes = reader.trailing_bytes()?;
+            json.push("trailing_bytes", trailing_bytes);
+        }
+        Err(e) => json.push_error(reader.offset, e)?,
+    }
+
+    Ok(json.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bub::{BubFnsBlock, BubMetadata, BubReader, BubSampleKind, BubWriter};
+    use crate::tags::TAGS_SPEC_VERSION;
+    use crate::{LpcmKind, TagKey};
+    use std::io::Result;
+
+    #[test]
+    fn dump_expr() -> Result<()> {
+        let v = std::fs::read("tests/expr_test.bub")?;
+        let json = BubReader::dump_json(&v[..])?;
+        assert!(json.starts_with(r#"{"spec_version":0,"bub_id":"0","rgb":null,"bub_version":0,"frames":8,"first_head_absolute_frame":2,"samples_per_sec":96000.0,"lpcm_kind":"F64LE","bub_sample_kind":"Expr","#));
+        assert!(json.contains(r#""head_absolute_frame":2,"bub_fns":"#));
+        assert!(json.contains(r#""expr":"#));
+        assert!(json.ends_with(r#""trailing_bytes":0}"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dump_lpcm() -> Result<()> {
+        let mut metadata = BubMetadata::new(
+            4,
+            1,
+            48000.0,
+            LpcmKind::I16LE,
+            BubSampleKind::Lpcm,
+            String::from("Dump \"1\""),
+        );
+        metadata.spec_version = TAGS_SPEC_VERSION;
+        metadata.tags.insert(TagKey::Composer, "Composer".into());
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let metadata_len = bub_writer.inner.len() as u64;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_i16_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3 1",
+            next_head_relative_frame: Some(4),
+            samples: vec![0.5, -0.5],
+        })?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3 0.5",
+            next_head_relative_frame: None,
+            samples: vec![0.25],
+        })?;
+        let v = bub_frame_writer.inner;
+
+        let json = BubReader::dump_json(&v[..])?;
+        assert!(json.contains(r#""name":"Dump \"1\"","#));
+        assert!(json.contains(r#""tags":{"COMPOSER":"Composer"},"#));
+        let first_block = format!(
+            r#"{{"byte_offset":{},"head_absolute_frame":1,"bub_fns":"0 0 0 X<3 1","foot_absolute_frame":2,"next_head_absolute_frame":4,"samples_byte_offset":{},"crc_byte_offset":{},"crc_ok":true,"samples":{{"count":2,"min":-0.5,"max":0.5,"mean":0.0,"rms":0.5}}}}"#,
+            metadata_len,
+            metadata_len + 2 + 11 + 8 + 8,
+            metadata_len + 2 + 11 + 8 + 8 + 4,
+        );
+        assert!(json.contains(&first_block), "{}", json);
+        assert!(json.contains(r#""head_absolute_frame":4,"#));
+
+        // Broken first block and the end
+        let mut broken = v.clone();
+        let len = broken.len();
+        broken[metadata_len as usize + 2 + 11 + 8 + 8] ^= 1;
+        let json = BubReader::dump_json(&broken[..len - 2])?;
+        assert!(json.contains(r#""crc_ok":false}"#));
+        assert!(json.contains(r#""error":"#));
+        assert!(json.ends_with(&format!(r#""error_byte_offset":{}}}"#, len - 2)));
+
+        Ok(())
+    }
+}
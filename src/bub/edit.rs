@@ -0,0 +1,562 @@
+//! Edit bubble files without rendering.
+
+use crate::bub::codec::{lossless, mdct};
+use crate::bub::functions::{parse, BubFn, BubFnsAST, BubFnsOutput, BubFnsVariable};
+use crate::bub::io::{read_payload_and_calc_bytes, write_payload_and_calc_bytes, BubBlockHead};
+use crate::bub::{BubBlockSamples, BubMetadata};
+use crate::utils::{expected_and_found_error, is_equal, read_crc, write_crc};
+use mpl::choices::Choice;
+use mpl::trees::Node;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Debug, PartialEq)]
+enum BlockSamples {
+    /// Bytes of LPCM samples
+    Lpcm(Vec<u8>),
+    Expr(Vec<u8>),
+    /// Integers of `LosslessLpcm` samples
+    LosslessLpcm(Vec<i64>),
+    Mdct {
+        gain: i8,
+        compressed: Vec<u8>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Block {
+    head_absolute_frame: u64,
+    bub_fns: Vec<u8>,
+    foot_relative_frame: u64,
+    samples: BlockSamples,
+}
+
+/// Offsets added to the time variables
+#[derive(Clone, Copy, Debug, Default)]
+struct TimeOffsets {
+    /// `n`
+    lowercase_n: i64,
+    /// `N`
+    uppercase_n: i64,
+    /// `F`
+    uppercase_f: i64,
+}
+
+impl TimeOffsets {
+    fn is_zero(&self) -> bool {
+        self.lowercase_n == 0 && self.uppercase_n == 0 && self.uppercase_f == 0
+    }
+}
+
+fn collect_time_variables(
+    ast: &BubFnsAST,
+    offsets: TimeOffsets,
+    replacements: &mut Vec<(usize, Vec<u8>)>,
+) {
+    match &ast.node {
+        Node::Internal(internal) => {
+            let name_and_offset = match internal.value.0 {
+                BubFnsVariable::LowercaseN => Some(('n', offsets.lowercase_n)),
+                BubFnsVariable::UppercaseN => Some(('N', offsets.uppercase_n)),
+                BubFnsVariable::UppercaseF => Some(('F', offsets.uppercase_f)),
+                _ => None,
+            };
+            match name_and_offset {
+                Some((_, 0)) => (),
+                Some((name, offset)) => replacements.push((
+                    ast.span.start as usize,
+                    format!("({}{:+})", name, offset).into_bytes(),
+                )),
+                None => match &*internal.equal {
+                    Choice::First(first) => {
+                        collect_time_variables(&first.lhs, offsets, replacements);
+                        collect_time_variables(&first.rhs, offsets, replacements);
+                    }
+                    Choice::Second(second) => {
+                        collect_time_variables(&second.0, offsets, replacements)
+                    }
+                },
+            }
+        }
+        Node::Leaf(leaf) => {
+            let bub_fns: Vec<BubFn> = match leaf.as_original() {
+                Some(BubFnsOutput::BubFns(bub_fns)) => bub_fns.clone().into(),
+                Some(BubFnsOutput::BubFn(bub_fn)) => vec![(**bub_fn).clone()],
+                _ => Vec::new(),
+            };
+            for bub_fn in bub_fns {
+                for ast in [
+                    &bub_fn.bub_absolute_coord.0,
+                    &bub_fn.bub_absolute_coord.1,
+                    &bub_fn.bub_absolute_coord.2,
+                    &bub_fn.domain,
+                    &bub_fn.volume,
+                ] {
+                    collect_time_variables(ast, offsets, replacements);
+                }
+            }
+        }
+    }
+}
+
+/// Replace `n`, `N` and `F` with `(n+offset)` and so on,
+/// so that the result is the same after moving frames.
+fn offset_time_variables(
+    input: &[u8],
+    start_variable: &BubFnsVariable,
+    offsets: TimeOffsets,
+) -> Result<Vec<u8>> {
+    if offsets.is_zero() {
+        return Ok(input.to_vec());
+    }
+
+    let ast = parse(input, start_variable).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("cannot parse `{}`", String::from_utf8_lossy(input)),
+        )
+    })?;
+    let mut replacements = Vec::new();
+    collect_time_variables(&ast, offsets, &mut replacements);
+    replacements.sort_by_key(|(start, _)| *start);
+
+    let mut output = Vec::with_capacity(input.len());
+    let mut pos = 0;
+    for (start, replacement) in replacements {
+        output.extend_from_slice(&input[pos..start]);
+        output.extend_from_slice(&replacement);
+        // Variables are a byte.
+        pos = start + 1;
+    }
+    output.extend_from_slice(&input[pos..]);
+
+    if output.len() > u16::MAX as usize {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "size of `{}` must be less than or equal to {}",
+                String::from_utf8_lossy(&output),
+                u16::MAX
+            ),
+        ));
+    }
+
+    Ok(output)
+}
+
+impl Block {
+    fn foot_absolute_frame(&self) -> u64 {
+        self.head_absolute_frame + self.foot_relative_frame - 1
+    }
+
+    fn offset_time_variables(&mut self, offsets: TimeOffsets) -> Result<()> {
+        self.bub_fns = offset_time_variables(&self.bub_fns, &BubFnsVariable::BubFns, offsets)?;
+        if let BlockSamples::Expr(expr) = &mut self.samples {
+            *expr = offset_time_variables(expr, &BubFnsVariable::Sum, offsets)?;
+        }
+
+        Ok(())
+    }
+
+    /// Keep frames from `first` to `last` and set Head at `first`.
+    fn cut(&mut self, first: u64, last: u64, bytes_per_sample: usize) -> Result<()> {
+        let start = (first - self.head_absolute_frame) as usize;
+        let len = (last - first + 1) as usize;
+        if start == 0 && len as u64 == self.foot_relative_frame {
+            return Ok(());
+        }
+
+        self.samples = match &self.samples {
+            BlockSamples::Lpcm(bytes) => BlockSamples::Lpcm(
+                bytes[start * bytes_per_sample..(start + len) * bytes_per_sample].to_vec(),
+            ),
+            BlockSamples::Expr(expr) => BlockSamples::Expr(expr.clone()),
+            BlockSamples::LosslessLpcm(values) => {
+                BlockSamples::LosslessLpcm(values[start..start + len].to_vec())
+            }
+            // Encode again with the same quantization step
+            BlockSamples::Mdct { gain, compressed } => {
+                let samples = mdct::decode(*gain, compressed, self.foot_relative_frame as usize)?;
+                BlockSamples::Mdct {
+                    gain: *gain,
+                    compressed: mdct::encode_with_gain(&samples[start..start + len], *gain),
+                }
+            }
+        };
+        self.offset_time_variables(TimeOffsets {
+            lowercase_n: start as i64,
+            ..Default::default()
+        })?;
+        self.head_absolute_frame = first;
+        self.foot_relative_frame = len as u64;
+
+        Ok(())
+    }
+}
+
+/// Move blocks by `frames` and keep the time variables.
+/// `F` is `uppercase_f` more than after moving.
+fn move_blocks(blocks: &mut [Block], frames: i64, uppercase_f: i64) -> Result<()> {
+    for block in blocks {
+        block.head_absolute_frame = (block.head_absolute_frame as i64 + frames) as u64;
+        block.offset_time_variables(TimeOffsets {
+            uppercase_n: -frames,
+            uppercase_f,
+            ..Default::default()
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Bubble File split into `BubFnsBlock`s to edit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BubBlocks {
+    metadata: BubMetadata,
+    blocks: Vec<Block>,
+}
+
+impl BubBlocks {
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let (metadata, mut crc) = BubMetadata::read(reader)?;
+        let mut blocks = Vec::new();
+        let mut next_head_absolute_frame = metadata.next_head_absolute_frame;
+        while let Some(head_absolute_frame) = next_head_absolute_frame {
+            let head = BubBlockHead::read_and_calc_bytes(reader, &mut crc)?;
+            head.check_frames(head_absolute_frame, metadata.frames)?;
+            let len = head.samples_len()?;
+            let samples =
+                BubBlockSamples::read_and_calc_bytes(reader, &metadata.bub_sample_kind, &mut crc)?;
+            let payload_size =
+                samples.payload_size(metadata.lpcm_kind, head.foot_relative_frame)?;
+            let payload = read_payload_and_calc_bytes(reader, payload_size, &mut crc)?;
+            read_crc(reader, &mut crc)?;
+
+            let samples = match samples {
+                BubBlockSamples::Lpcm => BlockSamples::Lpcm(payload),
+                BubBlockSamples::Expr(expr) => BlockSamples::Expr(expr),
+                BubBlockSamples::LosslessLpcm {
+                    predictor_order, ..
+                } => BlockSamples::LosslessLpcm(lossless::decode(predictor_order, &payload, len)?),
+                BubBlockSamples::Mdct { gain, .. } => BlockSamples::Mdct {
+                    gain,
+                    compressed: payload,
+                },
+            };
+            next_head_absolute_frame = head.next_head_absolute_frame(head_absolute_frame);
+            blocks.push(Block {
+                head_absolute_frame,
+                bub_fns: head.bub_fns,
+                foot_relative_frame: head.foot_relative_frame,
+                samples,
+            });
+        }
+        Ok(Self { metadata, blocks })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut metadata = self.metadata.clone();
+        // First Head Absolute Frame
+        metadata.head_absolute_frame = self
+            .blocks
+            .first()
+            .map_or(0, |block| block.head_absolute_frame);
+        let mut crc = metadata.write(writer)?;
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            let next_head_relative_frame = self.blocks.get(i + 1).map_or(0, |next_block| {
+                next_block.head_absolute_frame - block.head_absolute_frame + 1
+            });
+
+            BubBlockHead::write_and_calc_bytes(
+                writer,
+                &block.bub_fns,
+                block.foot_relative_frame,
+                next_head_relative_frame,
+                &mut crc,
+            )?;
+
+            let lossless_lpcm;
+            let (samples, payload) = match &block.samples {
+                BlockSamples::Lpcm(bytes) => (BubBlockSamples::Lpcm, &bytes[..]),
+                BlockSamples::Expr(expr) => (BubBlockSamples::Expr(expr.clone()), &[][..]),
+                BlockSamples::LosslessLpcm(values) => {
+                    lossless_lpcm = lossless::encode(values);
+                    let (predictor_order, compressed) = &lossless_lpcm;
+                    (
+                        BubBlockSamples::LosslessLpcm {
+                            predictor_order: *predictor_order,
+                            compressed_size: compressed.len() as u64,
+                        },
+                        &compressed[..],
+                    )
+                }
+                BlockSamples::Mdct { gain, compressed } => (
+                    BubBlockSamples::Mdct {
+                        gain: *gain,
+                        compressed_size: compressed.len() as u64,
+                    },
+                    &compressed[..],
+                ),
+            };
+            samples.write_and_calc_bytes(writer, &mut crc)?;
+            write_payload_and_calc_bytes(writer, payload, &mut crc)?;
+            write_crc(writer, &mut crc)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn metadata(&self) -> &BubMetadata {
+        &self.metadata
+    }
+
+    /// Keep frames in `range` of absolute frames, which start from 1.
+    pub fn trim(&mut self, range: RangeInclusive<u64>) -> Result<()> {
+        let (start, end) = (*range.start(), *range.end());
+        let frames = self.metadata.frames();
+        if start == 0 || end < start || frames < end {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("cannot trim {}..={} of {} frames", start, end, frames),
+            ));
+        }
+
+        let bytes_per_sample = self.metadata.lpcm_kind.bits_per_sample() as usize / 8;
+        let mut blocks = Vec::new();
+        for mut block in std::mem::take(&mut self.blocks) {
+            let first = block.head_absolute_frame.max(start);
+            let last = block.foot_absolute_frame().min(end);
+            if first <= last {
+                block.cut(first, last, bytes_per_sample)?;
+                blocks.push(block);
+            }
+        }
+
+        self.metadata.frames = end - start + 1;
+        move_blocks(
+            &mut blocks,
+            1 - start as i64,
+            (frames - self.metadata.frames) as i64,
+        )?;
+        self.blocks = blocks;
+
+        Ok(())
+    }
+
+    /// Split into frames before `frame` and frames from `frame`.
+    pub fn split(self, frame: u64) -> Result<(Self, Self)> {
+        let frames = self.metadata.frames();
+        if frame <= 1 || frames < frame {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("cannot split {} frames at {}", frames, frame),
+            ));
+        }
+
+        let mut former = self.clone();
+        former.trim(1..=frame - 1)?;
+        let mut latter = self;
+        latter.trim(frame..=frames)?;
+
+        Ok((former, latter))
+    }
+
+    /// Append `other` which has the same `LpcmKind`, `samples_per_sec` and `BubSampleKind`.
+    pub fn concat(&mut self, other: Self) -> Result<()> {
+        is_equal(
+            ErrorKind::Other,
+            self.metadata.lpcm_kind,
+            other.metadata.lpcm_kind,
+        )?;
+        if self.metadata.samples_per_sec != other.metadata.samples_per_sec {
+            return Err(expected_and_found_error(
+                ErrorKind::Other,
+                self.metadata.samples_per_sec,
+                other.metadata.samples_per_sec,
+            ));
+        }
+        if self.metadata.bub_sample_kind.to_u8() != other.metadata.bub_sample_kind.to_u8() {
+            return Err(expected_and_found_error(
+                ErrorKind::Other,
+                &self.metadata.bub_sample_kind,
+                &other.metadata.bub_sample_kind,
+            ));
+        }
+
+        let frames = self.metadata.frames() as i64;
+        let other_frames = other.metadata.frames() as i64;
+        move_blocks(&mut self.blocks, 0, -other_frames)?;
+        let mut other_blocks = other.blocks;
+        move_blocks(&mut other_blocks, frames, -frames)?;
+        self.blocks.append(&mut other_blocks);
+        self.metadata.frames += other.metadata.frames;
+
+        Ok(())
+    }
+
+    /// Insert `frames` frames of silence at the start.
+    pub fn insert_leading_silence(&mut self, frames: u64) -> Result<()> {
+        move_blocks(&mut self.blocks, frames as i64, -(frames as i64))?;
+        self.metadata.frames += frames;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubReader, BubSampleKind, BubWriter};
+    use crate::LpcmKind;
+
+    fn render(bytes: &[u8]) -> Result<Vec<f64>> {
+        let bub_reader = BubReader::new(bytes, vec![(0.0, 0.0, 0.0).into()])?;
+        let bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_f64_le()?;
+        bub_frame_reader
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect()
+    }
+
+    fn write(bub_blocks: &BubBlocks) -> Result<Vec<u8>> {
+        let mut v = Vec::new();
+        bub_blocks.write(&mut v)?;
+        Ok(v)
+    }
+
+    fn lpcm_bub(bub_sample_kind: BubSampleKind) -> Result<Vec<u8>> {
+        let metadata = BubMetadata::new(
+            9,
+            2,
+            48000.0,
+            LpcmKind::F64LE,
+            bub_sample_kind,
+            String::from("Edit"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f64_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 N>=3 n*N/F",
+            next_head_relative_frame: Some(5),
+            samples: vec![1.0, 0.5, -0.5],
+        })?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 n 0==0 1",
+            next_head_relative_frame: None,
+            samples: vec![0.25, 0.125, 1.0],
+        })?;
+        Ok(bub_frame_writer.inner)
+    }
+
+    #[test]
+    fn offset_time_variables() -> Result<()> {
+        let offsets = TimeOffsets {
+            lowercase_n: 2,
+            uppercase_n: -3,
+            uppercase_f: 0,
+        };
+        assert_eq!(
+            super::offset_time_variables(
+                b"n 0 sin(N) n<F 1 1 2 3 0==0 n",
+                &BubFnsVariable::BubFns,
+                offsets
+            )?,
+            b"(n+2) 0 sin((N-3)) (n+2)<F 1 1 2 3 0==0 (n+2)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_and_write() -> Result<()> {
+        for v in [
+            lpcm_bub(BubSampleKind::Lpcm)?,
+            std::fs::read("tests/expr_test.bub")?,
+        ] {
+            assert_eq!(write(&BubBlocks::read(&mut &v[..])?)?, v);
+        }
+
+        // Sizes which can't be written
+        let mut bub_blocks = BubBlocks::read(&mut &lpcm_bub(BubSampleKind::Lpcm)?[..])?;
+        bub_blocks.blocks[0].bub_fns = vec![b' '; u16::MAX as usize + 1];
+        let e = bub_blocks.write(&mut Vec::new()).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+        // Blocks out of frames
+        let mut v = lpcm_bub(BubSampleKind::Lpcm)?;
+        let metadata_len = v.len() - (2 + 16 + 8 + 8 + 3 * 8 + 4) - (2 + 12 + 8 + 8 + 3 * 8 + 4);
+        let foot_offset = metadata_len + 2 + 16;
+        v[foot_offset..foot_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let e = BubBlocks::read(&mut &v[..]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert!(e.to_string().contains("frames are left"), "{}", e);
+
+        Ok(())
+    }
+
+    #[test]
+    fn trim() -> Result<()> {
+        for v in [
+            lpcm_bub(BubSampleKind::Lpcm)?,
+            lpcm_bub(BubSampleKind::LosslessLpcm)?,
+            std::fs::read("tests/expr_test.bub")?,
+        ] {
+            let frames = render(&v)?;
+
+            let mut bub_blocks = BubBlocks::read(&mut &v[..])?;
+            bub_blocks.trim(3..=7)?;
+            assert_eq!(bub_blocks.metadata().frames(), 5);
+            assert_eq!(render(&write(&bub_blocks)?)?, frames[2..7]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn trim_mdct() -> Result<()> {
+        let v = lpcm_bub(BubSampleKind::Mdct)?;
+        let frames = render(&v)?;
+
+        let mut bub_blocks = BubBlocks::read(&mut &v[..])?;
+        bub_blocks.trim(2..=8)?;
+        for (sample, expect) in render(&write(&bub_blocks)?)?.iter().zip(&frames[1..8]) {
+            assert!((sample - expect).abs() < 0.01);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_and_concat() -> Result<()> {
+        let v = lpcm_bub(BubSampleKind::Lpcm)?;
+        let frames = render(&v)?;
+
+        let bub_blocks = BubBlocks::read(&mut &v[..])?;
+        let (mut former, latter) = bub_blocks.split(4)?;
+        assert_eq!(render(&write(&former)?)?, frames[..3]);
+        assert_eq!(render(&write(&latter)?)?, frames[3..]);
+
+        former.concat(latter)?;
+        assert_eq!(render(&write(&former)?)?, frames);
+
+        let expr = BubBlocks::read(&mut &std::fs::read("tests/expr_test.bub")?[..])?;
+        assert!(former.concat(expr).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn insert_leading_silence() -> Result<()> {
+        let v = lpcm_bub(BubSampleKind::Lpcm)?;
+        let frames = render(&v)?;
+
+        let mut bub_blocks = BubBlocks::read(&mut &v[..])?;
+        bub_blocks.insert_leading_silence(3)?;
+        assert_eq!(
+            render(&write(&bub_blocks)?)?,
+            [&[0.0; 3], &frames[..]].concat()
+        );
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::BubFnsCoord;
+use mpl::choices::Choice;
 use mpl::span::StartAndLenSpan;
-use mpl::trees::AST;
+use mpl::trees::{Node, AST};
 
 pub use self::interpreter::BubFnsInterpreter;
 pub use self::output::BubFnsOutput;
@@ -25,6 +26,34 @@ pub struct BubFn {
     pub volume: BubFnsAST,
 }
 
+impl BubFn {
+    /// Whether the domain or the volume uses `X`, `Y`, `Z`, `x`, `y` or `z`.
+    pub fn uses_speaker_coords(&self) -> bool {
+        uses_speaker_coords(&self.domain) || uses_speaker_coords(&self.volume)
+    }
+}
+
+/// Whether `ast` uses `X`, `Y`, `Z`, `x`, `y` or `z`.
+pub(crate) fn uses_speaker_coords(ast: &BubFnsAST) -> bool {
+    match &ast.node {
+        Node::Internal(internal) => match internal.value.0 {
+            BubFnsVariable::UppercaseX
+            | BubFnsVariable::UppercaseY
+            | BubFnsVariable::UppercaseZ
+            | BubFnsVariable::LowercaseX
+            | BubFnsVariable::LowercaseY
+            | BubFnsVariable::LowercaseZ => true,
+            _ => match &*internal.equal {
+                Choice::First(first) => {
+                    uses_speaker_coords(&first.lhs) || uses_speaker_coords(&first.rhs)
+                }
+                Choice::Second(second) => uses_speaker_coords(&second.0),
+            },
+        },
+        Node::Leaf(_) => false,
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct BubFns(Vec<BubFn>);
 
@@ -57,34 +86,17 @@ impl BubFns {
         frames: f64,
         samples_per_sec: f64,
     ) -> Option<Vec<(f64, BubFnsInterpreter)>> {
-        let mut volume_and_interpreter_vec = Vec::new();
-        for bub_fn in self.0.iter() {
-            let bub_absolute_coord = BubFnsCoord::default();
-            let mut interpreter = BubFnsInterpreter::new(
+        let volume_and_interpreter_vec: Vec<_> = self
+            .to_volume_of_each(
                 speaker_absolute_coord,
-                bub_absolute_coord,
                 absolute_frame,
                 relative_frame,
                 frames,
                 samples_per_sec,
-            );
-
-            interpreter.lowercase.x = interpreter.uppercase.x
-                - interpreter.eval_sum(&bub_fn.bub_absolute_coord.0).unwrap();
-            interpreter.lowercase.y = interpreter.uppercase.y
-                - interpreter.eval_sum(&bub_fn.bub_absolute_coord.1).unwrap();
-            interpreter.lowercase.z = interpreter.uppercase.z
-                - interpreter.eval_sum(&bub_fn.bub_absolute_coord.2).unwrap();
-
-            let domain = interpreter.eval_or_or_expr(&bub_fn.domain).unwrap();
-
-            if domain {
-                let volume = interpreter.eval_sum(&bub_fn.volume).unwrap();
-                if volume != 0.0 {
-                    volume_and_interpreter_vec.push((volume, interpreter));
-                }
-            }
-        }
+            )
+            .into_iter()
+            .filter(|(volume, _)| *volume != 0.0)
+            .collect();
 
         if volume_and_interpreter_vec.is_empty() {
             None
@@ -92,4 +104,92 @@ impl BubFns {
             Some(volume_and_interpreter_vec)
         }
     }
+
+    /// Evaluate each `BubFn` at the speaker, in the order of `BubFn`s.
+    /// Volume is 0.0 if the speaker is out of the domain.
+    pub fn to_volume_of_each(
+        &self,
+        speaker_absolute_coord: BubFnsCoord,
+        absolute_frame: f64,
+        relative_frame: f64,
+        frames: f64,
+        samples_per_sec: f64,
+    ) -> Vec<(f64, BubFnsInterpreter)> {
+        self.0
+            .iter()
+            .map(|bub_fn| {
+                let bub_absolute_coord = BubFnsCoord::default();
+                let mut interpreter = BubFnsInterpreter::new(
+                    speaker_absolute_coord,
+                    bub_absolute_coord,
+                    absolute_frame,
+                    relative_frame,
+                    frames,
+                    samples_per_sec,
+                );
+
+                interpreter.lowercase.x = interpreter.uppercase.x
+                    - interpreter.eval_sum(&bub_fn.bub_absolute_coord.0).unwrap();
+                interpreter.lowercase.y = interpreter.uppercase.y
+                    - interpreter.eval_sum(&bub_fn.bub_absolute_coord.1).unwrap();
+                interpreter.lowercase.z = interpreter.uppercase.z
+                    - interpreter.eval_sum(&bub_fn.bub_absolute_coord.2).unwrap();
+
+                let volume = if interpreter.eval_or_or_expr(&bub_fn.domain).unwrap() {
+                    interpreter.eval_sum(&bub_fn.volume).unwrap()
+                } else {
+                    0.0
+                };
+
+                (volume, interpreter)
+            })
+            .collect()
+    }
+
+    /// Evaluate each `BubFn` at its own bubble absolute coordinates instead of a speaker,
+    /// in the order of `BubFn`s.
+    /// Bubble absolute coordinates are evaluated with the speaker at (0, 0, 0),
+    /// and `uppercase` of the interpreter is the result, with `lowercase` at (0, 0, 0).
+    /// Volume is 0.0 if the coordinates are out of the domain, and `None` if the domain or
+    /// the volume uses speaker coordinates, since they depend on each speaker.
+    pub fn to_bub_absolute_coord_and_volume(
+        &self,
+        absolute_frame: f64,
+        relative_frame: f64,
+        frames: f64,
+        samples_per_sec: f64,
+    ) -> Vec<(Option<f64>, BubFnsInterpreter)> {
+        self.0
+            .iter()
+            .map(|bub_fn| {
+                let mut interpreter = BubFnsInterpreter::new(
+                    BubFnsCoord::default(),
+                    BubFnsCoord::default(),
+                    absolute_frame,
+                    relative_frame,
+                    frames,
+                    samples_per_sec,
+                );
+
+                let bub_absolute_coord: BubFnsCoord = (
+                    interpreter.eval_sum(&bub_fn.bub_absolute_coord.0).unwrap(),
+                    interpreter.eval_sum(&bub_fn.bub_absolute_coord.1).unwrap(),
+                    interpreter.eval_sum(&bub_fn.bub_absolute_coord.2).unwrap(),
+                )
+                    .into();
+                interpreter.uppercase = bub_absolute_coord;
+                interpreter.lowercase = BubFnsCoord::default();
+
+                let volume = if bub_fn.uses_speaker_coords() {
+                    None
+                } else if interpreter.eval_or_or_expr(&bub_fn.domain).unwrap() {
+                    Some(interpreter.eval_sum(&bub_fn.volume).unwrap())
+                } else {
+                    Some(0.0)
+                };
+
+                (volume, interpreter)
+            })
+            .collect()
+    }
 }
@@ -1,11 +1,15 @@
-use crate::io::{ReadExt, WriteExt};
-use crate::Rgb;
+use crate::io::{Error, ErrorKind, Read, ReadExt, Result, Write, WriteExt};
+use crate::{Rgb, CRC_32K_4_2};
+use alloc::format;
 use mycrc::CRC;
-use std::io::{Read, Result, Write};
+
+/// Spec version from which the Bubble ID has a color
+pub const RGB_SPEC_VERSION: u8 = 3;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct BubID {
     pub id: u128,
+    /// Color of the bubble in `OaoSpaces`. Written from `RGB_SPEC_VERSION`.
     pub rgb: Option<Rgb>,
 }
 
@@ -14,6 +18,7 @@ impl BubID {
         Self { id, rgb }
     }
 
+    /// Read the ID without the color, which is before `RGB_SPEC_VERSION`.
     pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
         Ok(Self {
             id: reader.read_le()?,
@@ -27,10 +32,85 @@ impl BubID {
         })
     }
 
+    pub fn read_with_spec_version<R: Read>(reader: &mut R, spec_version: u8) -> Result<Self> {
+        let mut crc = CRC_32K_4_2;
+        Self::read_with_spec_version_and_calc_bytes(reader, spec_version, &mut crc)
+    }
+    pub fn read_with_spec_version_and_calc_bytes<R: Read>(
+        reader: &mut R,
+        spec_version: u8,
+        crc: &mut CRC<u32>,
+    ) -> Result<Self> {
+        let id = reader.read_le_and_calc_bytes(crc)?;
+        let rgb = if spec_version >= RGB_SPEC_VERSION {
+            match reader.read_le_and_calc_bytes::<u8>(crc)? {
+                0 => None,
+                1 => Some(Rgb {
+                    red: reader.read_le_and_calc_bytes(crc)?,
+                    green: reader.read_le_and_calc_bytes(crc)?,
+                    blue: reader.read_le_and_calc_bytes(crc)?,
+                }),
+                value => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("invalid color kind {}", value),
+                    ))
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(Self { id, rgb })
+    }
+
+    /// Write the ID without the color, which is before `RGB_SPEC_VERSION`.
     pub fn write<W: Write>(self, writer: &mut W) -> Result<()> {
         writer.write_le(self.id)
     }
     pub fn write_and_calc_bytes<W: Write>(self, writer: &mut W, crc: &mut CRC<u32>) -> Result<()> {
         writer.write_le_and_calc_bytes(self.id, crc)
     }
+
+    /// Returns an error if the color can't be written in `spec_version`.
+    pub(crate) fn check_spec_version(&self, spec_version: u8) -> Result<()> {
+        if spec_version < RGB_SPEC_VERSION && self.rgb.is_some() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "color needs spec version {} or later, found {}",
+                    RGB_SPEC_VERSION, spec_version
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn write_with_spec_version<W: Write>(self, writer: &mut W, spec_version: u8) -> Result<()> {
+        let mut crc = CRC_32K_4_2;
+        self.write_with_spec_version_and_calc_bytes(writer, spec_version, &mut crc)
+    }
+    pub fn write_with_spec_version_and_calc_bytes<W: Write>(
+        self,
+        writer: &mut W,
+        spec_version: u8,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        self.check_spec_version(spec_version)?;
+        writer.write_le_and_calc_bytes(self.id, crc)?;
+        if spec_version >= RGB_SPEC_VERSION {
+            match self.rgb {
+                Some(rgb) => {
+                    writer.write_le_and_calc_bytes(1u8, crc)?;
+                    writer.write_le_and_calc_bytes(rgb.red, crc)?;
+                    writer.write_le_and_calc_bytes(rgb.green, crc)?;
+                    writer.write_le_and_calc_bytes(rgb.blue, crc)?;
+                }
+                None => writer.write_le_and_calc_bytes(0u8, crc)?,
+            }
+        }
+
+        Ok(())
+    }
 }
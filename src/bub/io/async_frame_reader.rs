@@ -0,0 +1,219 @@
+use crate::bub::{BubFrameReader, BubMetadata};
+use crate::io::async_ext::fill;
+use crate::{BubFnsCoord, Frame, OaoSpaces, Sample};
+use futures_util::io::AsyncRead;
+use mycrc::CRC;
+use std::collections::VecDeque;
+use std::io::Result;
+
+/// `BubFrameReader` over `AsyncRead`.
+/// Bytes of each frame are read into `bub_frame_reader.inner` before the frame is parsed.
+pub struct AsyncBubFrameReader<R: AsyncRead + Unpin, S: Sample> {
+    pub inner: R,
+    pub bub_frame_reader: BubFrameReader<VecDeque<u8>, S>,
+}
+
+impl<R: AsyncRead + Unpin, S: Sample> AsyncBubFrameReader<R, S> {
+    /// `metadata_and_crc` is returned by `BubMetadata::read_async`.
+    pub fn new(
+        inner: R,
+        metadata_and_crc: (BubMetadata, CRC<u32>),
+        speakers_absolute_coord: Vec<BubFnsCoord>,
+        oao_spaces: Option<OaoSpaces>,
+    ) -> Self {
+        Self {
+            inner,
+            bub_frame_reader: BubFrameReader::new(
+                VecDeque::new(),
+                metadata_and_crc,
+                speakers_absolute_coord,
+                oao_spaces,
+            ),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns `None` after the last frame like `Iterator::next`.
+    pub async fn next_frame(&mut self) -> Option<Result<Frame<S>>> {
+        if self.bub_frame_reader.metadata.frames() <= self.bub_frame_reader.pos {
+            return None;
+        }
+
+        loop {
+            self.bub_frame_reader.inner.make_contiguous();
+            let buffered = self.bub_frame_reader.inner.as_slices().0;
+            let (len, is_known) = match self.bub_frame_reader.next_sample_size(buffered) {
+                Ok(len_and_is_known) => len_and_is_known,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Err(e) = fill(&mut self.inner, &mut self.bub_frame_reader.inner, len).await {
+                return Some(Err(e));
+            }
+            if is_known {
+                break;
+            }
+        }
+
+        self.bub_frame_reader.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubReader, BubSampleKind, BubWriter};
+    use crate::LpcmKind;
+    use futures_executor::block_on;
+    use futures_util::io::BufReader;
+    use std::io::ErrorKind;
+
+    fn assert_same_frames(v: &[u8], speakers_absolute_coord: Vec<BubFnsCoord>) -> Result<()> {
+        let expects = BubReader::new(v, speakers_absolute_coord.clone())?
+            .into_bub_frame_reader_kind(None)
+            .into_f32_le()?
+            .collect::<Result<Vec<_>>>()?;
+
+        block_on(async {
+            // The metadata is parsed for each buffer.
+            let mut inner = BufReader::with_capacity(7, v);
+            let metadata_and_crc = BubMetadata::read_async(&mut inner).await?;
+            let mut async_bub_frame_reader = AsyncBubFrameReader::<_, f32>::new(
+                inner,
+                metadata_and_crc,
+                speakers_absolute_coord,
+                None,
+            );
+            for expect in expects {
+                assert_eq!(async_bub_frame_reader.next_frame().await.unwrap()?, expect);
+                assert!(async_bub_frame_reader.bub_frame_reader.inner.is_empty());
+            }
+            assert!(async_bub_frame_reader.next_frame().await.is_none());
+            let inner = async_bub_frame_reader.into_inner();
+            assert!(inner.buffer().is_empty() && inner.get_ref().is_empty());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn read_frames() -> Result<()> {
+        let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into(), (3.0, 0.0, 0.0).into()];
+        assert_same_frames(
+            &std::fs::read("tests/lpcm_test.bub")?,
+            speakers_absolute_coord.clone(),
+        )?;
+
+        for bub_sample_kind in [BubSampleKind::LosslessLpcm, BubSampleKind::Mdct] {
+            let metadata = BubMetadata::new(
+                8,
+                1,
+                48000.0,
+                LpcmKind::F32LE,
+                bub_sample_kind,
+                String::from("Async"),
+            );
+            let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+            let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+                bub_fns: b"0 0 0 X<3 1",
+                next_head_relative_frame: Some(5),
+                samples: vec![0.5, 0.25, 0.0, -0.25],
+            })?;
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+                bub_fns: b"0 0 0 X<3 1",
+                next_head_relative_frame: None,
+                samples: vec![-0.5, 1.0],
+            })?;
+            assert_same_frames(&bub_frame_writer.inner, speakers_absolute_coord.clone())?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_expr_frames() -> Result<()> {
+        let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into(), (0.0, 0.0, 1.0).into()];
+        let v = std::fs::read("tests/expr_test.bub")?;
+        let expects = BubReader::new(&v[..], speakers_absolute_coord.clone())?
+            .into_bub_frame_reader_kind(None)
+            .into_f64_le()?
+            .collect::<Result<Vec<_>>>()?;
+
+        block_on(async {
+            let mut inner = &v[..];
+            let metadata_and_crc = BubMetadata::read_async(&mut inner).await?;
+            let mut async_bub_frame_reader = AsyncBubFrameReader::<_, f64>::new(
+                inner,
+                metadata_and_crc,
+                speakers_absolute_coord,
+                None,
+            );
+            for expect in expects {
+                assert_eq!(async_bub_frame_reader.next_frame().await.unwrap()?, expect);
+            }
+            assert!(async_bub_frame_reader.next_frame().await.is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn too_large_blocks() -> Result<()> {
+        let bub_fns = b"0 0 0 X<3 1";
+        let metadata = BubMetadata::new(
+            3,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::LosslessLpcm,
+            String::from("Large"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let metadata_len = bub_writer.inner.len();
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns,
+            next_head_relative_frame: None,
+            samples: vec![0.5, 0.25, 0.125],
+        })?;
+        let v = bub_frame_writer.inner;
+        // Bubble Functions Size and Bubble Functions
+        let foot_offset = metadata_len + 2 + bub_fns.len();
+        // Foot and Next Head Relative Frame, Predictor Order
+        let compressed_size_offset = foot_offset + 8 + 8 + 1;
+
+        let next_frame_error = |offset: usize, value: u64| -> Result<ErrorKind> {
+            let mut broken = v.clone();
+            broken[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            block_on(async {
+                let mut inner = &broken[..];
+                let metadata_and_crc = BubMetadata::read_async(&mut inner).await?;
+                let mut async_bub_frame_reader =
+                    AsyncBubFrameReader::<_, f32>::new(inner, metadata_and_crc, Vec::new(), None);
+                Ok(async_bub_frame_reader
+                    .next_frame()
+                    .await
+                    .unwrap()
+                    .unwrap_err()
+                    .kind())
+            })
+        };
+        assert_eq!(
+            next_frame_error(foot_offset, u64::MAX)?,
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            next_frame_error(compressed_size_offset, u64::MAX)?,
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            next_frame_error(compressed_size_offset, 1 << 40)?,
+            ErrorKind::UnexpectedEof
+        );
+
+        Ok(())
+    }
+}
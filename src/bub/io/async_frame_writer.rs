@@ -0,0 +1,102 @@
+use crate::bub::{BubFnsBlock, BubFrameWriter, BubMetadata};
+use crate::io::async_ext::flush_buf;
+use crate::Sample;
+use futures_util::io::{AsyncWrite, AsyncWriteExt};
+use mycrc::CRC;
+use std::io::Result;
+
+/// `BubFrameWriter` over `AsyncWrite`.
+/// Each block is written into `bub_frame_writer.inner` and then to `inner`.
+pub struct AsyncBubFrameWriter<W: AsyncWrite + Unpin, S: Sample> {
+    pub inner: W,
+    pub bub_frame_writer: BubFrameWriter<Vec<u8>, S>,
+}
+
+impl<W: AsyncWrite + Unpin, S: Sample> AsyncBubFrameWriter<W, S> {
+    /// `metadata_and_crc` is from `BubMetadata::write_async`.
+    pub fn new(inner: W, metadata_and_crc: (BubMetadata, CRC<u32>)) -> Self {
+        Self {
+            inner,
+            bub_frame_writer: BubFrameWriter::new(Vec::new(), metadata_and_crc),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    pub async fn write_head_to_less_than_next_head_or_ended(
+        &mut self,
+        bub_fns_block: BubFnsBlock<'_, S>,
+    ) -> Result<()> {
+        let result = self
+            .bub_frame_writer
+            .write_head_to_less_than_next_head_or_ended(bub_fns_block);
+        flush_buf(&mut self.inner, &mut self.bub_frame_writer.inner).await?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubSampleKind, BubWriter};
+    use crate::LpcmKind;
+    use futures_executor::block_on;
+
+    #[test]
+    fn write_frames() -> Result<()> {
+        let metadata = BubMetadata::new(
+            8,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::Lpcm,
+            String::from("Async"),
+        );
+        let blocks = || {
+            vec![
+                BubFnsBlock::Lpcm {
+                    bub_fns: b"0 0 0 X<3 1",
+                    next_head_relative_frame: Some(4),
+                    samples: vec![0.5, 0.25],
+                },
+                BubFnsBlock::Expr {
+                    bub_fns: b"0 0 0 X<3 1",
+                    foot_relative_frame: 5,
+                    next_head_relative_frame: None,
+                    expression: b"0.1*n",
+                },
+            ]
+        };
+
+        let bub_writer = BubWriter::new(Vec::new(), metadata.clone())?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        for block in blocks() {
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(block)?;
+        }
+
+        let v = block_on(async {
+            let mut inner = Vec::new();
+            let crc = metadata.write_async(&mut inner).await?;
+            let mut async_bub_frame_writer =
+                AsyncBubFrameWriter::<_, f32>::new(inner, (metadata, crc));
+            for block in blocks() {
+                async_bub_frame_writer
+                    .write_head_to_less_than_next_head_or_ended(block)
+                    .await?;
+            }
+            async_bub_frame_writer.flush().await?;
+            assert!(async_bub_frame_writer.bub_frame_writer.inner.is_empty());
+
+            Result::Ok(async_bub_frame_writer.into_inner())
+        })?;
+        assert_eq!(v, bub_frame_writer.inner);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,594 @@
+use crate::bub::codec::{lossless, mdct};
+use crate::bub::{BubMetadata, BubSampleKind};
+use crate::io::{Error, ErrorKind, Read, ReadExt, Result};
+#[cfg(feature = "std")]
+use crate::io::{Write, WriteExt};
+#[cfg(feature = "std")]
+use crate::utils::u16_len;
+use crate::utils::{check_crc, read_crc};
+use crate::{LpcmKind, Sample};
+use alloc::format;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use mycrc::CRC;
+#[cfg(feature = "std")]
+use std::io::{Seek, SeekFrom};
+
+/// Samples of a block, without LPCM or compressed bytes
+#[derive(Clone, Debug, PartialEq)]
+pub enum BubBlockSamples {
+    Lpcm,
+    /// Source of the expression
+    Expr(Vec<u8>),
+    LosslessLpcm {
+        predictor_order: u8,
+        compressed_size: u64,
+    },
+    Mdct {
+        gain: i8,
+        compressed_size: u64,
+    },
+}
+
+/// Summary of a block from its head
+#[derive(Clone, Debug, PartialEq)]
+pub struct BubBlockSummary {
+    pub head_absolute_frame: u64,
+    pub foot_absolute_frame: u64,
+    pub next_head_absolute_frame: Option<u64>,
+    /// Source of the Bubble Functions
+    pub bub_fns: Vec<u8>,
+    pub samples: BubBlockSamples,
+}
+
+/// Head of a block, before its samples
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BubBlockHead {
+    /// Source of the Bubble Functions
+    pub(crate) bub_fns: Vec<u8>,
+    pub(crate) foot_relative_frame: u64,
+    pub(crate) next_head_relative_frame: u64,
+}
+
+impl BubBlockHead {
+    pub(crate) fn read_and_calc_bytes<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<Self> {
+        let bub_fns_size: u16 = reader.read_le_and_calc_bytes(crc)?;
+        let bub_fns = reader.read_vec_for_and_calc_bytes(bub_fns_size as usize, crc)?;
+        let foot_relative_frame = reader.read_le_and_calc_bytes(crc)?;
+        let next_head_relative_frame = reader.read_le_and_calc_bytes(crc)?;
+
+        Ok(Self {
+            bub_fns,
+            foot_relative_frame,
+            next_head_relative_frame,
+        })
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn write_and_calc_bytes<W: Write>(
+        writer: &mut W,
+        bub_fns: &[u8],
+        foot_relative_frame: u64,
+        next_head_relative_frame: u64,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        let bub_fns_size = u16_len("size of bubble functions", bub_fns.len())?;
+        writer.write_le_and_calc_bytes(bub_fns_size, crc)?;
+        write_payload_and_calc_bytes(writer, bub_fns, crc)?;
+        writer.write_le_and_calc_bytes(foot_relative_frame, crc)?;
+        writer.write_le_and_calc_bytes(next_head_relative_frame, crc)
+    }
+
+    /// Returns an error unless the block has frames and ends in `frames` of the metadata.
+    pub(crate) fn check_frames(&self, head_absolute_frame: u64, frames: u64) -> Result<()> {
+        if self.foot_relative_frame == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("block at frame {} has no frames", head_absolute_frame),
+            ));
+        }
+        let max_foot_relative_frame = frames.saturating_sub(head_absolute_frame.saturating_sub(1));
+        if max_foot_relative_frame < self.foot_relative_frame {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "block at frame {} has {} frames, but only {} frames are left",
+                    head_absolute_frame, self.foot_relative_frame, max_foot_relative_frame
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Number of samples to decode
+    pub(crate) fn samples_len(&self) -> Result<usize> {
+        usize::try_from(self.foot_relative_frame).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} frames can't be decoded", self.foot_relative_frame),
+            )
+        })
+    }
+
+    pub(crate) fn foot_absolute_frame(&self, head_absolute_frame: u64) -> u64 {
+        head_absolute_frame.saturating_add(self.foot_relative_frame.max(1) - 1)
+    }
+
+    pub(crate) fn next_head_absolute_frame(&self, head_absolute_frame: u64) -> Option<u64> {
+        match self.next_head_relative_frame {
+            0 => None,
+            relative_frame => Some(relative_frame.saturating_add(head_absolute_frame - 1)),
+        }
+    }
+
+    fn into_summary(self, head_absolute_frame: u64, samples: BubBlockSamples) -> BubBlockSummary {
+        BubBlockSummary {
+            head_absolute_frame,
+            foot_absolute_frame: self.foot_absolute_frame(head_absolute_frame),
+            next_head_absolute_frame: self.next_head_absolute_frame(head_absolute_frame),
+            bub_fns: self.bub_fns,
+            samples,
+        }
+    }
+}
+
+impl BubBlockSamples {
+    /// Read the samples of a block until LPCM or compressed bytes.
+    pub(crate) fn read_and_calc_bytes<R: Read>(
+        reader: &mut R,
+        bub_sample_kind: &BubSampleKind,
+        crc: &mut CRC<u32>,
+    ) -> Result<Self> {
+        Ok(match bub_sample_kind {
+            BubSampleKind::Lpcm => Self::Lpcm,
+            #[cfg(feature = "std")]
+            BubSampleKind::Expr(_) => {
+                let expr_size: u16 = reader.read_le_and_calc_bytes(crc)?;
+                Self::Expr(reader.read_vec_for_and_calc_bytes(expr_size as usize, crc)?)
+            }
+            BubSampleKind::LosslessLpcm => Self::LosslessLpcm {
+                predictor_order: reader.read_le_and_calc_bytes(crc)?,
+                compressed_size: reader.read_le_and_calc_bytes(crc)?,
+            },
+            BubSampleKind::Mdct => Self::Mdct {
+                gain: reader.read_le_and_calc_bytes(crc)?,
+                compressed_size: reader.read_le_and_calc_bytes(crc)?,
+            },
+        })
+    }
+
+    /// Write the samples of a block until LPCM or compressed bytes.
+    #[cfg(feature = "std")]
+    pub(crate) fn write_and_calc_bytes<W: Write>(
+        &self,
+        writer: &mut W,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        match self {
+            Self::Lpcm => Ok(()),
+            Self::Expr(expr) => {
+                let expr_size = u16_len("size of expression", expr.len())?;
+                writer.write_le_and_calc_bytes(expr_size, crc)?;
+                write_payload_and_calc_bytes(writer, expr, crc)
+            }
+            Self::LosslessLpcm {
+                predictor_order,
+                compressed_size,
+            } => {
+                writer.write_le_and_calc_bytes(*predictor_order, crc)?;
+                writer.write_le_and_calc_bytes(*compressed_size, crc)
+            }
+            Self::Mdct {
+                gain,
+                compressed_size,
+            } => {
+                writer.write_le_and_calc_bytes(*gain, crc)?;
+                writer.write_le_and_calc_bytes(*compressed_size, crc)
+            }
+        }
+    }
+
+    /// Number of LPCM or compressed bytes after the samples.
+    pub(crate) fn payload_size(
+        &self,
+        lpcm_kind: LpcmKind,
+        foot_relative_frame: u64,
+    ) -> Result<u64> {
+        match self {
+            Self::Lpcm => foot_relative_frame
+                .checked_mul(lpcm_kind.bits_per_sample() as u64 / 8)
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "{} frames of LPCM samples are too many",
+                            foot_relative_frame
+                        ),
+                    )
+                }),
+            Self::Expr(_) => Ok(0),
+            Self::LosslessLpcm {
+                compressed_size, ..
+            }
+            | Self::Mdct {
+                compressed_size, ..
+            } => Ok(*compressed_size),
+        }
+    }
+
+    /// Decode `len` samples from the LPCM or compressed bytes.
+    /// Expressions have no bytes, so they are empty.
+    fn decode<S: Sample>(
+        &self,
+        mut payload: &[u8],
+        lpcm_kind: LpcmKind,
+        len: usize,
+    ) -> Result<Vec<S>> {
+        Ok(match self {
+            Self::Lpcm => (0..len)
+                .map(|_| S::read_lpcm(&mut payload, lpcm_kind))
+                .collect::<Result<_>>()?,
+            Self::Expr(_) => Vec::new(),
+            Self::LosslessLpcm {
+                predictor_order, ..
+            } => lossless::decode(*predictor_order, payload, len)?
+                .into_iter()
+                .map(|n| lpcm_kind.i64_to_sample(n))
+                .collect(),
+            Self::Mdct { gain, .. } => mdct::decode(*gain, payload, len)?
+                .into_iter()
+                .map(S::from_f64)
+                .collect(),
+        })
+    }
+}
+
+/// Read a block until LPCM or compressed bytes, and check its frames.
+fn read_head_and_samples<R: Read>(
+    reader: &mut R,
+    metadata: &BubMetadata,
+    head_absolute_frame: u64,
+    crc: &mut CRC<u32>,
+) -> Result<(BubBlockHead, BubBlockSamples)> {
+    let head = BubBlockHead::read_and_calc_bytes(reader, crc)?;
+    head.check_frames(head_absolute_frame, metadata.frames)?;
+    let samples = BubBlockSamples::read_and_calc_bytes(reader, &metadata.bub_sample_kind, crc)?;
+
+    Ok((head, samples))
+}
+
+/// Set the frames of `metadata` to the block which has been read.
+fn set_block(metadata: &mut BubMetadata, block: &BubBlockSummary) {
+    metadata.head_absolute_frame = block.head_absolute_frame;
+    metadata.foot_absolute_frame_plus_one = block.foot_absolute_frame + 1;
+    metadata.next_head_absolute_frame = block.next_head_absolute_frame;
+}
+
+/// Read `len` bytes into the CRC.
+/// The bytes are not allocated at once, so a wrong `len` fails at the end of the file.
+pub(crate) fn read_payload_and_calc_bytes<R: Read>(
+    reader: &mut R,
+    len: u64,
+    crc: &mut CRC<u32>,
+) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    let mut buf = [0; 8192];
+    let mut rest = len;
+    while rest != 0 {
+        let buf = &mut buf[..rest.min(8192) as usize];
+        reader.read_exact(buf)?;
+        payload.extend_from_slice(buf);
+        rest -= buf.len() as u64;
+    }
+    crc.calc_bytes(&payload);
+
+    Ok(payload)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn write_payload_and_calc_bytes<W: Write>(
+    writer: &mut W,
+    payload: &[u8],
+    crc: &mut CRC<u32>,
+) -> Result<()> {
+    writer.write_all(payload)?;
+    crc.calc_bytes(payload);
+
+    Ok(())
+}
+
+/// Read `len` bytes into the CRC and discard them.
+pub(crate) fn discard<R: Read>(reader: &mut R, len: u64, crc: &mut CRC<u32>) -> Result<bool> {
+    let mut buf = [0; 8192];
+    let mut rest = len;
+    while rest != 0 {
+        let buf = &mut buf[..rest.min(8192) as usize];
+        reader.read_exact(buf)?;
+        crc.calc_bytes(buf);
+        rest -= buf.len() as u64;
+    }
+
+    Ok(true)
+}
+
+/// Seek `len` bytes forward. The bytes are not added to the CRC.
+#[cfg(feature = "std")]
+fn seek<R: Read + Seek>(reader: &mut R, len: u64, _: &mut CRC<u32>) -> Result<bool> {
+    let len = i64::try_from(len).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} bytes of samples can't be skipped", len),
+        )
+    })?;
+    reader.seek(SeekFrom::Current(len))?;
+
+    Ok(false)
+}
+
+/// Iterator over the blocks of a bubble file, which skips samples.
+///
+/// Created by [`BubReader::blocks`](crate::bub::BubReader::blocks), or
+/// `BubReader::seekable_blocks` with the `std` feature.
+pub struct BubBlockSummaries<R: Read> {
+    pub inner: R,
+    pub metadata: BubMetadata,
+    crc: CRC<u32>,
+    /// Skips bytes, and returns whether they are added to the CRC.
+    skip: fn(&mut R, u64, &mut CRC<u32>) -> Result<bool>,
+}
+
+impl<R: Read> BubBlockSummaries<R> {
+    pub(crate) fn new(inner: R, metadata_and_crc: (BubMetadata, CRC<u32>)) -> Self {
+        Self {
+            inner,
+            metadata: metadata_and_crc.0,
+            crc: metadata_and_crc.1,
+            skip: discard,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_block(&mut self, head_absolute_frame: u64) -> Result<BubBlockSummary> {
+        let crc = &mut self.crc;
+        let (head, samples) =
+            read_head_and_samples(&mut self.inner, &self.metadata, head_absolute_frame, crc)?;
+        let payload_size =
+            samples.payload_size(self.metadata.lpcm_kind, head.foot_relative_frame)?;
+        let is_calculated = (self.skip)(&mut self.inner, payload_size, crc)?;
+
+        // The CRC of skipped bytes can't be checked, but the next block can be.
+        if is_calculated {
+            read_crc(&mut self.inner, crc)?;
+        } else {
+            check_crc(&mut self.inner, crc)?;
+        }
+
+        Ok(head.into_summary(head_absolute_frame, samples))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> BubBlockSummaries<R> {
+    pub(crate) fn new_seekable(inner: R, metadata_and_crc: (BubMetadata, CRC<u32>)) -> Self {
+        Self {
+            skip: seek,
+            ..Self::new(inner, metadata_and_crc)
+        }
+    }
+}
+
+impl<R: Read> Iterator for BubBlockSummaries<R> {
+    type Item = Result<BubBlockSummary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Stops after an error.
+        let head_absolute_frame = self.metadata.next_head_absolute_frame.take()?;
+        let block = self.read_block(head_absolute_frame);
+        if let Ok(block) = &block {
+            set_block(&mut self.metadata, block);
+        }
+
+        Some(block)
+    }
+}
+
+/// Block with decoded samples
+#[derive(Clone, Debug, PartialEq)]
+pub struct BubBlock<S: Sample> {
+    pub summary: BubBlockSummary,
+    /// Samples from the head to the foot of the block. Empty for expressions.
+    pub samples: Vec<S>,
+}
+
+/// Iterator over the blocks of a bubble file, which decodes samples.
+/// Bubble Functions are not applied to the samples.
+///
+/// Created by [`BubReader::decoded_blocks`](crate::bub::BubReader::decoded_blocks).
+pub struct BubBlocks<R: Read, S: Sample> {
+    pub inner: R,
+    pub metadata: BubMetadata,
+    crc: CRC<u32>,
+    _phantom_sample: PhantomData<S>,
+}
+
+impl<R: Read, S: Sample> BubBlocks<R, S> {
+    pub(crate) fn new(inner: R, metadata_and_crc: (BubMetadata, CRC<u32>)) -> Self {
+        Self {
+            inner,
+            metadata: metadata_and_crc.0,
+            crc: metadata_and_crc.1,
+            _phantom_sample: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_block(&mut self, head_absolute_frame: u64) -> Result<BubBlock<S>> {
+        let crc = &mut self.crc;
+        let (head, samples) =
+            read_head_and_samples(&mut self.inner, &self.metadata, head_absolute_frame, crc)?;
+        let lpcm_kind = self.metadata.lpcm_kind;
+        let payload_size = samples.payload_size(lpcm_kind, head.foot_relative_frame)?;
+        let payload = read_payload_and_calc_bytes(&mut self.inner, payload_size, crc)?;
+        read_crc(&mut self.inner, crc)?;
+        let decoded_samples = samples.decode(&payload, lpcm_kind, head.samples_len()?)?;
+
+        Ok(BubBlock {
+            summary: head.into_summary(head_absolute_frame, samples),
+            samples: decoded_samples,
+        })
+    }
+}
+
+impl<R: Read, S: Sample> Iterator for BubBlocks<R, S> {
+    type Item = Result<BubBlock<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Stops after an error.
+        let head_absolute_frame = self.metadata.next_head_absolute_frame.take()?;
+        let block = self.read_block(head_absolute_frame);
+        if let Ok(block) = &block {
+            set_block(&mut self.metadata, &block.summary);
+        }
+
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubReader, BubWriter};
+    use std::io::Cursor;
+
+    fn lpcm() -> Result<Vec<u8>> {
+        let metadata = BubMetadata::new(
+            8,
+            2,
+            48000.0,
+            LpcmKind::I16LE,
+            BubSampleKind::Lpcm,
+            String::from("Blocks"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_i16_le()?;
+        bub_frame_writer.pos = 1;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3 1",
+            next_head_relative_frame: Some(5),
+            samples: vec![0.5, -0.5, 0.25],
+        })?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3 n/F",
+            next_head_relative_frame: None,
+            samples: vec![0.125; 2],
+        })?;
+        Ok(bub_frame_writer.inner)
+    }
+
+    #[test]
+    fn blocks() -> Result<()> {
+        let v = lpcm()?;
+        let expects = [
+            BubBlockSummary {
+                head_absolute_frame: 2,
+                foot_absolute_frame: 4,
+                next_head_absolute_frame: Some(6),
+                bub_fns: b"0 0 0 X<3 1".to_vec(),
+                samples: BubBlockSamples::Lpcm,
+            },
+            BubBlockSummary {
+                head_absolute_frame: 6,
+                foot_absolute_frame: 7,
+                next_head_absolute_frame: None,
+                bub_fns: b"0 0 0 X<3 n/F".to_vec(),
+                samples: BubBlockSamples::Lpcm,
+            },
+        ];
+
+        let blocks = BubReader::new(&v[..], Vec::new())?
+            .blocks()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(blocks, expects);
+        let blocks = BubReader::new(Cursor::new(&v), Vec::new())?
+            .seekable_blocks()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(blocks, expects);
+
+        // A broken sample is found only by reading.
+        let mut broken = v.clone();
+        let len = broken.len();
+        broken[len - 6] ^= 1;
+        let mut blocks = BubReader::new(&broken[..], Vec::new())?.blocks();
+        assert!(blocks.next().unwrap().is_ok());
+        assert_eq!(
+            blocks.next().unwrap().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(blocks.next().is_none());
+        let blocks = BubReader::new(Cursor::new(&broken), Vec::new())?
+            .seekable_blocks()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(blocks, expects);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decoded_blocks() -> Result<()> {
+        let v = lpcm()?;
+        let blocks = BubReader::new(&v[..], Vec::new())?
+            .decoded_blocks::<f32>()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].summary.head_absolute_frame, 2);
+        assert_eq!(blocks[0].samples, [0.5, -0.5, 0.25]);
+        assert_eq!(blocks[1].summary.foot_absolute_frame, 7);
+        assert_eq!(blocks[1].samples, [0.125; 2]);
+
+        // Samples are read, so a broken sample is found.
+        let mut broken = v;
+        let len = broken.len();
+        broken[len - 6] ^= 1;
+        let mut blocks = BubReader::new(&broken[..], Vec::new())?.decoded_blocks::<f32>();
+        assert!(blocks.next().unwrap().is_ok());
+        assert_eq!(
+            blocks.next().unwrap().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(blocks.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn too_large_skip() {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut crc = crate::crc::CRC_32K_4_2;
+        assert_eq!(
+            seek(&mut cursor, u64::MAX, &mut crc).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn expr_blocks() -> Result<()> {
+        let v = std::fs::read("tests/expr_test.bub")?;
+        let blocks = BubReader::new(&v[..], Vec::new())?
+            .blocks()
+            .collect::<Result<Vec<_>>>()?;
+        assert!(!blocks.is_empty());
+        assert!(blocks
+            .iter()
+            .all(|block| matches!(block.samples, BubBlockSamples::Expr(_))));
+
+        Ok(())
+    }
+}
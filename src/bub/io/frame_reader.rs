@@ -1,14 +1,204 @@
 use crate::bub::{
-    functions::{parse, BubFnsAST, BubFnsInterpreter, BubFnsVariable},
+    codec::{lossless, mdct},
+    functions::{parse, BubFns, BubFnsAST, BubFnsInterpreter, BubFnsVariable},
+    io::{read_payload_and_calc_bytes, BubBlockHead},
     BubMetadata, BubSampleKind, BubState,
 };
 use crate::io::ReadExt;
 use crate::utils::read_crc;
 use crate::{BubFnsCoord, Frame, FrameIOKind, FrameReader, OaoSpace, OaoSpaces, Sample};
 use mycrc::CRC;
-use std::io::{Read, Result};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind, Read, Result};
 use std::marker::PhantomData;
 
+#[cfg(feature = "async")]
+use crate::bub::BubBlockSamples;
+#[cfg(feature = "async")]
+use crate::io::async_ext::RecordingSlice;
+
+/// Time variables of Bubble Functions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BubFnsTime {
+    pub(crate) absolute_frame: f64,
+    pub(crate) relative_frame: f64,
+    pub(crate) frames: f64,
+    pub(crate) samples_per_sec: f64,
+}
+
+impl BubFnsTime {
+    fn volume_and_interpreter(
+        &self,
+        bub_fns: &BubFns,
+        speaker_absolute_coord: BubFnsCoord,
+    ) -> Option<Vec<(f64, BubFnsInterpreter)>> {
+        bub_fns.to_volume(
+            speaker_absolute_coord,
+            self.absolute_frame,
+            self.relative_frame,
+            self.frames,
+            self.samples_per_sec,
+        )
+    }
+
+    fn volume_of_each(
+        &self,
+        bub_fns: &BubFns,
+        speaker_absolute_coord: BubFnsCoord,
+    ) -> Vec<(f64, BubFnsInterpreter)> {
+        bub_fns.to_volume_of_each(
+            speaker_absolute_coord,
+            self.absolute_frame,
+            self.relative_frame,
+            self.frames,
+            self.samples_per_sec,
+        )
+    }
+}
+
+/// Add `sample` multiplied by the volumes of `bub_fns` to `frame`.
+pub(crate) fn lpcm_frame<S: Sample>(
+    bub_fns: &BubFns,
+    speakers_absolute_coord: &[BubFnsCoord],
+    time: BubFnsTime,
+    sample: S,
+    frame: &mut Frame<S>,
+) {
+    if sample != S::default() {
+        for (i, speaker_absolute_coord) in speakers_absolute_coord.iter().enumerate() {
+            if let Some(volume_and_interpreter_vec) =
+                time.volume_and_interpreter(bub_fns, *speaker_absolute_coord)
+            {
+                let mut volumes = 0.0;
+                for (volume, _) in volume_and_interpreter_vec {
+                    volumes += volume;
+                }
+                frame.0[i] = sample * S::from_f64(volumes);
+            }
+        }
+    }
+}
+
+/// Add `expr` evaluated in each `BubFn` of `bub_fns` to `frame`.
+pub(crate) fn expr_frame<S: Sample>(
+    bub_fns: &BubFns,
+    speakers_absolute_coord: &[BubFnsCoord],
+    time: BubFnsTime,
+    expr: &BubFnsAST,
+    frame: &mut Frame<S>,
+) {
+    for (i, speaker_absolute_coord) in speakers_absolute_coord.iter().enumerate() {
+        if let Some(volume_and_interpreter_vec) =
+            time.volume_and_interpreter(bub_fns, *speaker_absolute_coord)
+        {
+            for (volume, interpreter) in volume_and_interpreter_vec {
+                let sample = interpreter.eval_sum(expr).unwrap();
+                frame.0[i] += S::from_f64(sample * volume);
+            }
+        }
+    }
+}
+
+/// Gain of a speaker which moves linearly to its target
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct GainRamp {
+    gain: f64,
+    target: f64,
+    step: f64,
+    remaining: u64,
+    /// Volume of each `BubFn` at the last frame in the domains
+    volumes: Vec<f64>,
+}
+
+impl GainRamp {
+    const fn new(gain: f64) -> Self {
+        Self {
+            gain,
+            target: gain,
+            step: 0.0,
+            remaining: 0,
+            volumes: Vec::new(),
+        }
+    }
+
+    /// Move to `target` over `frames`, and returns the gain at this frame.
+    fn next(&mut self, target: f64, frames: u64) -> f64 {
+        if target != self.target {
+            self.target = target;
+            self.step = (target - self.gain) / frames as f64;
+            self.remaining = frames;
+        }
+        if self.remaining != 0 {
+            self.remaining -= 1;
+            self.gain = if self.remaining == 0 {
+                self.target
+            } else {
+                self.gain + self.step
+            };
+        }
+
+        self.gain
+    }
+}
+
+/// Write `sample`, or `expr` if there is no sample, with volumes moving over `smoothing_frames`
+/// to `frame`. `gain_ramps` are empty after frames out of blocks, so volumes start instantly.
+/// Out of the domains, `expr` is evaluated in the `BubFn`s of the last frame in the domains.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn smoothed_frame<S: Sample>(
+    bub_fns: &BubFns,
+    speakers_absolute_coord: &[BubFnsCoord],
+    time: BubFnsTime,
+    sample: Option<S>,
+    expr: Option<&BubFnsAST>,
+    smoothing_frames: u64,
+    gain_ramps: &mut Vec<GainRamp>,
+    frame: &mut Frame<S>,
+) {
+    let is_connected = !gain_ramps.is_empty();
+    for (i, speaker_absolute_coord) in speakers_absolute_coord.iter().enumerate() {
+        let volume_and_interpreter_vec = time.volume_of_each(bub_fns, *speaker_absolute_coord);
+        let volumes: f64 = volume_and_interpreter_vec
+            .iter()
+            .map(|(volume, _)| volume)
+            .sum();
+        if !is_connected {
+            gain_ramps.push(GainRamp::new(volumes));
+        }
+        let gain_ramp = &mut gain_ramps[i];
+        let gain = gain_ramp.next(volumes, smoothing_frames);
+        if volumes != 0.0 {
+            gain_ramp.volumes = volume_and_interpreter_vec
+                .iter()
+                .map(|(volume, _)| *volume)
+                .collect();
+        }
+
+        match (sample, expr) {
+            (Some(sample), _) => frame.0[i] = sample * S::from_f64(gain),
+            // Samples of `Expr` are scaled to the gain.
+            (None, Some(expr))
+                if gain != 0.0 && gain_ramp.volumes.len() == volume_and_interpreter_vec.len() =>
+            {
+                let mut sample = 0.0;
+                let mut last_volumes = 0.0;
+                for (&volume, (_, interpreter)) in gain_ramp
+                    .volumes
+                    .iter()
+                    .zip(volume_and_interpreter_vec.iter())
+                    .filter(|(&volume, _)| volume != 0.0)
+                {
+                    sample += interpreter.eval_sum(expr).unwrap() * volume;
+                    last_volumes += volume;
+                }
+                frame.0[i] = S::from_f64(sample * gain / last_volumes);
+            }
+            _ => (),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BubFrameReader<R: Read, S: Sample> {
     pub inner: R,
@@ -21,6 +211,14 @@ pub struct BubFrameReader<R: Read, S: Sample> {
     pub crc: CRC<u32>,
     /// Floaout Spaces
     pub oao_spaces: Option<OaoSpaces>,
+    /// Decoded samples of the current `LosslessLpcm` or `Mdct` block
+    decoded_samples: VecDeque<S>,
+    /// Number of frames over which volumes of each speaker move to new ones,
+    /// such as when Bubble Functions change at the next head or a domain boundary is crossed.
+    /// 0 if volumes change instantly.
+    pub smoothing_frames: u64,
+    /// Empty if the previous frame is out of blocks
+    gain_ramps: Vec<GainRamp>,
 }
 
 impl<R: Read, S: Sample> FrameReader<R, S> for BubFrameReader<R, S> {
@@ -62,31 +260,26 @@ impl<R: Read, S: Sample> BubFrameReader<R, S> {
             speakers_absolute_coord,
             crc: metadata_and_crc.1,
             oao_spaces,
+            decoded_samples: VecDeque::new(),
+            smoothing_frames: 0,
+            gain_ramps: Vec::new(),
         }
     }
 
     fn read_head_metadata_and_calc_bytes(&mut self) -> Result<()> {
-        let functions_size: u16 = self.inner.read_le_and_calc_bytes(&mut self.crc)?;
+        let head = BubBlockHead::read_and_calc_bytes(&mut self.inner, &mut self.crc)?;
+        head.check_frames(self.pos, self.metadata.frames)?;
 
-        let bub_fns_vec = self
-            .inner
-            .read_vec_for_and_calc_bytes(functions_size as usize, &mut self.crc)?;
-
-        self.metadata.bub_fns = parse(&bub_fns_vec, &BubFnsVariable::BubFns)
+        self.metadata.bub_fns = parse(&head.bub_fns, &BubFnsVariable::BubFns)
             .unwrap()
             .into_original()
             .unwrap()
             .into_bub_fns()
             .unwrap();
         // Foot relative frame
-        self.metadata.foot_absolute_frame_plus_one =
-            self.pos + self.inner.read_le_and_calc_bytes::<u64>(&mut self.crc)?;
+        self.metadata.foot_absolute_frame_plus_one = self.pos + head.foot_relative_frame;
         // Next head relative frame
-        self.metadata.read_next_head_absolute_frame_from_relative(
-            &mut self.inner,
-            self.pos,
-            &mut self.crc,
-        )?;
+        self.metadata.next_head_absolute_frame = head.next_head_absolute_frame(self.pos);
 
         Ok(())
     }
@@ -96,7 +289,8 @@ impl<R: Read, S: Sample> BubFrameReader<R, S> {
     }
 
     fn read_lpcm_sample_and_crc(&mut self) -> Result<S> {
-        let sample = S::read_and_calc_bytes(&mut self.inner, &mut self.crc)?;
+        let sample =
+            S::read_lpcm_and_calc_bytes(&mut self.inner, self.metadata.lpcm_kind, &mut self.crc)?;
         // Read CRC
         if self.metadata.foot_absolute_frame_plus_one - 1 == self.pos {
             self.read_crc()?;
@@ -105,6 +299,51 @@ impl<R: Read, S: Sample> BubFrameReader<R, S> {
         Ok(sample)
     }
 
+    /// Read compressed bytes and the CRC.
+    /// Returns the bytes and the number of samples to decode.
+    fn read_compressed_and_crc(&mut self, compressed_size: u64) -> Result<(Vec<u8>, usize)> {
+        let compressed =
+            read_payload_and_calc_bytes(&mut self.inner, compressed_size, &mut self.crc)?;
+        // CRC
+        self.read_crc()?;
+
+        let foot_relative_frame = self.metadata.foot_absolute_frame_plus_one - self.pos;
+        let len = usize::try_from(foot_relative_frame).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} frames can't be decoded", foot_relative_frame),
+            )
+        })?;
+
+        Ok((compressed, len))
+    }
+
+    fn read_lossless_lpcm_and_crc(&mut self) -> Result<()> {
+        let predictor_order: u8 = self.inner.read_le_and_calc_bytes(&mut self.crc)?;
+        let compressed_size: u64 = self.inner.read_le_and_calc_bytes(&mut self.crc)?;
+        let (compressed, len) = self.read_compressed_and_crc(compressed_size)?;
+
+        let lpcm_kind = self.metadata.lpcm_kind;
+        self.decoded_samples = lossless::decode(predictor_order, &compressed, len)?
+            .into_iter()
+            .map(|n| lpcm_kind.i64_to_sample(n))
+            .collect();
+
+        Ok(())
+    }
+
+    fn read_mdct_and_crc(&mut self) -> Result<()> {
+        let gain: i8 = self.inner.read_le_and_calc_bytes(&mut self.crc)?;
+        let compressed_size: u64 = self.inner.read_le_and_calc_bytes(&mut self.crc)?;
+        let (compressed, len) = self.read_compressed_and_crc(compressed_size)?;
+        self.decoded_samples = mdct::decode(gain, &compressed, len)?
+            .into_iter()
+            .map(S::from_f64)
+            .collect();
+
+        Ok(())
+    }
+
     fn read_expression_and_crc(&mut self) -> Result<Vec<u8>> {
         let expr_size: u16 = self.inner.read_le_and_calc_bytes(&mut self.crc)?;
         let expr = self
@@ -115,51 +354,144 @@ impl<R: Read, S: Sample> BubFrameReader<R, S> {
         Ok(expr)
     }
 
-    fn get_volume_and_interpreter(
-        &self,
-        speaker_absolute_coord: BubFnsCoord,
-    ) -> Option<Vec<(f64, BubFnsInterpreter)>> {
-        self.metadata.bub_fns.to_volume(
-            speaker_absolute_coord,
-            self.pos as f64,
-            (self.pos - self.metadata.head_absolute_frame + 1) as f64,
-            self.metadata.frames as f64,
-            self.metadata.samples_per_sec,
-        )
+    /// Time variables of Bubble Functions at `pos`
+    fn bub_fns_time(&self) -> BubFnsTime {
+        BubFnsTime {
+            absolute_frame: self.pos as f64,
+            relative_frame: self.pos as f64 - self.metadata.head_absolute_frame as f64 + 1.0,
+            frames: self.metadata.frames as f64,
+            samples_per_sec: self.metadata.samples_per_sec,
+        }
     }
 
-    fn read_lpcm_frame(&mut self, frame: &mut Frame<S>) -> Result<()> {
-        let sample = self.read_lpcm_sample_and_crc()?;
+    fn pop_decoded_sample(&mut self) -> Result<S> {
+        self.decoded_samples
+            .pop_front()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "decoded samples are too short"))
+    }
 
-        if sample != S::default() {
-            // TODO: Create method
-            for (i, speaker_absolute_coord) in self.speakers_absolute_coord.iter().enumerate() {
-                if let Some(volume_and_interpreter_vec) =
-                    self.get_volume_and_interpreter(*speaker_absolute_coord)
-                {
-                    let mut volumes = 0.0;
-                    for (volume, _) in volume_and_interpreter_vec {
-                        volumes += volume;
+    /// Advance `pos` and read the sample without Bubble Functions.
+    pub(crate) fn next_sample(&mut self) -> Option<Result<Option<S>>> {
+        if self.metadata.frames() <= self.pos {
+            return None;
+        } else {
+            self.pos += 1;
+        }
+
+        Some(self.read_sample())
+    }
+
+    /// Read the frame at `pos`.
+    /// Returns the sample unless the frame is out of blocks or the sample kind is `Expr`.
+    pub(crate) fn read_sample(&mut self) -> Result<Option<S>> {
+        self.metadata.init_with_pos(self.pos);
+
+        match self.metadata.bub_state {
+            BubState::Head => {
+                self.read_head_metadata_and_calc_bytes()?;
+
+                // Read Sample
+                match self.metadata.bub_sample_kind {
+                    BubSampleKind::Lpcm => self.read_lpcm_sample_and_crc().map(Some),
+                    BubSampleKind::Expr(_) => {
+                        let expr = self.read_expression_and_crc()?;
+                        let expr = parse(&expr, &BubFnsVariable::Sum).unwrap();
+                        self.metadata.bub_sample_kind = expr.into();
+                        Ok(None)
+                    }
+                    BubSampleKind::LosslessLpcm => {
+                        self.read_lossless_lpcm_and_crc()?;
+                        self.pop_decoded_sample().map(Some)
+                    }
+                    BubSampleKind::Mdct => {
+                        self.read_mdct_and_crc()?;
+                        self.pop_decoded_sample().map(Some)
                     }
-                    frame.0[i] = sample * S::from_f64(volumes);
                 }
             }
+            BubState::Body => match self.metadata.bub_sample_kind {
+                BubSampleKind::Lpcm => self.read_lpcm_sample_and_crc().map(Some),
+                BubSampleKind::Expr(_) => Ok(None),
+                BubSampleKind::LosslessLpcm | BubSampleKind::Mdct => {
+                    self.pop_decoded_sample().map(Some)
+                }
+            },
+            BubState::Stopped | BubState::Ended => Ok(None),
         }
-
-        Ok(())
     }
 
-    fn expr_frame(&self, expr: &BubFnsAST, frame: &mut Frame<S>) {
-        for (i, speaker_absolute_coord) in self.speakers_absolute_coord.iter().enumerate() {
-            if let Some(volume_and_interpreter_vec) =
-                self.get_volume_and_interpreter(*speaker_absolute_coord)
-            {
-                for (volume, interpreter) in volume_and_interpreter_vec {
-                    let sample = interpreter.eval_sum(expr).unwrap();
-                    frame.0[i] += S::from_f64(sample * volume);
-                }
+    /// Number of bytes `read_sample` reads at the next frame, and whether it is known.
+    /// If `buffered` is too short to know it, returns the number of bytes needed to know it.
+    /// Heads are parsed by the same functions as `read_sample`.
+    #[cfg(feature = "async")]
+    pub(crate) fn next_sample_size(&self, buffered: &[u8]) -> Result<(usize, bool)> {
+        let pos = self.pos + 1;
+        let metadata = &self.metadata;
+        let bytes_per_sample = metadata.lpcm_kind.bits_per_sample() as u64 / 8;
+        // LPCM sample, and the CRC at the foot
+        let lpcm_sample_size = |foot_absolute_frame_plus_one: u64| {
+            if pos + 1 == foot_absolute_frame_plus_one {
+                bytes_per_sample + 4
+            } else {
+                bytes_per_sample
             }
+        };
+
+        // Body
+        if (metadata.bub_state.is_head() || metadata.bub_state.is_body())
+            && pos < metadata.foot_absolute_frame_plus_one
+        {
+            return Ok(match metadata.bub_sample_kind {
+                BubSampleKind::Lpcm => (
+                    lpcm_sample_size(metadata.foot_absolute_frame_plus_one) as usize,
+                    true,
+                ),
+                _ => (0, true),
+            });
         }
+        // Stopped or Ended
+        if metadata.bub_state.is_ended() || metadata.next_head_absolute_frame != Some(pos) {
+            return Ok((0, true));
+        }
+
+        // Head
+        let mut slice = RecordingSlice::new(buffered);
+        let mut crc = self.crc;
+        let head_and_samples =
+            BubBlockHead::read_and_calc_bytes(&mut slice, &mut crc).and_then(|head| {
+                let samples = BubBlockSamples::read_and_calc_bytes(
+                    &mut slice,
+                    &metadata.bub_sample_kind,
+                    &mut crc,
+                )?;
+                Ok((head, samples))
+            });
+        let (head, samples) = match head_and_samples {
+            Ok(head_and_samples) => head_and_samples,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok((slice.needed(), false)),
+            Err(e) => return Err(e),
+        };
+        head.check_frames(pos, metadata.frames)?;
+
+        let rest = match samples {
+            BubBlockSamples::Lpcm => lpcm_sample_size(pos + head.foot_relative_frame),
+            // CRC
+            _ => samples
+                .payload_size(metadata.lpcm_kind, head.foot_relative_frame)?
+                .checked_add(4)
+                .ok_or(ErrorKind::InvalidData)?,
+        };
+        let size = usize::try_from(rest)
+            .ok()
+            .and_then(|rest| rest.checked_add(slice.position()))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} bytes of samples can't be read", rest),
+                )
+            })?;
+
+        Ok((size, true))
     }
 }
 
@@ -173,52 +505,56 @@ impl<R: Read, S: Sample> Iterator for BubFrameReader<R, S> {
             self.pos += 1;
         }
 
-        self.metadata.init_with_pos(self.pos);
-
         let channels = self.speakers_absolute_coord.len();
 
         let mut frame: Frame<S> = vec![S::default(); channels].into();
 
-        match self.metadata.bub_state {
-            BubState::Head => {
-                if let Err(e) = self.read_head_metadata_and_calc_bytes() {
-                    return Some(Err(e));
-                }
-
-                // Read Sample
-                match self.metadata.bub_sample_kind {
-                    BubSampleKind::Lpcm => {
-                        if let Err(e) = self.read_lpcm_frame(&mut frame) {
-                            return Some(Err(e));
-                        }
-                    }
-                    BubSampleKind::Expr(_) => {
-                        let expr = match self.read_expression_and_crc() {
-                            Ok(v) => v,
-                            Err(e) => return Some(Err(e)),
-                        };
-                        let expr = parse(&expr, &BubFnsVariable::Sum).unwrap();
-                        self.expr_frame(&expr, &mut frame);
-                        self.metadata.bub_sample_kind = expr.into();
-                    }
+        match self.read_sample() {
+            Ok(sample) if self.smoothing_frames != 0 => {
+                if self.metadata.bub_state.is_head() || self.metadata.bub_state.is_body() {
+                    let expr = match &self.metadata.bub_sample_kind {
+                        BubSampleKind::Expr(expr) => Some(expr),
+                        _ => None,
+                    };
+                    smoothed_frame(
+                        &self.metadata.bub_fns,
+                        &self.speakers_absolute_coord,
+                        self.bub_fns_time(),
+                        sample,
+                        expr,
+                        self.smoothing_frames,
+                        &mut self.gain_ramps,
+                        &mut frame,
+                    );
+                } else {
+                    self.gain_ramps.clear();
                 }
             }
-            BubState::Body => {
-                // Read Sample
-                match &self.metadata.bub_sample_kind {
-                    BubSampleKind::Lpcm => {
-                        if let Err(e) = self.read_lpcm_frame(&mut frame) {
-                            return Some(Err(e));
-                        }
+            Ok(Some(sample)) => lpcm_frame(
+                &self.metadata.bub_fns,
+                &self.speakers_absolute_coord,
+                self.bub_fns_time(),
+                sample,
+                &mut frame,
+            ),
+            Ok(None) => {
+                if let BubSampleKind::Expr(expr) = &self.metadata.bub_sample_kind {
+                    if self.metadata.bub_state.is_head() || self.metadata.bub_state.is_body() {
+                        expr_frame(
+                            &self.metadata.bub_fns,
+                            &self.speakers_absolute_coord,
+                            self.bub_fns_time(),
+                            expr,
+                            &mut frame,
+                        );
                     }
-                    BubSampleKind::Expr(expr) => self.expr_frame(expr, &mut frame),
                 }
             }
-            BubState::Stopped => (),
-            BubState::Ended => (),
+            Err(e) => return Some(Err(e)),
         }
 
         // Volume Space
+        let time = self.bub_fns_time();
         if let Some(oao_spaces) = &mut self.oao_spaces {
             if let Some(rgb) = self.metadata.bub_id.rgb {
                 if self.pos % oao_spaces.frames_between_spaces == 0 {
@@ -234,16 +570,11 @@ impl<R: Read, S: Sample> Iterator for BubFrameReader<R, S> {
                                     + oao_spaces.start as f64;
                                 // Get Volumes
                                 let mut volumes = 0.0;
-                                if let Some(volume_and_interpreter_vec) =
-                                    // TODO : Create method
-                                    self.metadata.bub_fns.to_volume(
-                                            (x, y, z).into(),
-                                            self.pos as f64,
-                                            (self.pos - self.metadata.head_absolute_frame + 1)
-                                                as f64,
-                                            self.metadata.frames as f64,
-                                            self.metadata.samples_per_sec,
-                                        )
+                                if let Some(volume_and_interpreter_vec) = time
+                                    .volume_and_interpreter(
+                                        &self.metadata.bub_fns,
+                                        (x, y, z).into(),
+                                    )
                                 {
                                     for (volume, _) in volume_and_interpreter_vec {
                                         volumes += volume;
@@ -262,7 +593,14 @@ impl<R: Read, S: Sample> Iterator for BubFrameReader<R, S> {
     }
 }
 
-pub type BubFrameReaderKind<R> = FrameIOKind<BubFrameReader<R, f32>, BubFrameReader<R, f64>>;
+pub type BubFrameReaderKind<R> = FrameIOKind<
+    BubFrameReader<R, f32>,
+    BubFrameReader<R, f64>,
+    BubFrameReader<R, f32>,
+    BubFrameReader<R, f32>,
+    BubFrameReader<R, f64>,
+    BubFrameReader<R, f32>,
+>;
 
 #[cfg(test)]
 mod tests {
@@ -424,4 +762,145 @@ mod tests {
             assert_eq!(frame.0, expect.1);
         }
     }
+
+    #[test]
+    fn smooth_volumes() -> Result<()> {
+        use crate::bub::{BubFnsBlock, BubReader, BubWriter};
+
+        let bub_fns: [&[u8]; 2] = [b"0 0 0 X<3 1", b"0 0 0 X<3&&n<=2 0.5"];
+        for bub_sample_kind in [BubSampleKind::Lpcm, BubSampleKind::default_expr()] {
+            let is_expr = bub_sample_kind != BubSampleKind::Lpcm;
+            let metadata = BubMetadata::new(
+                12,
+                1,
+                48000.0,
+                LpcmKind::F64LE,
+                bub_sample_kind,
+                String::from("Smooth"),
+            );
+            let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+            let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f64_le()?;
+            for (i, &bub_fns) in bub_fns.iter().enumerate() {
+                let next_head_relative_frame = if i == 0 { Some(5) } else { None };
+                bub_frame_writer.write_head_to_less_than_next_head_or_ended(if is_expr {
+                    BubFnsBlock::Expr {
+                        bub_fns,
+                        foot_relative_frame: 4 * (i as u64 + 1),
+                        next_head_relative_frame,
+                        expression: b"0.5",
+                    }
+                } else {
+                    BubFnsBlock::Lpcm {
+                        bub_fns,
+                        next_head_relative_frame,
+                        samples: vec![0.5; 4 * (i + 1)],
+                    }
+                })?;
+            }
+            let v = bub_frame_writer.inner;
+
+            let mut bub_frame_reader = BubReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?
+                .into_bub_frame_reader_kind(None)
+                .into_f64_le()?;
+            bub_frame_reader.smoothing_frames = 4;
+            let samples = bub_frame_reader
+                .map(|frame| frame.map(|frame| frame.0[0]))
+                .collect::<Result<Vec<_>>>()?;
+            // Volumes move from 1.0 to 0.5 at the head, and to 0.0 at the domain boundary.
+            let expected = [
+                1.0, 1.0, 1.0, 1.0, 0.875, 0.75, 0.5625, 0.375, 0.1875, 0.0, 0.0, 0.0,
+            ];
+            assert_eq!(
+                samples,
+                expected.iter().map(|v| v * 0.5).collect::<Vec<_>>()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn fade_out_across_domain_exit() -> Result<()> {
+        use crate::bub::{BubFnsBlock, BubReader, BubWriter};
+
+        let metadata = BubMetadata::new(
+            6,
+            1,
+            48000.0,
+            LpcmKind::F64LE,
+            BubSampleKind::default_expr(),
+            String::from("Fade"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f64_le()?;
+        // The first `BubFn` is never in its domain.
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 0==1 1 5 0 0 n<=2 1",
+            foot_relative_frame: 6,
+            next_head_relative_frame: None,
+            expression: b"n*x",
+        })?;
+        let v = bub_frame_writer.inner;
+
+        let mut bub_frame_reader = BubReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?
+            .into_bub_frame_reader_kind(None)
+            .into_f64_le()?;
+        bub_frame_reader.smoothing_frames = 4;
+        let samples = bub_frame_reader
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+        // After the domain exit, `Expr` is still evaluated in the second `BubFn`, where x is -5.
+        assert_eq!(samples, [-5.0, -10.0, -11.25, -10.0, -6.25, 0.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn too_large_blocks() -> Result<()> {
+        use crate::bub::{BubFnsBlock, BubReader, BubWriter};
+
+        let bub_fns = b"0 0 0 X<3 1";
+        let metadata = BubMetadata::new(
+            3,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::LosslessLpcm,
+            String::from("Large"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let metadata_len = bub_writer.inner.len();
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns,
+            next_head_relative_frame: None,
+            samples: vec![0.5, 0.25, 0.125],
+        })?;
+        let v = bub_frame_writer.inner;
+        // Bubble Functions Size and Bubble Functions
+        let foot_offset = metadata_len + 2 + bub_fns.len();
+        // Foot and Next Head Relative Frame, Predictor Order
+        let compressed_size_offset = foot_offset + 8 + 8 + 1;
+
+        let read = |v: &[u8]| -> Result<Vec<Frame<f32>>> {
+            BubReader::new(v, vec![(0.0, 0.0, 0.0).into()])?
+                .into_bub_frame_reader_kind(None)
+                .into_f32_le()?
+                .collect()
+        };
+        assert_eq!(read(&v)?.len(), 3);
+
+        let mut broken = v.clone();
+        broken[foot_offset..foot_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(read(&broken).unwrap_err().kind(), ErrorKind::InvalidData);
+        broken[foot_offset..foot_offset + 8].copy_from_slice(&4u64.to_le_bytes());
+        assert_eq!(read(&broken).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let mut broken = v;
+        broken[compressed_size_offset..compressed_size_offset + 8]
+            .copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(read(&broken).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        Ok(())
+    }
 }
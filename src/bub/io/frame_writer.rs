@@ -1,11 +1,25 @@
-use crate::bub::{BubFnsBlock, BubMetadata, BubbleSample};
+use crate::bub::codec::{lossless, mdct};
+use crate::bub::io::{write_payload_and_calc_bytes, BubBlockHead};
+use crate::bub::{BubBlockSamples, BubFnsBlock, BubMetadata, BubSampleKind, BubbleSample};
 use crate::io::WriteExt;
-use crate::utils::write_crc;
+use crate::utils::{u16_len, write_crc};
 use crate::{FrameIOKind, FrameWriter, Sample};
-use mycrc::CRC;
-use std::io::{Error, ErrorKind, Result, Write};
+use mycrc::{Endian, CRC};
+use std::io::{Error, ErrorKind, Result, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
 
+/// Default bits per second of `BubSampleKind::Mdct` samples
+const DEFAULT_MDCT_BITRATE: u32 = 96_000;
+
+/// `BubFnsBlock` which is being written sample by sample.
+struct OpenLpcmBlock {
+    head_absolute_frame: u64,
+    /// Stream position of `Foot Relative Frame`
+    foot_relative_frame_pos: u64,
+    /// Number of samples written
+    foot_relative_frame: u64,
+}
+
 pub struct BubFrameWriter<W: Write, S: Sample> {
     pub inner: W,
     pub pos: u64,
@@ -13,6 +27,9 @@ pub struct BubFrameWriter<W: Write, S: Sample> {
     pub metadata: BubMetadata,
     /// CRC
     pub crc: CRC<u32>,
+    /// Bits per second of `BubSampleKind::Mdct` samples. 96000 by default.
+    pub mdct_bitrate: u32,
+    open_lpcm_block: Option<OpenLpcmBlock>,
 }
 
 impl<W: Write, S: Sample> FrameWriter<W> for BubFrameWriter<W, S> {
@@ -40,6 +57,8 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
             _phantom_sample: PhantomData,
             metadata: metadata_and_crc.0,
             crc: metadata_and_crc.1,
+            mdct_bitrate: DEFAULT_MDCT_BITRATE,
+            open_lpcm_block: None,
         }
     }
 
@@ -54,20 +73,15 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
         foot_relative_frame: u64,
         next_head_relative_frame: u64,
     ) -> Result<()> {
+        BubBlockHead::write_and_calc_bytes(
+            &mut self.inner,
+            bub_fns,
+            foot_relative_frame,
+            next_head_relative_frame,
+            &mut self.crc,
+        )?;
         self.metadata.set_as_head(head_absolute_frame);
         self.metadata.foot_absolute_frame_plus_one = head_absolute_frame + foot_relative_frame;
-        // functions size
-        self.inner
-            .write_le_and_calc_bytes(bub_fns.len() as u16, &mut self.crc)?;
-        // Bubble Functions
-        self.inner.write_all(bub_fns)?;
-        self.crc.calc_bytes(bub_fns);
-        // Foot Relative Frame
-        self.inner
-            .write_le_and_calc_bytes(foot_relative_frame, &mut self.crc)?;
-        // Next head relative frame
-        self.inner
-            .write_le_and_calc_bytes(next_head_relative_frame, &mut self.crc)?;
         self.metadata.set_next_head_absolute_frame_from_relative(
             next_head_relative_frame,
             head_absolute_frame,
@@ -92,11 +106,19 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
                     next_head_relative_frame.unwrap_or(0),
                 )?;
 
-                sample.write_and_calc_bytes(&mut self.inner, &mut self.crc)?;
+                sample.write_lpcm_and_calc_bytes(
+                    &mut self.inner,
+                    self.metadata.lpcm_kind,
+                    &mut self.crc,
+                )?;
             }
             BubbleSample::LpcmBody(sample) => {
                 self.metadata.set_as_body();
-                sample.write_and_calc_bytes(&mut self.inner, &mut self.crc)?;
+                sample.write_lpcm_and_calc_bytes(
+                    &mut self.inner,
+                    self.metadata.lpcm_kind,
+                    &mut self.crc,
+                )?;
             }
             BubbleSample::Expr {
                 head_absolute_frame,
@@ -105,6 +127,7 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
                 next_head_relative_frame,
                 expression,
             } => {
+                let expression_size = u16_len("size of expression", expression.len())?;
                 self.write_head_metadata_and_calc_bytes(
                     head_absolute_frame,
                     bub_fns,
@@ -113,7 +136,7 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
                 )?;
                 // Write Expr
                 self.inner
-                    .write_le_and_calc_bytes(expression.len() as u16, &mut self.crc)?;
+                    .write_le_and_calc_bytes(expression_size, &mut self.crc)?;
                 self.inner.write_all(expression)?;
                 self.crc.calc_bytes(expression);
             }
@@ -122,6 +145,35 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
         Ok(())
     }
 
+    fn write_lossless_lpcm_and_calc_bytes(&mut self, samples: &[S]) -> Result<()> {
+        let lpcm_kind = self.metadata.lpcm_kind;
+        let values: Vec<i64> = samples
+            .iter()
+            .map(|sample| lpcm_kind.sample_to_i64(*sample))
+            .collect();
+        let (predictor_order, compressed) = lossless::encode(&values);
+        BubBlockSamples::LosslessLpcm {
+            predictor_order,
+            compressed_size: compressed.len() as u64,
+        }
+        .write_and_calc_bytes(&mut self.inner, &mut self.crc)?;
+        // Compressed Samples
+        write_payload_and_calc_bytes(&mut self.inner, &compressed, &mut self.crc)
+    }
+
+    fn write_mdct_and_calc_bytes(&mut self, samples: &[S]) -> Result<()> {
+        let samples: Vec<f64> = samples.iter().map(|sample| sample.to_f64()).collect();
+        let bits = self.mdct_bitrate as f64 * samples.len() as f64 / self.metadata.samples_per_sec;
+        let (gain, compressed) = mdct::encode(&samples, bits as u64);
+        BubBlockSamples::Mdct {
+            gain,
+            compressed_size: compressed.len() as u64,
+        }
+        .write_and_calc_bytes(&mut self.inner, &mut self.crc)?;
+        // Compressed Coefficients
+        write_payload_and_calc_bytes(&mut self.inner, &compressed, &mut self.crc)
+    }
+
     fn add_pos_to_less_than_next_head_or_ended(
         &mut self,
         head_absolute_frame: u64,
@@ -180,6 +232,12 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
         &mut self,
         bub_fns_block: BubFnsBlock<S>,
     ) -> Result<()> {
+        if self.open_lpcm_block.is_some() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "lpcm block must be ended before writing another block",
+            ));
+        }
         if self.metadata.frames() <= self.pos {
             return Err(Error::new(
                 ErrorKind::Other,
@@ -187,6 +245,21 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
             ));
         }
 
+        // Sizes are checked before anything is written.
+        match &bub_fns_block {
+            BubFnsBlock::Lpcm { bub_fns, .. } => {
+                u16_len("size of bubble functions", bub_fns.len())?;
+            }
+            BubFnsBlock::Expr {
+                bub_fns,
+                expression,
+                ..
+            } => {
+                u16_len("size of bubble functions", bub_fns.len())?;
+                u16_len("size of expression", expression.len())?;
+            }
+        }
+
         let head_absolute_frame = self.pos + 1;
 
         match bub_fns_block {
@@ -208,17 +281,35 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
                     next_head_relative_frame,
                 )?;
 
-                // Write Head
-                self.write_sample_and_calc_bytes(BubbleSample::LpcmHead {
-                    head_absolute_frame,
-                    bub_fns,
-                    foot_relative_frame,
-                    next_head_relative_frame,
-                    sample: samples[0],
-                })?;
-                // Write Body
-                for sample in samples.into_iter().skip(1) {
-                    self.write_sample_and_calc_bytes(BubbleSample::LpcmBody(sample))?;
+                match self.metadata.bub_sample_kind {
+                    BubSampleKind::LosslessLpcm | BubSampleKind::Mdct => {
+                        // All samples are in Head
+                        self.write_head_metadata_and_calc_bytes(
+                            head_absolute_frame,
+                            bub_fns,
+                            foot_relative_frame,
+                            next_head_relative_frame.unwrap_or(0),
+                        )?;
+                        if self.metadata.bub_sample_kind == BubSampleKind::Mdct {
+                            self.write_mdct_and_calc_bytes(&samples)?;
+                        } else {
+                            self.write_lossless_lpcm_and_calc_bytes(&samples)?;
+                        }
+                    }
+                    _ => {
+                        // Write Head
+                        self.write_sample_and_calc_bytes(BubbleSample::LpcmHead {
+                            head_absolute_frame,
+                            bub_fns,
+                            foot_relative_frame,
+                            next_head_relative_frame,
+                            sample: samples[0],
+                        })?;
+                        // Write Body
+                        for sample in samples.into_iter().skip(1) {
+                            self.write_sample_and_calc_bytes(BubbleSample::LpcmBody(sample))?;
+                        }
+                    }
                 }
                 // Write CRC
                 self.write_crc()?;
@@ -287,13 +378,170 @@ impl<W: Write, S: Sample> BubFrameWriter<W, S> {
     // }
 }
 
-pub type BubFrameWriterKind<W> = FrameIOKind<BubFrameWriter<W, f32>, BubFrameWriter<W, f64>>;
+impl<W: Write + Seek, S: Sample> BubFrameWriter<W, S> {
+    /// Start writing a `BubFnsBlock::Lpcm` whose samples are written one at a time
+    /// with `write_lpcm_sample`.
+    /// `Foot Relative Frame` and `Next Head Relative Frame` are written by `end_lpcm_block`.
+    pub fn start_lpcm_block(&mut self, bub_fns: &[u8]) -> Result<()> {
+        if self.open_lpcm_block.is_some() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "lpcm block has already been started",
+            ));
+        }
+        if self.metadata.bub_sample_kind != BubSampleKind::Lpcm {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "samples can be written one at a time only when bub_sample_kind is Lpcm",
+            ));
+        }
+        if self.metadata.frames() <= self.pos {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "all frames have already been written",
+            ));
+        }
+
+        let head_absolute_frame = self.pos + 1;
+
+        let bub_fns_size = u16_len("size of bubble functions", bub_fns.len())?;
+        self.metadata.set_as_head(head_absolute_frame);
+        // functions size
+        self.inner
+            .write_le_and_calc_bytes(bub_fns_size, &mut self.crc)?;
+        // Bubble Functions
+        self.inner.write_all(bub_fns)?;
+        self.crc.calc_bytes(bub_fns);
+        // Foot Relative Frame and Next head relative frame are patched later.
+        let foot_relative_frame_pos = self.inner.stream_position()?;
+        self.inner.write_le_and_calc_bytes(0u64, &mut self.crc)?;
+        self.inner.write_le_and_calc_bytes(0u64, &mut self.crc)?;
+
+        self.open_lpcm_block = Some(OpenLpcmBlock {
+            head_absolute_frame,
+            foot_relative_frame_pos,
+            foot_relative_frame: 0,
+        });
+
+        Ok(())
+    }
+
+    pub fn write_lpcm_sample(&mut self, sample: S) -> Result<()> {
+        let block = self
+            .open_lpcm_block
+            .as_mut()
+            .ok_or_else(|| Error::new(ErrorKind::Other, "lpcm block has not been started"))?;
+        if self.metadata.frames() < block.head_absolute_frame + block.foot_relative_frame {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "all frames have already been written",
+            ));
+        }
+
+        sample.write_lpcm_and_calc_bytes(
+            &mut self.inner,
+            self.metadata.lpcm_kind,
+            &mut self.crc,
+        )?;
+        if block.foot_relative_frame != 0 {
+            self.metadata.set_as_body();
+        }
+        block.foot_relative_frame += 1;
+
+        Ok(())
+    }
+
+    /// End the block started by `start_lpcm_block`.
+    /// `Foot Relative Frame` and `Next Head Relative Frame` are written back with `Seek`,
+    /// and the CRC is corrected without reading the samples again.
+    pub fn end_lpcm_block(&mut self, next_head_relative_frame: Option<u64>) -> Result<()> {
+        let (head_absolute_frame, foot_relative_frame_pos, foot_relative_frame) =
+            match &self.open_lpcm_block {
+                Some(block) => (
+                    block.head_absolute_frame,
+                    block.foot_relative_frame_pos,
+                    block.foot_relative_frame,
+                ),
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        "lpcm block has not been started",
+                    ))
+                }
+            };
+        // Check if samples have Head frame sample.
+        if foot_relative_frame == 0 {
+            return Err(Error::new(ErrorKind::Other, "samples must not be empty"));
+        }
+
+        self.add_pos_to_less_than_next_head_or_ended(
+            head_absolute_frame,
+            foot_relative_frame,
+            next_head_relative_frame,
+        )?;
+        self.open_lpcm_block = None;
+
+        // Patch relative frames
+        let relative_frames = [
+            foot_relative_frame.to_le_bytes(),
+            next_head_relative_frame.unwrap_or(0).to_le_bytes(),
+        ]
+        .concat();
+        let end_pos = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(foot_relative_frame_pos))?;
+        self.inner.write_all(&relative_frames)?;
+        self.inner.seek(SeekFrom::Start(end_pos))?;
+
+        // CRC is linear, so the difference from the placeholder (all zero) can be added
+        // as the CRC of the patched bytes followed by the sample bytes set to zero.
+        let mut crc_difference = CRC::<u32>::new(
+            Endian::Little,
+            self.crc.algorithm.poly,
+            0,
+            self.crc.algorithm.refin,
+            self.crc.algorithm.refout,
+            0,
+        );
+        crc_difference.calc_bytes(&relative_frames);
+        let zeros = [0; 1024];
+        let mut samples_size = end_pos - foot_relative_frame_pos - relative_frames.len() as u64;
+        while samples_size != 0 {
+            let len = samples_size.min(zeros.len() as u64);
+            crc_difference.calc_bytes(&zeros[..len as usize]);
+            samples_size -= len;
+        }
+        self.crc.value ^= crc_difference.value;
+
+        self.metadata.foot_absolute_frame_plus_one = head_absolute_frame + foot_relative_frame;
+        self.metadata.set_next_head_absolute_frame_from_relative(
+            next_head_relative_frame.unwrap_or(0),
+            head_absolute_frame,
+        );
+        // Write CRC
+        self.write_crc()?;
+        // Finalize
+        self.metadata
+            .set_bub_state_from_connected_and_ended(self.pos);
+
+        Ok(())
+    }
+}
+
+pub type BubFrameWriterKind<W> = FrameIOKind<
+    BubFrameWriter<W, f32>,
+    BubFrameWriter<W, f64>,
+    BubFrameWriter<W, f32>,
+    BubFrameWriter<W, f32>,
+    BubFrameWriter<W, f64>,
+    BubFrameWriter<W, f32>,
+>;
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bub::BubSampleKind;
+    use crate::bub::{BubSampleKind, BubWriter};
     use crate::LpcmKind;
+    use std::io::Cursor;
 
     #[test]
     fn write_lpcm_frames() -> Result<()> {
@@ -440,4 +688,101 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn too_large_sizes() -> Result<()> {
+        let metadata = BubMetadata::new(
+            2,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::default_expr(),
+            String::from("Bubble"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        let len = bub_frame_writer.inner.len();
+        let long = vec![b'0'; u16::MAX as usize + 1];
+        for (bub_fns, expression) in [(&long[..], &b"0"[..]), (b"0 0 0 X<3 1", &long[..])] {
+            let e = bub_frame_writer
+                .write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+                    bub_fns,
+                    foot_relative_frame: 2,
+                    next_head_relative_frame: None,
+                    expression,
+                })
+                .unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+            // Nothing is written.
+            assert_eq!(bub_frame_writer.inner.len(), len);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_lpcm_frames_by_sample() -> Result<()> {
+        let metadata = BubMetadata::new(
+            8,
+            1,
+            96000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::Lpcm,
+            String::from("0.1*N"),
+        );
+        let mut skip = Vec::new();
+        let crc = metadata.write(&mut skip).unwrap();
+
+        let mut expected = BubFrameWriter::<Vec<u8>, f32>::new(Vec::new(), (metadata.clone(), crc));
+        let mut bub_frame_writer =
+            BubFrameWriter::<Cursor<Vec<u8>>, f32>::new(Cursor::new(Vec::new()), (metadata, crc));
+
+        let blocks: [(&[u8], Option<u64>, Vec<f32>); 4] = [
+            (b"1 2 3 X<3 0.1*N", Some(3), vec![1.0, 1.0]),
+            (b"1 2 3 X<3 1", Some(3), vec![0.3]),
+            (b"0 0 0 0==0 1", Some(2), vec![0.4]),
+            (b"0 0 n X>=3 -z", None, vec![1.0]),
+        ];
+
+        assert!(bub_frame_writer.write_lpcm_sample(1.0).is_err());
+        assert!(bub_frame_writer.end_lpcm_block(None).is_err());
+
+        for (bub_fns, next_head_relative_frame, samples) in blocks {
+            bub_frame_writer.start_lpcm_block(bub_fns)?;
+            assert!(bub_frame_writer.start_lpcm_block(bub_fns).is_err());
+            assert!(bub_frame_writer
+                .write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+                    bub_fns,
+                    next_head_relative_frame,
+                    samples: samples.clone(),
+                })
+                .is_err());
+            for sample in samples.iter() {
+                bub_frame_writer.write_lpcm_sample(*sample)?;
+            }
+            // Samples reach the next head
+            if let Some(next_head_relative_frame) = next_head_relative_frame {
+                assert!(bub_frame_writer
+                    .end_lpcm_block(Some(samples.len() as u64))
+                    .is_err());
+                assert!(bub_frame_writer
+                    .end_lpcm_block(Some(next_head_relative_frame))
+                    .is_ok());
+            } else {
+                bub_frame_writer.end_lpcm_block(None)?;
+            }
+
+            expected.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+                bub_fns,
+                next_head_relative_frame,
+                samples,
+            })?;
+            assert_eq!(bub_frame_writer.pos, expected.pos);
+            assert_eq!(bub_frame_writer.crc, expected.crc);
+        }
+
+        assert_eq!(bub_frame_writer.inner.into_inner(), expected.inner);
+
+        Ok(())
+    }
 }
@@ -1,13 +1,42 @@
 use crate::Sample;
+use alloc::vec::Vec;
 
+#[cfg(feature = "async")]
+pub use self::async_frame_reader::AsyncBubFrameReader;
+#[cfg(feature = "async")]
+pub use self::async_frame_writer::AsyncBubFrameWriter;
+pub use self::blocks::{BubBlock, BubBlockSamples, BubBlockSummaries, BubBlockSummary, BubBlocks};
+#[cfg(feature = "std")]
 pub use self::frame_reader::{BubFrameReader, BubFrameReaderKind};
+#[cfg(feature = "std")]
 pub use self::frame_writer::{BubFrameWriter, BubFrameWriterKind};
+#[cfg(feature = "std")]
+pub use self::object_reader::{BubObjectFrame, BubObjectReader};
 pub use self::reader::BubReader;
+#[cfg(feature = "std")]
+pub use self::resampled_frame_reader::ResampledBubFrameReader;
+#[cfg(feature = "std")]
 pub use self::writer::BubWriter;
 
+pub(crate) use self::blocks::read_payload_and_calc_bytes;
+#[cfg(feature = "std")]
+pub(crate) use self::blocks::{discard, write_payload_and_calc_bytes, BubBlockHead};
+
+#[cfg(feature = "async")]
+mod async_frame_reader;
+#[cfg(feature = "async")]
+mod async_frame_writer;
+mod blocks;
+#[cfg(feature = "std")]
 mod frame_reader;
+#[cfg(feature = "std")]
 mod frame_writer;
+#[cfg(feature = "std")]
+mod object_reader;
 mod reader;
+#[cfg(feature = "std")]
+mod resampled_frame_reader;
+#[cfg(feature = "std")]
 mod writer;
 
 pub enum BubbleSample<'a, S: Sample> {
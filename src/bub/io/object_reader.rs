@@ -0,0 +1,213 @@
+use crate::bub::{BubFrameReader, BubSampleKind, BubState};
+use crate::{BubFnsCoord, Sample};
+use std::io::{Read, Result};
+
+/// Frame of a bubble as an object
+#[derive(Clone, Debug, PartialEq)]
+pub struct BubObjectFrame<S: Sample> {
+    /// Mono sample. `Expr` is evaluated at the coordinates of the first `BubFn`
+    /// which is not out of its domain.
+    pub sample: S,
+    /// Bubble absolute coordinates and volumes of each `BubFn`, in the order of `bub_fns`.
+    /// Volume is 0.0 out of the domain, and `None` if the domain or the volume uses
+    /// speaker coordinates, so the `BubFn` must be evaluated at each speaker.
+    pub bub_absolute_coords_and_volumes: Vec<(BubFnsCoord, Option<f64>)>,
+    /// Bubble State
+    pub bub_state: BubState,
+}
+
+/// Reads a bubble as a mono signal and its trajectory instead of rendering it to speakers.
+pub struct BubObjectReader<R: Read, S: Sample> {
+    pub bub_frame_reader: BubFrameReader<R, S>,
+}
+
+impl<R: Read, S: Sample> BubObjectReader<R, S> {
+    pub fn new(bub_frame_reader: BubFrameReader<R, S>) -> Self {
+        Self { bub_frame_reader }
+    }
+
+    pub fn into_inner(self) -> BubFrameReader<R, S> {
+        self.bub_frame_reader
+    }
+}
+
+impl<R: Read, S: Sample> BubFrameReader<R, S> {
+    pub fn into_bub_object_reader(self) -> BubObjectReader<R, S> {
+        BubObjectReader::new(self)
+    }
+}
+
+impl<R: Read, S: Sample> Iterator for BubObjectReader<R, S> {
+    type Item = Result<BubObjectFrame<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bub_frame_reader = &mut self.bub_frame_reader;
+        if bub_frame_reader.metadata.frames() <= bub_frame_reader.pos {
+            return None;
+        } else {
+            bub_frame_reader.pos += 1;
+        }
+
+        let sample = match bub_frame_reader.read_sample() {
+            Ok(sample) => sample,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let pos = bub_frame_reader.pos;
+        let metadata = &bub_frame_reader.metadata;
+        let mut object_frame = BubObjectFrame {
+            sample: S::default(),
+            bub_absolute_coords_and_volumes: Vec::new(),
+            bub_state: metadata.bub_state,
+        };
+
+        if metadata.bub_state.is_head() || metadata.bub_state.is_body() {
+            let volume_and_interpreter_vec = metadata.bub_fns.to_bub_absolute_coord_and_volume(
+                pos as f64,
+                (pos - metadata.head_absolute_frame + 1) as f64,
+                metadata.frames as f64,
+                metadata.samples_per_sec,
+            );
+
+            object_frame.sample = match (sample, &metadata.bub_sample_kind) {
+                (Some(sample), _) => sample,
+                (None, BubSampleKind::Expr(expr)) => volume_and_interpreter_vec
+                    .iter()
+                    .find(|(volume, _)| *volume != Some(0.0))
+                    .map(|(_, interpreter)| S::from_f64(interpreter.eval_sum(expr).unwrap()))
+                    .unwrap_or_default(),
+                (None, _) => S::default(),
+            };
+            object_frame.bub_absolute_coords_and_volumes = volume_and_interpreter_vec
+                .into_iter()
+                .map(|(volume, interpreter)| (interpreter.uppercase, volume))
+                .collect();
+        }
+
+        Some(Ok(object_frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubMetadata, BubReader, BubState::*, BubWriter};
+    use crate::LpcmKind;
+
+    #[test]
+    fn read_objects() -> Result<()> {
+        let metadata = BubMetadata::new(
+            6,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::Lpcm,
+            String::from("Object"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"1 2 n 0==0 0.5 0 0 0 X>=3 1 3 0 0 X>=3 1",
+            next_head_relative_frame: Some(4),
+            samples: vec![0.25, -0.5],
+        })?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 0==0 1",
+            next_head_relative_frame: None,
+            samples: vec![1.0, 0.75],
+        })?;
+        let v = bub_frame_writer.inner;
+
+        let bub_reader = BubReader::new(&v[..], Vec::new())?;
+        let bub_object_reader = bub_reader
+            .into_bub_frame_reader_kind(None)
+            .into_f32_le()?
+            .into_bub_object_reader();
+
+        // Domains with `X` depend on speakers.
+        let expects = vec![
+            (
+                Head,
+                0.25,
+                vec![
+                    ((1.0, 2.0, 1.0), Some(0.5)),
+                    ((0.0, 0.0, 0.0), None),
+                    ((3.0, 0.0, 0.0), None),
+                ],
+            ),
+            (
+                Body,
+                -0.5,
+                vec![
+                    ((1.0, 2.0, 2.0), Some(0.5)),
+                    ((0.0, 0.0, 0.0), None),
+                    ((3.0, 0.0, 0.0), None),
+                ],
+            ),
+            (Stopped, 0.0, vec![]),
+            (Head, 1.0, vec![((0.0, 0.0, 0.0), Some(1.0))]),
+            (Body, 0.75, vec![((0.0, 0.0, 0.0), Some(1.0))]),
+            (Ended, 0.0, vec![]),
+        ];
+
+        let object_frames = bub_object_reader.collect::<Result<Vec<_>>>()?;
+        assert_eq!(object_frames.len(), expects.len());
+        for (object_frame, expect) in object_frames.into_iter().zip(expects) {
+            assert_eq!(object_frame.bub_state, expect.0);
+            assert_eq!(object_frame.sample, expect.1);
+            let coords_and_volumes: Vec<(BubFnsCoord, Option<f64>)> = expect
+                .2
+                .into_iter()
+                .map(|(coord, volume)| (coord.into(), volume))
+                .collect();
+            assert_eq!(
+                object_frame.bub_absolute_coords_and_volumes,
+                coords_and_volumes
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_expr_objects() -> Result<()> {
+        let bub_reader = BubReader::open("tests/expr_test.bub", Vec::new())?;
+        let bub_object_reader = bub_reader
+            .into_bub_frame_reader_kind(None)
+            .into_f64_le()?
+            .into_bub_object_reader();
+
+        let object_frames = bub_object_reader.collect::<Result<Vec<_>>>()?;
+        let samples: Vec<f64> = object_frames.iter().map(|frame| frame.sample).collect();
+        // "1", "1/n" and "0.1"
+        assert_eq!(samples, [0.0, 1.0, 0.0, 1.0, 0.5, 0.1, 0.0, 0.0]);
+
+        // `Expr` is evaluated in the second `BubFn` while the first one is out of its domain.
+        let metadata = BubMetadata::new(
+            2,
+            1,
+            48000.0,
+            LpcmKind::F64LE,
+            BubSampleKind::default_expr(),
+            String::from("Object"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f64_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"1 0 0 n>=2 1 2 0 0 0==0 1",
+            foot_relative_frame: 2,
+            next_head_relative_frame: None,
+            expression: b"X",
+        })?;
+        let v = bub_frame_writer.inner;
+        let samples = BubReader::new(&v[..], Vec::new())?
+            .into_bub_frame_reader_kind(None)
+            .into_f64_le()?
+            .into_bub_object_reader()
+            .map(|frame| frame.map(|frame| frame.sample))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(samples, [2.0, 1.0]);
+
+        Ok(())
+    }
+}
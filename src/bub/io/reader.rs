@@ -1,8 +1,19 @@
-use crate::bub::{BubFrameReader, BubFrameReaderKind, BubMetadata};
-use crate::{BubFnsCoord, LpcmKind, OaoSpaces, Sample};
+use crate::bub::{BubBlockSummaries, BubBlocks, BubMetadata};
+#[cfg(feature = "std")]
+use crate::bub::{BubFrameReader, BubFrameReaderKind};
+use crate::io::{Read, Result};
+#[cfg(feature = "std")]
+use crate::verify::VerifyReport;
+use crate::{BubFnsCoord, Sample};
+#[cfg(feature = "std")]
+use crate::{LpcmKind, OaoSpaces};
+use alloc::vec::Vec;
 use mycrc::CRC;
+#[cfg(feature = "std")]
 use std::fs::File;
-use std::io::{BufReader, Read, Result};
+#[cfg(feature = "std")]
+use std::io::{BufReader, Error, ErrorKind, Seek};
+#[cfg(feature = "std")]
 use std::path::Path;
 
 pub struct BubReader<R: Read> {
@@ -26,6 +37,34 @@ impl<R: Read> BubReader<R> {
         })
     }
 
+    /// Iterate over the blocks without decoding samples.
+    /// Samples are read and discarded to check the CRCs.
+    pub fn blocks(self) -> BubBlockSummaries<R> {
+        BubBlockSummaries::new(self.inner, (self.metadata, self.crc))
+    }
+
+    /// Iterate over the blocks with samples decoded as `S`.
+    /// Unlike `BubFrameReader`, Bubble Functions are not applied, so this works without `std`.
+    pub fn decoded_blocks<S: Sample>(self) -> BubBlocks<R, S> {
+        BubBlocks::new(self.inner, (self.metadata, self.crc))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> BubReader<R> {
+    /// Walk the whole bubble file and report every problem, such as a wrong CRC.
+    /// Returns an error only if `inner` can't be read.
+    pub fn verify(inner: R) -> Result<VerifyReport> {
+        crate::bub::verify::verify(inner)
+    }
+
+    /// Walk the whole bubble file and return its metadata and blocks as JSON,
+    /// with byte offsets, sample statistics and CRC status.
+    /// Broken files are dumped as far as they can be read, with `error` and `error_byte_offset`.
+    pub fn dump_json(inner: R) -> Result<String> {
+        crate::bub::dump::dump(inner)
+    }
+
     /// # Safety
     ///
     /// This is unsafe, due to the type of sample isn’t checked:
@@ -42,6 +81,32 @@ impl<R: Read> BubReader<R> {
         )
     }
 
+    /// Type of sample must follow `lpcm_kind`, such as the `LpcmKind` of a Floaout file.
+    /// Returns `InvalidData` if the bubble is not `lpcm_kind`.
+    pub fn try_into_bub_frame_reader<S: Sample>(
+        self,
+        lpcm_kind: LpcmKind,
+        oao_spaces: Option<OaoSpaces>,
+    ) -> Result<BubFrameReader<R, S>> {
+        if self.metadata.lpcm_kind() != lpcm_kind {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "bubble is `{:?}`, but `{:?}` is expected",
+                    self.metadata.lpcm_kind(),
+                    lpcm_kind
+                ),
+            ));
+        }
+
+        Ok(BubFrameReader::new(
+            self.inner,
+            (self.metadata, self.crc),
+            self.speakers_absolute_coord,
+            oao_spaces,
+        ))
+    }
+
     pub fn into_bub_frame_reader_kind(
         self,
         oao_spaces: Option<OaoSpaces>,
@@ -59,11 +124,44 @@ impl<R: Read> BubReader<R> {
                 self.speakers_absolute_coord,
                 oao_spaces,
             )),
-            _ => unimplemented!(),
+            LpcmKind::I16LE => BubFrameReaderKind::I16LE(BubFrameReader::<R, f32>::new(
+                self.inner,
+                (self.metadata, self.crc),
+                self.speakers_absolute_coord,
+                oao_spaces,
+            )),
+            LpcmKind::I24LE => BubFrameReaderKind::I24LE(BubFrameReader::<R, f32>::new(
+                self.inner,
+                (self.metadata, self.crc),
+                self.speakers_absolute_coord,
+                oao_spaces,
+            )),
+            LpcmKind::I32LE => BubFrameReaderKind::I32LE(BubFrameReader::<R, f64>::new(
+                self.inner,
+                (self.metadata, self.crc),
+                self.speakers_absolute_coord,
+                oao_spaces,
+            )),
+            LpcmKind::U8 => BubFrameReaderKind::U8(BubFrameReader::<R, f32>::new(
+                self.inner,
+                (self.metadata, self.crc),
+                self.speakers_absolute_coord,
+                oao_spaces,
+            )),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl<R: Read + Seek> BubReader<R> {
+    /// Iterate over the blocks without decoding samples.
+    /// Samples are skipped by seeking, so the CRCs of blocks with samples are not checked.
+    pub fn seekable_blocks(self) -> BubBlockSummaries<R> {
+        BubBlockSummaries::new_seekable(self.inner, (self.metadata, self.crc))
+    }
+}
+
+#[cfg(feature = "std")]
 impl BubReader<BufReader<File>> {
     pub fn open<P: AsRef<Path>>(
         filename: P,
@@ -96,6 +194,24 @@ mod tests {
         assert_eq!(bub_reader.metadata, metadata);
     }
 
+    #[test]
+    fn try_into_bub_frame_reader() -> std::io::Result<()> {
+        let bub_reader = BubReader::open("tests/lpcm_test.bub", vec![(0.0, 0.0, 0.0).into()])?;
+        let e = bub_reader
+            .try_into_bub_frame_reader::<f64>(LpcmKind::F64LE, None)
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+
+        let bub_reader = BubReader::open("tests/lpcm_test.bub", vec![(0.0, 0.0, 0.0).into()])?;
+        let frames = bub_reader
+            .try_into_bub_frame_reader::<f32>(LpcmKind::F32LE, None)?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        assert_eq!(frames.len(), 8);
+
+        Ok(())
+    }
+
     #[test]
     fn read_lpcm_frames() -> std::io::Result<()> {
         let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into(), (3.0, 0.0, 0.0).into()];
@@ -165,4 +281,156 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn write_and_read_i16_le_frames() -> std::io::Result<()> {
+        use crate::bub::{BubFnsBlock, BubWriter};
+
+        let metadata = BubMetadata::new(
+            4,
+            1,
+            48000.0,
+            LpcmKind::I16LE,
+            BubSampleKind::Lpcm,
+            String::from("16-bit"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_i16_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3 0.5",
+            next_head_relative_frame: None,
+            samples: vec![0.5, -0.25, 1.0, -1.0],
+        })?;
+        let v = bub_frame_writer.inner;
+
+        let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into(), (3.0, 0.0, 0.0).into()];
+        let bub_reader = BubReader::new(&v[..], speakers_absolute_coord)?;
+        let mut bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_i16_le()?;
+
+        let expects = vec![
+            (Head, [0.25, 0.0]),
+            (Body, [-0.125, 0.0]),
+            (Body, [32767.0 / 65536.0, 0.0]),
+            (Body, [-0.5, 0.0]),
+        ];
+
+        for expect in expects {
+            let frame = bub_frame_reader.next().unwrap()?;
+            assert_eq!(bub_frame_reader.metadata.bub_state, expect.0);
+            assert_eq!(frame.0, expect.1);
+        }
+
+        assert!(bub_frame_reader.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_and_read_lossless_lpcm_frames() -> std::io::Result<()> {
+        use crate::bub::{BubFnsBlock, BubWriter};
+
+        let mut seed = 7u32;
+        let samples: Vec<f32> = (0..1000)
+            .map(|n| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (n as f32 * 0.03).sin() * 0.8 + (seed >> 16) as f32 / 65536.0 * 0.01
+            })
+            .collect();
+
+        let write = |lpcm_kind, bub_sample_kind| -> std::io::Result<Vec<u8>> {
+            let metadata = BubMetadata::new(
+                1001,
+                1,
+                48000.0,
+                lpcm_kind,
+                bub_sample_kind,
+                String::from("Lossless"),
+            );
+            let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+            let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+                bub_fns: b"0 0 0 X<3 1",
+                next_head_relative_frame: Some(601),
+                samples: samples[..600].to_vec(),
+            })?;
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+                bub_fns: b"0 0 0 X<3 1",
+                next_head_relative_frame: None,
+                samples: samples[600..].to_vec(),
+            })?;
+            Ok(bub_frame_writer.inner)
+        };
+
+        let lpcm = write(LpcmKind::F32LE, BubSampleKind::Lpcm)?;
+        let lossless_lpcm = write(LpcmKind::F32LE, BubSampleKind::LosslessLpcm)?;
+        assert!(lossless_lpcm.len() < lpcm.len());
+
+        let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into()];
+        let bub_reader = BubReader::new(&lossless_lpcm[..], speakers_absolute_coord)?;
+        assert_eq!(
+            bub_reader.metadata.bub_sample_kind,
+            BubSampleKind::LosslessLpcm
+        );
+        let bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_f32_le()?;
+
+        let frames = bub_frame_reader.collect::<std::io::Result<Vec<_>>>()?;
+        assert_eq!(frames.len(), 1001);
+        for (frame, sample) in frames.iter().zip(&samples) {
+            assert_eq!(frame.0[0].to_bits(), sample.to_bits());
+        }
+        assert_eq!(frames[1000].0[0], 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_and_read_mdct_frames() -> std::io::Result<()> {
+        use crate::bub::{BubFnsBlock, BubWriter};
+
+        let samples: Vec<f32> = (0..24000)
+            .map(|n| (n as f32 * 0.0576).sin() * 0.5 + (n as f32 * 0.21).sin() * 0.25)
+            .collect();
+
+        let metadata = BubMetadata::new(
+            24000,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::Mdct,
+            String::from("MDCT"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.mdct_bitrate = 64000;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3 1",
+            next_head_relative_frame: Some(10001),
+            samples: samples[..10000].to_vec(),
+        })?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3 1",
+            next_head_relative_frame: None,
+            samples: samples[10000..].to_vec(),
+        })?;
+        let v = bub_frame_writer.inner;
+        // 64 kbps for 0.5 seconds and headers
+        assert!(v.len() < 4000 + 200);
+
+        let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into()];
+        let bub_reader = BubReader::new(&v[..], speakers_absolute_coord)?;
+        let bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_f32_le()?;
+        let frames = bub_frame_reader.collect::<std::io::Result<Vec<_>>>()?;
+        assert_eq!(frames.len(), samples.len());
+
+        let mut signal = 0.0;
+        let mut noise = 0.0;
+        for (frame, sample) in frames.iter().zip(&samples) {
+            signal += (sample * sample) as f64;
+            noise += ((frame.0[0] - sample) * (frame.0[0] - sample)) as f64;
+        }
+        let snr = 10.0 * (signal / noise).log10();
+        assert!(30.0 < snr, "SNR: {}", snr);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,400 @@
+use crate::bub::functions::{BubFns, BubFnsAST};
+use crate::bub::io::frame_reader::{expr_frame, lpcm_frame, smoothed_frame, BubFnsTime, GainRamp};
+use crate::bub::{BubFrameReader, BubSampleKind};
+use crate::resample::{ResampleQuality, SincKernel};
+use crate::{Frame, FrameReader, Sample};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{Read, Result};
+
+/// Block which buffered frames belong to
+struct Segment {
+    /// First absolute frame of the bubble in this segment
+    first_frame: u64,
+    /// `None` if the frames are out of blocks
+    block: Option<Block>,
+}
+
+struct Block {
+    head_absolute_frame: u64,
+    bub_fns: BubFns,
+    expr: Option<BubFnsAST>,
+}
+
+/// Reads a bubble at other samples per sec.
+///
+/// Lpcm samples are resampled before Bubble Functions are applied, and Bubble Functions and
+/// `Expr` are evaluated on the new timeline.
+/// If the samples per sec are the same, frames of `bub_frame_reader` are returned as they are.
+/// Otherwise, `smoothing_frames` of `bub_frame_reader` are frames on the new timeline.
+pub struct ResampledBubFrameReader<R: Read, S: Sample> {
+    pub bub_frame_reader: BubFrameReader<R, S>,
+    pub pos: u64,
+    samples_per_sec: f64,
+    kernel: Option<SincKernel>,
+    /// Mono samples from the 0-based index `start`
+    samples: VecDeque<f64>,
+    start: i64,
+    segments: VecDeque<Segment>,
+    /// Empty if the previous frame is out of blocks
+    gain_ramps: Vec<GainRamp>,
+}
+
+impl<R: Read, S: Sample> FrameReader<R, S> for ResampledBubFrameReader<R, S> {
+    fn get_ref(&self) -> &R {
+        self.bub_frame_reader.get_ref()
+    }
+    fn get_mut(&mut self) -> &mut R {
+        self.bub_frame_reader.get_mut()
+    }
+    fn into_inner(self) -> R {
+        self.bub_frame_reader.into_inner()
+    }
+
+    fn frames(&self) -> u64 {
+        match &self.kernel {
+            Some(kernel) => kernel.output_frames(self.bub_frame_reader.frames()),
+            None => self.bub_frame_reader.frames(),
+        }
+    }
+
+    fn samples_per_sec(&self) -> f64 {
+        self.samples_per_sec
+    }
+
+    fn number_of_channels(&self) -> u32 {
+        self.bub_frame_reader.number_of_channels()
+    }
+}
+
+impl<R: Read, S: Sample> ResampledBubFrameReader<R, S> {
+    pub fn new(
+        bub_frame_reader: BubFrameReader<R, S>,
+        samples_per_sec: f64,
+        quality: ResampleQuality,
+    ) -> Self {
+        let bub_samples_per_sec = bub_frame_reader.samples_per_sec();
+        let kernel = if bub_samples_per_sec == samples_per_sec {
+            None
+        } else {
+            Some(SincKernel::new(
+                bub_samples_per_sec,
+                samples_per_sec,
+                quality,
+            ))
+        };
+
+        Self {
+            bub_frame_reader,
+            pos: 0,
+            samples_per_sec,
+            kernel,
+            samples: VecDeque::new(),
+            start: 0,
+            segments: VecDeque::new(),
+            gain_ramps: Vec::new(),
+        }
+    }
+
+    pub fn into_bub_frame_reader(self) -> BubFrameReader<R, S> {
+        self.bub_frame_reader
+    }
+
+    /// Skip the first `time_offset` frames of the bubble, at its own samples per sec.
+    /// The rest of the bubble is on the same timeline, so `pos` starts from the frame
+    /// at `time_offset`. This must be called before reading frames.
+    pub fn skip_bub_frames(&mut self, time_offset: u64) -> Result<()> {
+        let kernel = match &self.kernel {
+            Some(kernel) => kernel.clone(),
+            None => {
+                for _ in 0..time_offset {
+                    match self.bub_frame_reader.next_sample() {
+                        Some(result) => {
+                            result?;
+                        }
+                        None => break,
+                    }
+                }
+                self.pos = self.bub_frame_reader.pos;
+                return Ok(());
+            }
+        };
+
+        self.pos = kernel.output_frames(time_offset);
+        // Samples before the first one used at `pos` are not buffered.
+        let (first, _) = kernel.range(kernel.position(self.pos));
+        while (self.bub_frame_reader.pos as i64) < first {
+            match self.read_bub_frame() {
+                Some(result) => result?,
+                None => break,
+            }
+            self.samples.pop_front();
+            self.start += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Read the next frame of the bubble into the buffers.
+    fn read_bub_frame(&mut self) -> Option<Result<()>> {
+        let sample = match self.bub_frame_reader.next_sample()? {
+            Ok(sample) => sample,
+            Err(e) => return Some(Err(e)),
+        };
+        self.samples
+            .push_back(sample.map(S::to_f64).unwrap_or_default());
+
+        let pos = self.bub_frame_reader.pos;
+        let metadata = &self.bub_frame_reader.metadata;
+        let is_in_block = metadata.bub_state.is_head() || metadata.bub_state.is_body();
+        let was_in_block = matches!(self.segments.back(), Some(Segment { block: Some(_), .. }));
+        if metadata.bub_state.is_head() || is_in_block != was_in_block {
+            let block = if is_in_block {
+                Some(Block {
+                    head_absolute_frame: metadata.head_absolute_frame,
+                    bub_fns: metadata.bub_fns.clone(),
+                    expr: match &metadata.bub_sample_kind {
+                        BubSampleKind::Expr(expr) => Some(expr.clone()),
+                        _ => None,
+                    },
+                })
+            } else {
+                None
+            };
+            self.segments.push_back(Segment {
+                first_frame: pos,
+                block,
+            });
+        }
+
+        Some(Ok(()))
+    }
+}
+
+impl<R: Read, S: Sample> Iterator for ResampledBubFrameReader<R, S> {
+    type Item = Result<Frame<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let kernel = match &self.kernel {
+            Some(kernel) => kernel.clone(),
+            None => {
+                let frame = self.bub_frame_reader.next();
+                self.pos = self.bub_frame_reader.pos;
+                return frame;
+            }
+        };
+        if self.frames() <= self.pos {
+            return None;
+        }
+        let position = kernel.position(self.pos);
+        self.pos += 1;
+
+        let (first, last) = kernel.range(position);
+        while self.bub_frame_reader.pos as i64 <= last {
+            match self.read_bub_frame() {
+                Some(Ok(())) => (),
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+        while self.start < first && !self.samples.is_empty() {
+            self.samples.pop_front();
+            self.start += 1;
+        }
+
+        // Absolute frame of the bubble at `position`
+        let bub_pos = position.floor() as u64 + 1;
+        while matches!(self.segments.get(1), Some(segment) if segment.first_frame <= bub_pos) {
+            self.segments.pop_front();
+        }
+
+        let channels = self.bub_frame_reader.number_of_channels() as usize;
+        let mut frame: Frame<S> = vec![S::default(); channels].into();
+        let block = match self.segments.front() {
+            Some(Segment {
+                first_frame,
+                block: Some(block),
+            }) if *first_frame <= bub_pos => block,
+            _ => {
+                self.gain_ramps.clear();
+                return Some(Ok(frame));
+            }
+        };
+
+        let speakers_absolute_coord = &self.bub_frame_reader.speakers_absolute_coord;
+        let time = BubFnsTime {
+            absolute_frame: self.pos as f64,
+            relative_frame: self.pos as f64 - (block.head_absolute_frame - 1) as f64 / kernel.ratio,
+            frames: self.bub_frame_reader.metadata.frames as f64 / kernel.ratio,
+            samples_per_sec: self.samples_per_sec,
+        };
+        let sample = match &block.expr {
+            Some(_) => None,
+            None => {
+                let (samples, start) = (&self.samples, self.start);
+                let sample = kernel.interpolate(position, |i| {
+                    usize::try_from(i - start)
+                        .ok()
+                        .and_then(|i| samples.get(i).copied())
+                        .unwrap_or_default()
+                });
+                Some(S::from_f64(sample))
+            }
+        };
+        let smoothing_frames = self.bub_frame_reader.smoothing_frames;
+        if smoothing_frames != 0 {
+            smoothed_frame(
+                &block.bub_fns,
+                speakers_absolute_coord,
+                time,
+                sample,
+                block.expr.as_ref(),
+                smoothing_frames,
+                &mut self.gain_ramps,
+                &mut frame,
+            );
+        } else {
+            match (sample, &block.expr) {
+                (Some(sample), _) => lpcm_frame(
+                    &block.bub_fns,
+                    speakers_absolute_coord,
+                    time,
+                    sample,
+                    &mut frame,
+                ),
+                (None, Some(expr)) => expr_frame(
+                    &block.bub_fns,
+                    speakers_absolute_coord,
+                    time,
+                    expr,
+                    &mut frame,
+                ),
+                (None, None) => (),
+            }
+        }
+
+        Some(Ok(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubMetadata, BubReader, BubWriter};
+    use crate::LpcmKind;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn resample_lpcm() -> Result<()> {
+        let sine = |n: u64, samples_per_sec: f64| {
+            (2.0 * PI * 1000.0 * n as f64 / samples_per_sec).sin() * 0.5
+        };
+        let metadata = BubMetadata::new(
+            4800,
+            1,
+            48000.0,
+            LpcmKind::F64LE,
+            BubSampleKind::Lpcm,
+            String::from("Resample"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f64_le()?;
+        // Volume is 1 for the first half of a second on the new timeline.
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 X<3&&N<=4800 1",
+            next_head_relative_frame: None,
+            samples: (0..4800).map(|n| sine(n, 48000.0)).collect(),
+        })?;
+        let v = bub_frame_writer.inner;
+
+        let bub_reader = BubReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?;
+        let bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_f64_le()?;
+        let resampled_bub_frame_reader =
+            ResampledBubFrameReader::new(bub_frame_reader, 96000.0, ResampleQuality::High);
+        assert_eq!(resampled_bub_frame_reader.frames(), 9600);
+        let frames = resampled_bub_frame_reader.collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames.len(), 9600);
+
+        for (n, frame) in frames.iter().enumerate().take(4000).skip(800) {
+            let error = (frame.0[0] - sine(n as u64, 96000.0)).abs();
+            assert!(error < 1e-3, "frame {}: {}", n, error);
+        }
+        assert!(frames[4800..].iter().all(|frame| frame.0[0] == 0.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resample_expr() -> Result<()> {
+        let metadata = BubMetadata::new(
+            8,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::default_expr(),
+            String::from("Resample"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 X<3 1",
+            foot_relative_frame: 2,
+            next_head_relative_frame: Some(5),
+            expression: b"0.1*N",
+        })?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 X<3 1",
+            foot_relative_frame: 4,
+            next_head_relative_frame: None,
+            expression: b"n",
+        })?;
+        let v = bub_frame_writer.inner;
+
+        let bub_reader = BubReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?;
+        let bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_f32_le()?;
+        let frames = ResampledBubFrameReader::new(bub_frame_reader, 96000.0, Default::default())
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+
+        // `N` and `n` count frames on the new timeline.
+        assert_eq!(
+            frames,
+            [0.1, 0.2, 0.3, 0.4, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn smooth_resampled_volumes() -> Result<()> {
+        let metadata = BubMetadata::new(
+            4,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::default_expr(),
+            String::from("Resample"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 n<=4 1",
+            foot_relative_frame: 4,
+            next_head_relative_frame: None,
+            expression: b"0.5",
+        })?;
+        let v = bub_frame_writer.inner;
+
+        let bub_reader = BubReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?;
+        let mut bub_frame_reader = bub_reader.into_bub_frame_reader_kind(None).into_f32_le()?;
+        bub_frame_reader.smoothing_frames = 2;
+        let frames = ResampledBubFrameReader::new(bub_frame_reader, 96000.0, Default::default())
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Volumes move to 0.0 over 2 frames on the new timeline.
+        assert_eq!(frames, [0.5, 0.5, 0.5, 0.5, 0.25, 0.0, 0.0, 0.0]);
+
+        Ok(())
+    }
+}
@@ -45,7 +45,22 @@ impl<W: Write> BubWriter<W> {
                 self.inner,
                 (self.metadata, self.crc),
             )),
-            _ => unimplemented!(),
+            LpcmKind::I16LE => BubFrameWriterKind::I16LE(BubFrameWriter::<W, f32>::new(
+                self.inner,
+                (self.metadata, self.crc),
+            )),
+            LpcmKind::I24LE => BubFrameWriterKind::I24LE(BubFrameWriter::<W, f32>::new(
+                self.inner,
+                (self.metadata, self.crc),
+            )),
+            LpcmKind::I32LE => BubFrameWriterKind::I32LE(BubFrameWriter::<W, f64>::new(
+                self.inner,
+                (self.metadata, self.crc),
+            )),
+            LpcmKind::U8 => BubFrameWriterKind::U8(BubFrameWriter::<W, f32>::new(
+                self.inner,
+                (self.metadata, self.crc),
+            )),
         }
     }
 }
@@ -1,12 +1,15 @@
-use crate::bub::{
-    functions::{parse, BubFns, BubFnsAST, BubFnsVariable},
-    BubID,
+#[cfg(feature = "std")]
+use crate::bub::functions::{parse, BubFns, BubFnsAST, BubFnsVariable};
+use crate::bub::BubID;
+use crate::io::{ErrorKind, Read, ReadExt, Result, Write, WriteExt};
+use crate::tags::{check_spec_version, Tags, TAGS_SPEC_VERSION};
+use crate::utils::{
+    check_string_size, read_crc, read_sized_string_and_calc_bytes, write_crc,
+    write_sized_str_and_calc_bytes,
 };
-use crate::io::{ReadExt, WriteExt};
-use crate::utils::{read_crc, write_crc};
 use crate::{LpcmKind, Metadata, CRC_32K_4_2};
+use alloc::string::String;
 use mycrc::CRC;
-use std::io::{ErrorKind, Read, Result, Write};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum BubState {
@@ -37,9 +40,16 @@ impl BubState {
 #[derive(Clone, Debug, PartialEq)]
 pub enum BubSampleKind {
     Lpcm,
+    /// Needs the `std` feature.
+    #[cfg(feature = "std")]
     Expr(BubFnsAST),
+    /// Lpcm compressed without loss
+    LosslessLpcm,
+    /// Lpcm compressed with loss by the MDCT
+    Mdct,
 }
 
+#[cfg(feature = "std")]
 impl From<BubFnsAST> for BubSampleKind {
     fn from(ast: BubFnsAST) -> Self {
         Self::Expr(ast)
@@ -47,25 +57,36 @@ impl From<BubFnsAST> for BubSampleKind {
 }
 
 impl BubSampleKind {
+    #[cfg(feature = "std")]
     pub fn default_expr() -> Self {
         parse("0".as_bytes(), &BubFnsVariable::Sum).unwrap().into()
     }
 
-    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
-        let value: u8 = reader.read_le()?;
+    fn try_from_u8(value: u8) -> Result<Self> {
         Ok(match value {
             0 => Self::Lpcm,
+            #[cfg(feature = "std")]
             1 => Self::default_expr(),
+            #[cfg(not(feature = "std"))]
+            1 => {
+                return Err(crate::io::Error::new(
+                    ErrorKind::Unsupported,
+                    "expression bubbles need the `std` feature",
+                ))
+            }
+            2 => Self::LosslessLpcm,
+            3 => Self::Mdct,
             _ => return Err(ErrorKind::InvalidData.into()),
         })
     }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let value: u8 = reader.read_le()?;
+        Self::try_from_u8(value)
+    }
     pub fn read_and_calc_bytes<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<Self> {
         let value: u8 = reader.read_le_and_calc_bytes(crc)?;
-        Ok(match value {
-            0 => Self::Lpcm,
-            1 => Self::default_expr(),
-            _ => return Err(ErrorKind::InvalidData.into()),
-        })
+        Self::try_from_u8(value)
     }
 
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
@@ -78,7 +99,10 @@ impl BubSampleKind {
     pub fn from_u8(value: u8) -> Self {
         match value {
             0 => Self::Lpcm,
+            #[cfg(feature = "std")]
             1 => Self::default_expr(),
+            2 => Self::LosslessLpcm,
+            3 => Self::Mdct,
             _ => unimplemented!(),
         }
     }
@@ -86,7 +110,10 @@ impl BubSampleKind {
     pub const fn to_u8(&self) -> u8 {
         match self {
             Self::Lpcm => 0,
+            #[cfg(feature = "std")]
             Self::Expr(_) => 1,
+            Self::LosslessLpcm => 2,
+            Self::Mdct => 3,
         }
     }
 }
@@ -110,6 +137,8 @@ pub struct BubMetadata {
     pub bub_sample_kind: BubSampleKind,
     /// Name of Bubble
     pub name: String,
+    /// Tags from spec version 1
+    pub tags: Tags,
 
     /// Bubble State
     pub bub_state: BubState,
@@ -117,6 +146,7 @@ pub struct BubMetadata {
     pub head_absolute_frame: u64,
 
     /// Bubble Functions
+    #[cfg(feature = "std")]
     pub bub_fns: BubFns,
     /// Foot Absolute Frame Plus One
     pub foot_absolute_frame_plus_one: u64,
@@ -143,10 +173,12 @@ impl BubMetadata {
             lpcm_kind,
             bub_sample_kind,
             name,
+            tags: Tags::new(),
 
             bub_state: BubState::Stopped,
             head_absolute_frame: first_head_absolute_frame,
 
+            #[cfg(feature = "std")]
             bub_fns: BubFns::new(),
             foot_absolute_frame_plus_one: 0,
             // TODO: Value must not be 0
@@ -247,6 +279,7 @@ impl BubMetadata {
         }
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn set_next_head_absolute_frame_from_relative(
         &mut self,
         next_head_relative_frame: u64,
@@ -257,61 +290,65 @@ impl BubMetadata {
     }
 
     // IO
-    /// TODO: Refactoring
-    pub(crate) fn read_next_head_absolute_frame_from_relative<R: std::io::Read>(
-        &mut self,
-        reader: &mut R,
-        pos: u64,
-        crc: &mut CRC<u32>,
-    ) -> Result<()> {
-        let next_head_relative_frame: u64 = reader.read_le_and_calc_bytes(crc)?;
-        self.set_next_head_absolute_frame_from_relative(next_head_relative_frame, pos);
+    pub fn read<R: Read>(reader: &mut R) -> Result<(Self, CRC<u32>)> {
+        let mut crc = CRC_32K_4_2;
+        let mut metadata = Self::read_and_calc_bytes(reader, &mut crc)?;
+        // CRC
+        read_crc(reader, &mut crc)?;
 
-        Ok(())
+        // Tags
+        if metadata.spec_version >= TAGS_SPEC_VERSION {
+            metadata.tags = Tags::read_and_calc_bytes(reader, &mut crc)?;
+            read_crc(reader, &mut crc)?;
+        }
+
+        Ok((metadata, crc))
     }
 
-    pub fn read<R: std::io::Read>(reader: &mut R) -> Result<(Self, CRC<u32>)> {
-        let mut crc = CRC_32K_4_2;
+    /// Bytes after the metadata are left in the buffer of `reader`.
+    #[cfg(feature = "async")]
+    pub async fn read_async<R: futures_util::io::AsyncBufRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<(Self, CRC<u32>)> {
+        crate::io::async_ext::read_with(reader, |reader| Self::read(reader)).await
+    }
 
-        let spec_version = reader.read_le_and_calc_bytes(&mut crc)?;
-        let bub_id = BubID::read_and_calc_bytes(reader, &mut crc)?;
-        let bub_version = reader.read_le_and_calc_bytes(&mut crc)?;
+    /// Read the file header without its CRC.
+    pub(crate) fn read_and_calc_bytes<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<Self> {
+        let spec_version = reader.read_le_and_calc_bytes(crc)?;
+        let bub_id = BubID::read_with_spec_version_and_calc_bytes(reader, spec_version, crc)?;
+        let bub_version = reader.read_le_and_calc_bytes(crc)?;
 
-        let frames = reader.read_le_and_calc_bytes(&mut crc)?;
+        let frames = reader.read_le_and_calc_bytes(crc)?;
 
-        let first_head_absolute_frame: u64 = reader.read_le_and_calc_bytes(&mut crc)?;
+        let first_head_absolute_frame: u64 = reader.read_le_and_calc_bytes(crc)?;
         let next_head_absolute_frame =
             Self::next_head_absolute_frame_from_relative(first_head_absolute_frame, 1);
-        let samples_per_sec = reader.read_le_and_calc_bytes(&mut crc)?;
-        let lpcm_kind = LpcmKind::read_and_calc_bytes(reader, &mut crc)?;
-        let bub_sample_kind = BubSampleKind::read_and_calc_bytes(reader, &mut crc)?;
+        let samples_per_sec = reader.read_le_and_calc_bytes(crc)?;
+        let lpcm_kind = LpcmKind::read_and_calc_bytes(reader, crc)?;
+        let bub_sample_kind = BubSampleKind::read_and_calc_bytes(reader, crc)?;
 
-        let name_size: u8 = reader.read_le_and_calc_bytes(&mut crc)?;
-        let name = reader.read_string_for_and_calc_bytes(name_size as usize, &mut crc)?;
+        let name = read_sized_string_and_calc_bytes(reader, spec_version, crc)?;
 
-        // CRC
-        read_crc(reader, &mut crc)?;
-
-        Ok((
-            Self {
-                spec_version,
-                bub_id,
-                bub_version,
-                frames,
-                samples_per_sec,
-                lpcm_kind,
-                bub_sample_kind,
-                name,
+        Ok(Self {
+            spec_version,
+            bub_id,
+            bub_version,
+            frames,
+            samples_per_sec,
+            lpcm_kind,
+            bub_sample_kind,
+            name,
+            tags: Tags::new(),
 
-                bub_state: BubState::Stopped,
-                head_absolute_frame: first_head_absolute_frame,
+            bub_state: BubState::Stopped,
+            head_absolute_frame: first_head_absolute_frame,
 
-                bub_fns: BubFns::new(),
-                foot_absolute_frame_plus_one: 0,
-                next_head_absolute_frame,
-            },
-            crc,
-        ))
+            #[cfg(feature = "std")]
+            bub_fns: BubFns::new(),
+            foot_absolute_frame_plus_one: 0,
+            next_head_absolute_frame,
+        })
     }
 
     // fn next_head_absolute_frame_into_relative(&self, pos: u64) -> u64 {
@@ -321,11 +358,21 @@ impl BubMetadata {
     //     }
     // }
 
-    pub fn write<W: std::io::Write>(&self, writer: &mut W) -> Result<CRC<u32>> {
+    /// Returns an error if the metadata can't be written, before anything is written.
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        check_spec_version(&self.tags, self.spec_version)?;
+        self.tags.check_writable()?;
+        self.bub_id.check_spec_version(self.spec_version)?;
+        check_string_size("name", &self.name, self.spec_version)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<CRC<u32>> {
+        self.check_writable()?;
         let mut crc = CRC_32K_4_2;
 
         writer.write_le_and_calc_bytes(self.spec_version, &mut crc)?;
-        self.bub_id.write_and_calc_bytes(writer, &mut crc)?;
+        self.bub_id
+            .write_with_spec_version_and_calc_bytes(writer, self.spec_version, &mut crc)?;
         writer.write_le_and_calc_bytes(self.bub_version, &mut crc)?;
 
         writer.write_le_and_calc_bytes(self.frames, &mut crc)?;
@@ -334,14 +381,27 @@ impl BubMetadata {
         self.lpcm_kind.write_and_calc_bytes(writer, &mut crc)?;
         self.bub_sample_kind
             .write_and_calc_bytes(writer, &mut crc)?;
-        writer.write_le_and_calc_bytes(self.name.len() as u8, &mut crc)?;
-        writer.write_str_and_calc_bytes(&self.name, &mut crc)?;
+        write_sized_str_and_calc_bytes(writer, "name", &self.name, self.spec_version, &mut crc)?;
 
         // CRC
         write_crc(writer, &mut crc)?;
 
+        // Tags
+        if self.spec_version >= TAGS_SPEC_VERSION {
+            self.tags.write_and_calc_bytes(writer, &mut crc)?;
+            write_crc(writer, &mut crc)?;
+        }
+
         Ok(crc)
     }
+
+    #[cfg(feature = "async")]
+    pub async fn write_async<W: futures_util::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<CRC<u32>> {
+        crate::io::async_ext::write_with(writer, |writer| self.write(writer)).await
+    }
 }
 
 impl Metadata for BubMetadata {}
@@ -349,6 +409,9 @@ impl Metadata for BubMetadata {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bub::RGB_SPEC_VERSION;
+    use crate::tags::TagKey;
+    use crate::utils::U16_STRING_SIZE_SPEC_VERSION;
 
     #[test]
     fn write_and_read() -> Result<()> {
@@ -370,6 +433,56 @@ mod tests {
 
         assert_eq!(val, expected);
 
+        // Tags
+        let mut bub_metadata = expected;
+        bub_metadata
+            .tags
+            .insert(TagKey::Software, String::from("floaout"));
+        assert!(bub_metadata.write(&mut Vec::new()).is_err());
+        bub_metadata.spec_version = TAGS_SPEC_VERSION;
+        let expected = bub_metadata.clone();
+
+        let mut v: Vec<u8> = Vec::new();
+        bub_metadata.write(&mut v)?;
+        let mut reader = &v[..];
+        let val = BubMetadata::read(&mut reader)?.0;
+        assert_eq!(val, expected);
+        assert!(reader.is_empty());
+        let mut long_key = bub_metadata.clone();
+        long_key
+            .tags
+            .insert(TagKey::new(&"K".repeat(256)), String::new());
+        let mut v: Vec<u8> = Vec::new();
+        assert!(long_key.write(&mut v).is_err());
+        assert!(v.is_empty());
+
+        // Name
+        bub_metadata.name = "n".repeat(256);
+        let mut v: Vec<u8> = Vec::new();
+        assert!(bub_metadata.write(&mut v).is_err());
+        assert!(v.is_empty());
+        bub_metadata.spec_version = U16_STRING_SIZE_SPEC_VERSION;
+        let expected = bub_metadata.clone();
+        let mut v: Vec<u8> = Vec::new();
+        bub_metadata.write(&mut v)?;
+        assert_eq!(BubMetadata::read(&mut &v[..])?.0, expected);
+
+        // Color
+        bub_metadata.bub_id.rgb = Some((1.0, 0.5, 0.25).into());
+        let mut v: Vec<u8> = Vec::new();
+        assert!(bub_metadata.write(&mut v).is_err());
+        assert!(v.is_empty());
+        bub_metadata.spec_version = RGB_SPEC_VERSION;
+        for rgb in [Some((1.0, 0.5, 0.25).into()), None] {
+            bub_metadata.bub_id.rgb = rgb;
+            let expected = bub_metadata.clone();
+            let mut v: Vec<u8> = Vec::new();
+            bub_metadata.write(&mut v)?;
+            let mut reader = &v[..];
+            assert_eq!(BubMetadata::read(&mut reader)?.0, expected);
+            assert!(reader.is_empty());
+        }
+
         Ok(())
     }
 }
@@ -1,14 +1,35 @@
+#[cfg(feature = "std")]
 pub use self::functions::{
     BubFn, BubFns, BubFnsAST, BubFnsInterpreter, BubFnsRules, BubFnsVariable,
 };
-pub use self::id::BubID;
+pub use self::id::{BubID, RGB_SPEC_VERSION};
+pub(crate) use self::io::read_payload_and_calc_bytes;
+#[cfg(feature = "std")]
+pub(crate) use self::io::write_payload_and_calc_bytes;
+#[cfg(feature = "async")]
+pub use self::io::{AsyncBubFrameReader, AsyncBubFrameWriter};
 pub use self::io::{
-    BubFnsBlock, BubFrameReader, BubFrameReaderKind, BubFrameWriter, BubFrameWriterKind, BubReader,
-    BubWriter, BubbleSample,
+    BubBlock, BubBlockSamples, BubBlockSummaries, BubBlockSummary, BubBlocks, BubFnsBlock,
+    BubReader, BubbleSample,
+};
+#[cfg(feature = "std")]
+pub use self::io::{
+    BubFrameReader, BubFrameReaderKind, BubFrameWriter, BubFrameWriterKind, BubObjectFrame,
+    BubObjectReader, BubWriter, ResampledBubFrameReader,
 };
 pub use self::metadata::{BubMetadata, BubSampleKind, BubState};
 
+mod codec;
+#[cfg(feature = "std")]
+pub mod convert;
+#[cfg(feature = "std")]
+pub(crate) mod dump;
+#[cfg(feature = "std")]
+pub mod edit;
+#[cfg(feature = "std")]
 pub mod functions;
 mod id;
 mod io;
 mod metadata;
+#[cfg(feature = "std")]
+mod verify;
@@ -0,0 +1,255 @@
+use crate::bub::codec::{lossless, mdct};
+use crate::bub::functions::{parse, BubFnsVariable};
+use crate::bub::io::{discard, read_payload_and_calc_bytes, BubBlockHead};
+use crate::bub::{BubBlockSamples, BubMetadata};
+use crate::tags::{Tags, TAGS_SPEC_VERSION};
+use crate::verify::{OffsetReader, ProblemKind, VerifyReport};
+use crate::CRC_32K_4_2;
+use std::io::{Read, Result};
+
+/// Decode compressed samples to check them.
+fn decode(samples: &BubBlockSamples, payload: &[u8], len: usize) -> Result<()> {
+    match samples {
+        BubBlockSamples::LosslessLpcm {
+            predictor_order, ..
+        } => lossless::decode(*predictor_order, payload, len).map(|_| ()),
+        BubBlockSamples::Mdct { gain, .. } => mdct::decode(*gain, payload, len).map(|_| ()),
+        BubBlockSamples::Lpcm | BubBlockSamples::Expr(_) => Ok(()),
+    }
+}
+
+/// Walk all blocks. Returns an error if the rest can't be read.
+fn verify_blocks<R: Read>(
+    reader: &mut OffsetReader<R>,
+    report: &mut VerifyReport,
+    frame: &mut Option<u64>,
+) -> Result<()> {
+    let mut crc = CRC_32K_4_2;
+    let metadata = BubMetadata::read_and_calc_bytes(reader, &mut crc)?;
+    report.check_crc(reader, &mut crc, None)?;
+    if metadata.spec_version >= TAGS_SPEC_VERSION {
+        Tags::read_and_calc_bytes(reader, &mut crc)?;
+        report.check_crc(reader, &mut crc, None)?;
+    }
+
+    let mut next_head_absolute_frame = metadata.next_head_absolute_frame;
+    while let Some(head_absolute_frame) = next_head_absolute_frame {
+        *frame = Some(head_absolute_frame);
+
+        let bub_fns_offset = reader.offset;
+        let head = BubBlockHead::read_and_calc_bytes(reader, &mut crc)?;
+        // Bubble Functions Size and Bubble Functions
+        let foot_offset = bub_fns_offset + 2 + head.bub_fns.len() as u64;
+        // Foot Relative Frame
+        let next_head_offset = foot_offset + 8;
+        if parse(&head.bub_fns, &BubFnsVariable::BubFns).is_err() {
+            report.push(*frame, bub_fns_offset, ProblemKind::InvalidBubFns);
+        }
+
+        let foot_absolute_frame = head.foot_absolute_frame(head_absolute_frame);
+        let is_in_frames = head
+            .check_frames(head_absolute_frame, metadata.frames)
+            .is_ok();
+        if head.foot_relative_frame == 0 {
+            report.push(*frame, foot_offset, ProblemKind::EmptyBlock);
+        } else if !is_in_frames {
+            report.push(
+                *frame,
+                foot_offset,
+                ProblemKind::OutOfFrames {
+                    frame: foot_absolute_frame,
+                    frames: metadata.frames,
+                },
+            );
+        }
+
+        next_head_absolute_frame = head.next_head_absolute_frame(head_absolute_frame);
+        if matches!(next_head_absolute_frame, Some(next) if next <= foot_absolute_frame) {
+            report.push(*frame, next_head_offset, ProblemKind::OverlappingBlocks);
+        }
+
+        let samples_offset = reader.offset;
+        let samples =
+            BubBlockSamples::read_and_calc_bytes(reader, &metadata.bub_sample_kind, &mut crc)?;
+        if let BubBlockSamples::Expr(expr) = &samples {
+            if parse(expr, &BubFnsVariable::Sum).is_err() {
+                report.push(*frame, samples_offset, ProblemKind::InvalidExpr);
+            }
+        }
+        let payload_size = samples.payload_size(metadata.lpcm_kind, head.foot_relative_frame)?;
+        let payload = match samples {
+            BubBlockSamples::Lpcm => {
+                discard(reader, payload_size, &mut crc)?;
+                Vec::new()
+            }
+            _ => read_payload_and_calc_bytes(reader, payload_size, &mut crc)?,
+        };
+
+        // Samples are decoded only if the CRC is correct and the block is in frames.
+        if report.check_crc(reader, &mut crc, *frame)? && is_in_frames {
+            if let Err(e) = head
+                .samples_len()
+                .and_then(|len| decode(&samples, &payload, len))
+            {
+                report.push(
+                    *frame,
+                    samples_offset,
+                    ProblemKind::InvalidData(e.to_string()),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn verify<R: Read>(inner: R) -> Result<VerifyReport> {
+    let mut reader = OffsetReader::new(inner);
+    let mut report = VerifyReport::default();
+    let mut frame = None;
+
+    match verify_blocks(&mut reader, &mut report, &mut frame) {
+        Ok(()) => {
+            let byte_offset = reader.offset;
+            let trailing_bytes = reader.trailing_bytes()?;
+            if trailing_bytes != 0 {
+                report.push(
+                    None,
+                    byte_offset,
+                    ProblemKind::TrailingBytes(trailing_bytes),
+                );
+            }
+        }
+        Err(e) => report.push_error(frame, reader.offset, e)?,
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubReader, BubSampleKind, BubWriter};
+    use crate::verify::Problem;
+    use crate::LpcmKind;
+
+    /// Returns the file and the length of the metadata.
+    fn bub() -> Result<(Vec<u8>, usize)> {
+        let metadata = BubMetadata::new(
+            4,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::Lpcm,
+            String::from("Verify"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let metadata_len = bub_writer.inner.len();
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns: b"0 0 0 0==0 1",
+            next_head_relative_frame: None,
+            samples: vec![0.5, 0.25],
+        })?;
+        Ok((bub_frame_writer.inner, metadata_len))
+    }
+
+    #[test]
+    fn verify_valid_files() -> Result<()> {
+        for file_name in ["tests/lpcm_test.bub", "tests/expr_test.bub"] {
+            assert!(BubReader::verify(std::fs::read(file_name)?.as_slice())?.is_valid());
+        }
+        assert!(BubReader::verify(bub()?.0.as_slice())?.is_valid());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_broken_files() -> Result<()> {
+        let (v, metadata_len) = bub()?;
+        // Bubble Functions Size, Bubble Functions, Foot and Next Head Relative Frame, Samples
+        let crc_offset = metadata_len + 2 + 12 + 8 + 8 + 4 * 2;
+        assert_eq!(crc_offset + 4, v.len());
+
+        let mut broken = v.clone();
+        broken[crc_offset - 1] ^= 1;
+        assert_eq!(
+            BubReader::verify(broken.as_slice())?.problems,
+            [Problem {
+                frame: Some(1),
+                byte_offset: crc_offset as u64,
+                kind: ProblemKind::CrcMismatch,
+            }]
+        );
+
+        // Broken metadata
+        let mut broken = v.clone();
+        broken[metadata_len - 5] ^= 1;
+        assert_eq!(
+            BubReader::verify(broken.as_slice())?.problems[0].kind,
+            ProblemKind::CrcMismatch
+        );
+        let e = BubReader::new(broken.as_slice(), Vec::new()).err().unwrap();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+
+        assert_eq!(
+            BubReader::verify(&v[..v.len() - 2])?.problems,
+            [Problem {
+                frame: Some(1),
+                byte_offset: v.len() as u64 - 2,
+                kind: ProblemKind::UnexpectedEof,
+            }]
+        );
+
+        let trailing = [v.as_slice(), &[0; 3]].concat();
+        assert_eq!(
+            BubReader::verify(trailing.as_slice())?.problems,
+            [Problem {
+                frame: None,
+                byte_offset: v.len() as u64,
+                kind: ProblemKind::TrailingBytes(3),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_too_many_frames() -> Result<()> {
+        let bub_fns = b"0 0 0 0==0 1";
+        let metadata = BubMetadata::new(
+            u64::MAX,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::LosslessLpcm,
+            String::from("Verify"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let metadata_len = bub_writer.inner.len();
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Lpcm {
+            bub_fns,
+            next_head_relative_frame: None,
+            samples: vec![0.5, 0.25],
+        })?;
+        let mut v = bub_frame_writer.inner;
+
+        // Frames in the metadata, but too many for the compressed samples, with a valid CRC
+        let foot_offset = metadata_len + 2 + bub_fns.len();
+        v[foot_offset..foot_offset + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        let crc_offset = v.len() - 4;
+        let mut crc = CRC_32K_4_2;
+        crc.calc_bytes(&v[metadata_len - 4..crc_offset]);
+        let checksum_bytes = crc.finalize_to_endian_bytes();
+        v[crc_offset..].copy_from_slice(&checksum_bytes);
+
+        let problems = BubReader::verify(v.as_slice())?.problems;
+        assert_eq!(problems.len(), 1);
+        // Foot and Next Head Relative Frame
+        assert_eq!(problems[0].byte_offset, foot_offset as u64 + 8 + 8);
+        assert!(matches!(problems[0].kind, ProblemKind::InvalidData(_)));
+
+        Ok(())
+    }
+}
@@ -5,7 +5,7 @@ pub fn soft_light(a: f32, b: f32) -> f32 {
         let g_a = if a <= 0.25 {
             ((16.0 * a - 12.0) * a + 4.0) * a
         } else {
-            a.sqrt()
+            libm::sqrtf(a)
         };
         a + (2.0 * b - 1.0) * (g_a - a)
     }
@@ -1,4 +1,4 @@
-use std::ops::Sub;
+use core::ops::Sub;
 
 pub type BubFnsCoord = Coord<f64>;
 
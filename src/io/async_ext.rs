@@ -0,0 +1,122 @@
+//! Helpers to use the sync parsers with `AsyncRead` and `AsyncWrite`.
+
+use futures_util::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Result};
+
+/// Slice which records how many bytes a parser needs when it runs out.
+pub(crate) struct RecordingSlice<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    needed: usize,
+}
+
+impl<'a> RecordingSlice<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            needed: 0,
+        }
+    }
+
+    /// Number of bytes read
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Number of bytes needed by the read which ran out
+    pub(crate) fn needed(&self) -> usize {
+        self.needed
+    }
+}
+
+impl Read for RecordingSlice<'_> {
+    fn read(&mut self, out: &mut [u8]) -> Result<usize> {
+        let len = out.len().min(self.buf.len() - self.pos);
+        if len < out.len() {
+            self.needed = self.needed.max(self.pos + out.len());
+        }
+        out[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Run the sync parser `parse` over the bytes buffered by `reader`.
+/// Only the bytes read by `parse` are consumed.
+///
+/// `parse` runs again from the start each time the buffer of `reader` is consumed,
+/// so it runs once for each buffer of bytes.
+pub(crate) async fn read_with<R, T, F>(reader: &mut R, mut parse: F) -> Result<T>
+where
+    R: AsyncBufRead + Unpin,
+    F: FnMut(&mut RecordingSlice) -> Result<T>,
+{
+    // Bytes consumed from `reader`
+    let mut buf = Vec::new();
+    loop {
+        let consumed = buf.len();
+        let available = reader.fill_buf().await?;
+        let available_len = available.len();
+        buf.extend_from_slice(available);
+
+        let mut slice = RecordingSlice::new(&buf);
+        match parse(&mut slice) {
+            Err(e)
+                if e.kind() == ErrorKind::UnexpectedEof
+                    && buf.len() < slice.needed
+                    && available_len != 0 =>
+            {
+                reader.consume_unpin(available_len);
+            }
+            result => {
+                reader.consume_unpin(slice.pos.saturating_sub(consumed));
+                return result;
+            }
+        }
+    }
+}
+
+/// Run the sync writer `write` and write its bytes to `writer`.
+pub(crate) async fn write_with<W, T, F>(writer: &mut W, write: F) -> Result<T>
+where
+    W: AsyncWrite + Unpin,
+    F: FnOnce(&mut Vec<u8>) -> Result<T>,
+{
+    let mut buf = Vec::new();
+    let t = write(&mut buf)?;
+    writer.write_all(&buf).await?;
+    Ok(t)
+}
+
+/// Read from `reader` until `buf` has `len` bytes.
+pub(crate) async fn fill<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut VecDeque<u8>,
+    len: usize,
+) -> Result<()> {
+    if buf.len() < len {
+        // Bytes are not allocated at once, so a wrong `len` fails at the end of `reader`.
+        let rest = (len - buf.len()) as u64;
+        let mut bytes = Vec::new();
+        reader.take(rest).read_to_end(&mut bytes).await?;
+        if (bytes.len() as u64) < rest {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend(bytes);
+    }
+    Ok(())
+}
+
+/// Write all bytes buffered by a sync writer to `writer`.
+pub(crate) async fn flush_buf<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    buf: &mut Vec<u8>,
+) -> Result<()> {
+    writer.write_all(buf).await?;
+    buf.clear();
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Errors of `std::io`, or the same subset of them without `std`.
+
+#[cfg(not(feature = "std"))]
+pub use self::core_error::{Error, ErrorKind, Result};
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Result};
+
+#[cfg(not(feature = "std"))]
+mod core_error {
+    use alloc::string::{String, ToString};
+    use core::fmt;
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// Kinds of `std::io::ErrorKind` which this crate returns
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        NotFound,
+        InvalidInput,
+        InvalidData,
+        UnexpectedEof,
+        Unsupported,
+        Other,
+    }
+
+    impl fmt::Display for ErrorKind {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                Self::NotFound => "entity not found",
+                Self::InvalidInput => "invalid input parameter",
+                Self::InvalidData => "invalid data",
+                Self::UnexpectedEof => "unexpected end of file",
+                Self::Unsupported => "unsupported",
+                Self::Other => "other error",
+            })
+        }
+    }
+
+    /// `std::io::Error` without `std`
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: Option<String>,
+    }
+
+    impl Error {
+        pub fn new<M: fmt::Display>(kind: ErrorKind, message: M) -> Self {
+            Self {
+                kind,
+                message: Some(message.to_string()),
+            }
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Self {
+                kind,
+                message: None,
+            }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.message {
+                Some(message) => f.write_str(message),
+                None => self.kind.fmt(f),
+            }
+        }
+    }
+
+    impl core::error::Error for Error {}
+}
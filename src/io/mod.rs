@@ -1,7 +1,13 @@
+pub use self::error::{Error, ErrorKind, Result};
+pub use self::read::Read;
 pub use self::read::ReadBytes;
 pub use self::read::ReadExt;
+pub use self::write::Write;
 pub use self::write::WriteBytes;
 pub use self::write::WriteExt;
 
+#[cfg(feature = "async")]
+pub(crate) mod async_ext;
+mod error;
 mod read;
 mod write;
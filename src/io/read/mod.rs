@@ -1,5 +1,7 @@
 pub use self::read_bytes::ReadBytes;
 pub use self::read_ext::ReadExt;
+pub use self::read_trait::Read;
 
 mod read_bytes;
 mod read_ext;
+mod read_trait;
@@ -1,6 +1,6 @@
+use crate::io::{Read, Result};
+use core::mem;
 use mycrc::CRC;
-use std::io::{Read, Result};
-use std::mem;
 
 pub trait ReadBytes: Sized {
     /// This method reads bytes in big-endian byte order.
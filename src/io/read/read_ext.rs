@@ -1,6 +1,8 @@
-use crate::io::ReadBytes;
+use crate::io::{Error, ErrorKind, Read, ReadBytes, Result};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 use mycrc::CRC;
-use std::io::{Error, ErrorKind, Read, Result};
 
 pub trait ReadExt: Read + Sized {
     fn read_be<T: ReadBytes>(&mut self) -> Result<T> {
@@ -1,5 +1,7 @@
 pub use self::write_bytes::WriteBytes;
 pub use self::write_ext::WriteExt;
+pub use self::write_trait::Write;
 
 mod write_bytes;
 mod write_ext;
+mod write_trait;
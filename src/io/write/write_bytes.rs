@@ -1,5 +1,5 @@
+use crate::io::{Result, Write};
 use mycrc::CRC;
-use std::io::{Result, Write};
 
 pub trait WriteBytes {
     /// This method writes bytes in big-endian byte order.
@@ -1,6 +1,6 @@
 use crate::io::write::write_bytes::WriteBytes;
+use crate::io::{Result, Write};
 use mycrc::CRC;
-use std::io::{Result, Write};
 
 pub trait WriteExt: Write + Sized {
     fn write_be<T: WriteBytes>(&mut self, n: T) -> Result<()> {
@@ -0,0 +1,187 @@
+//! Minimal JSON output for dumps
+
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    /// `null` if not finite
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(crate) fn object() -> Self {
+        Self::Object(Vec::new())
+    }
+
+    /// Add a member to an object.
+    pub(crate) fn push<T: Into<Json>>(&mut self, key: &str, value: T) {
+        if let Self::Object(members) = self {
+            members.push((key.into(), value.into()));
+        }
+    }
+
+    /// Add `error` and `error_byte_offset` if `e` is caused by the data, or return it.
+    pub(crate) fn push_error(&mut self, byte_offset: u64, e: Error) -> Result<()> {
+        match e.kind() {
+            ErrorKind::UnexpectedEof | ErrorKind::InvalidData => {
+                self.push("error", e.to_string());
+                self.push("error_byte_offset", byte_offset);
+                Ok(())
+            }
+            _ => Err(e),
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Self::UInt(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Self::String(value.into())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(value: Vec<T>) -> Self {
+        Self::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::UInt(n) => write!(f, "{}", n),
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Float(n) if n.is_finite() => write!(f, "{:?}", n),
+            Self::Float(_) => f.write_str("null"),
+            Self::String(s) => write_str(f, s),
+            Self::Array(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(members) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Count, min, max, mean and RMS of samples
+pub(crate) fn sample_stats<I: IntoIterator<Item = f64>>(samples: I) -> Json {
+    let mut count = 0u64;
+    let (mut min, mut max, mut sum, mut sum_of_squares) =
+        (f64::INFINITY, f64::NEG_INFINITY, 0.0, 0.0);
+    for sample in samples {
+        count += 1;
+        min = min.min(sample);
+        max = max.max(sample);
+        sum += sample;
+        sum_of_squares += sample * sample;
+    }
+
+    let mut stats = Json::object();
+    stats.push("count", count);
+    if count != 0 {
+        stats.push("min", min);
+        stats.push("max", max);
+        stats.push("mean", sum / count as f64);
+        stats.push("rms", (sum_of_squares / count as f64).sqrt());
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_string() {
+        let mut json = Json::object();
+        json.push("name", "\"a\"\\\n\u{1}😀");
+        json.push(
+            "values",
+            vec![Json::Null, 1u64.into(), (-1i64).into(), 0.5.into()],
+        );
+        json.push("none", None::<u64>);
+        json.push("nan", f64::NAN);
+        json.push("stats", sample_stats(vec![1.0, -1.0, 0.0, 0.0]));
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"\"a\"\\\n\u0001😀","values":[null,1,-1,0.5],"none":null,"nan":null,"stats":{"count":4,"min":-1.0,"max":1.0,"mean":0.0,"rms":0.7071067811865476}}"#
+        );
+    }
+}
@@ -1,53 +1,129 @@
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::io::{ErrorKind, Read, Result, Write};
+extern crate alloc;
+
+use crate::io::{ErrorKind, Read, Result, Write};
 
 pub use crate::colors::{Rgb, Rgba};
 pub use crate::coord::{BubFnsCoord, Coord};
 pub use crate::crc::CRC_32K_4_2;
 pub use crate::lpcm::{Frame, LpcmKind, Sample};
+#[cfg(feature = "std")]
+pub use crate::resample::{ResampleQuality, Resampler};
 pub use crate::space::{OaoSpace, OaoSpaces};
+pub use crate::tags::{OtherTagKey, TagKey, Tags};
 
 pub mod bub;
 pub mod colors;
 pub mod coord;
 pub mod crc;
 pub mod io;
+#[cfg(feature = "std")]
+mod json;
 pub mod lpcm;
+#[cfg(feature = "std")]
 pub mod oao;
+#[cfg(feature = "std")]
+pub mod resample;
 pub mod space;
+pub mod tags;
+#[cfg(test)]
+mod tests;
 pub mod utils;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "std")]
 pub mod wav;
 
 /// Metadata
 pub trait Metadata {}
 
 /// FrameIOKind
-pub enum FrameIOKind<F32LE, F64LE> {
+pub enum FrameIOKind<F32LE, F64LE, I16LE, I24LE, I32LE, U8> {
     F32LE(F32LE),
     F64LE(F64LE),
+    I16LE(I16LE),
+    I24LE(I24LE),
+    I32LE(I32LE),
+    U8(U8),
 }
 
-impl<F32LE, F64LE> FrameIOKind<F32LE, F64LE> {
+impl<F32LE, F64LE, I16LE, I24LE, I32LE, U8> FrameIOKind<F32LE, F64LE, I16LE, I24LE, I32LE, U8> {
+    pub const fn lpcm_kind(&self) -> LpcmKind {
+        match self {
+            Self::F32LE(_) => LpcmKind::F32LE,
+            Self::F64LE(_) => LpcmKind::F64LE,
+            Self::I16LE(_) => LpcmKind::I16LE,
+            Self::I24LE(_) => LpcmKind::I24LE,
+            Self::I32LE(_) => LpcmKind::I32LE,
+            Self::U8(_) => LpcmKind::U8,
+        }
+    }
+
     pub fn into_f32_le(self) -> Result<F32LE> {
         match self {
             Self::F32LE(r) => Ok(r),
-            Self::F64LE(_) => Err(utils::expected_and_found_error(
+            _ => Err(utils::expected_and_found_error(
                 ErrorKind::Other,
                 LpcmKind::F32LE,
-                LpcmKind::F64LE,
+                self.lpcm_kind(),
             )),
         }
     }
 
     pub fn into_f64_le(self) -> Result<F64LE> {
         match self {
-            Self::F32LE(_) => Err(utils::expected_and_found_error(
+            Self::F64LE(r) => Ok(r),
+            _ => Err(utils::expected_and_found_error(
                 ErrorKind::Other,
                 LpcmKind::F64LE,
-                LpcmKind::F32LE,
+                self.lpcm_kind(),
+            )),
+        }
+    }
+
+    pub fn into_i16_le(self) -> Result<I16LE> {
+        match self {
+            Self::I16LE(r) => Ok(r),
+            _ => Err(utils::expected_and_found_error(
+                ErrorKind::Other,
+                LpcmKind::I16LE,
+                self.lpcm_kind(),
+            )),
+        }
+    }
+
+    pub fn into_i24_le(self) -> Result<I24LE> {
+        match self {
+            Self::I24LE(r) => Ok(r),
+            _ => Err(utils::expected_and_found_error(
+                ErrorKind::Other,
+                LpcmKind::I24LE,
+                self.lpcm_kind(),
+            )),
+        }
+    }
+
+    pub fn into_i32_le(self) -> Result<I32LE> {
+        match self {
+            Self::I32LE(r) => Ok(r),
+            _ => Err(utils::expected_and_found_error(
+                ErrorKind::Other,
+                LpcmKind::I32LE,
+                self.lpcm_kind(),
+            )),
+        }
+    }
+
+    pub fn into_u8(self) -> Result<U8> {
+        match self {
+            Self::U8(r) => Ok(r),
+            _ => Err(utils::expected_and_found_error(
+                ErrorKind::Other,
+                LpcmKind::U8,
+                self.lpcm_kind(),
             )),
-            Self::F64LE(r) => Ok(r),
         }
     }
 }
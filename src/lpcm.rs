@@ -1,7 +1,8 @@
-use crate::io::{ReadExt, WriteExt};
+use crate::io::{Error, ErrorKind, Read, ReadExt, Result, Write, WriteExt};
+use alloc::format;
+use alloc::vec::Vec;
+use core::ops::{AddAssign, Mul};
 use mycrc::CRC;
-use std::io::{Error, ErrorKind, Read, Result, Write};
-use std::ops::{AddAssign, Mul};
 
 /// Lpcm Sample
 pub trait Sample:
@@ -16,6 +17,106 @@ pub trait Sample:
 
     fn read_and_calc_bytes<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<Self>;
     fn write_and_calc_bytes<W: Write>(self, writer: &mut W, crc: &mut CRC<u32>) -> Result<()>;
+
+    /// Read a sample stored as `lpcm_kind` and convert it to `Self`.
+    /// Integer samples are scaled to -1.0 ~ 1.0.
+    fn read_lpcm<R: Read>(reader: &mut R, lpcm_kind: LpcmKind) -> Result<Self> {
+        Ok(match lpcm_kind {
+            LpcmKind::F32LE => Self::from_f32(reader.read_le()?),
+            LpcmKind::F64LE => Self::from_f64(reader.read_le()?),
+            LpcmKind::I16LE => Self::from_f64(i16_to_f64(reader.read_le()?)),
+            LpcmKind::I24LE => Self::from_f64(i24_to_f64(ReadExt::read_array::<3>(reader)?)),
+            LpcmKind::I32LE => Self::from_f64(i32_to_f64(reader.read_le()?)),
+            LpcmKind::U8 => Self::from_f64(u8_to_f64(reader.read_le()?)),
+        })
+    }
+    /// Convert `self` to `lpcm_kind` and write it.
+    /// Samples are clamped to -1.0 ~ 1.0 when written as integers.
+    fn write_lpcm<W: Write>(self, writer: &mut W, lpcm_kind: LpcmKind) -> Result<()> {
+        match lpcm_kind {
+            LpcmKind::F32LE => writer.write_le(self.to_f32()),
+            LpcmKind::F64LE => writer.write_le(self.to_f64()),
+            LpcmKind::I16LE => writer.write_le(f64_to_i16(self.to_f64())),
+            LpcmKind::I24LE => writer.write_all(&f64_to_i24(self.to_f64())),
+            LpcmKind::I32LE => writer.write_le(f64_to_i32(self.to_f64())),
+            LpcmKind::U8 => writer.write_le(f64_to_u8(self.to_f64())),
+        }
+    }
+
+    fn read_lpcm_and_calc_bytes<R: Read>(
+        reader: &mut R,
+        lpcm_kind: LpcmKind,
+        crc: &mut CRC<u32>,
+    ) -> Result<Self> {
+        Ok(match lpcm_kind {
+            LpcmKind::F32LE => Self::from_f32(reader.read_le_and_calc_bytes(crc)?),
+            LpcmKind::F64LE => Self::from_f64(reader.read_le_and_calc_bytes(crc)?),
+            LpcmKind::I16LE => Self::from_f64(i16_to_f64(reader.read_le_and_calc_bytes(crc)?)),
+            LpcmKind::I24LE => {
+                Self::from_f64(i24_to_f64(reader.read_array_and_calc_bytes::<3>(crc)?))
+            }
+            LpcmKind::I32LE => Self::from_f64(i32_to_f64(reader.read_le_and_calc_bytes(crc)?)),
+            LpcmKind::U8 => Self::from_f64(u8_to_f64(reader.read_le_and_calc_bytes(crc)?)),
+        })
+    }
+    fn write_lpcm_and_calc_bytes<W: Write>(
+        self,
+        writer: &mut W,
+        lpcm_kind: LpcmKind,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        match lpcm_kind {
+            LpcmKind::F32LE => writer.write_le_and_calc_bytes(self.to_f32(), crc),
+            LpcmKind::F64LE => writer.write_le_and_calc_bytes(self.to_f64(), crc),
+            LpcmKind::I16LE => writer.write_le_and_calc_bytes(f64_to_i16(self.to_f64()), crc),
+            LpcmKind::I24LE => {
+                let bytes = f64_to_i24(self.to_f64());
+                writer.write_all(&bytes)?;
+                crc.calc_bytes(&bytes);
+                Ok(())
+            }
+            LpcmKind::I32LE => writer.write_le_and_calc_bytes(f64_to_i32(self.to_f64()), crc),
+            LpcmKind::U8 => writer.write_le_and_calc_bytes(f64_to_u8(self.to_f64()), crc),
+        }
+    }
+}
+
+fn i16_to_f64(n: i16) -> f64 {
+    n as f64 / 32768.0
+}
+
+fn f64_to_i16(n: f64) -> i16 {
+    libm::round(n * 32768.0).clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+/// 3 bytes, little endian
+fn i24_to_f64(bytes: [u8; 3]) -> f64 {
+    // Sign extension
+    let n = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+    n as f64 / 8388608.0
+}
+
+fn f64_to_i24(n: f64) -> [u8; 3] {
+    let n = libm::round(n * 8388608.0).clamp(-8388608.0, 8388607.0) as i32;
+    let bytes = n.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+fn i32_to_f64(n: i32) -> f64 {
+    n as f64 / 2147483648.0
+}
+
+fn f64_to_i32(n: f64) -> i32 {
+    libm::round(n * 2147483648.0).clamp(i32::MIN as f64, i32::MAX as f64) as i32
+}
+
+/// 128 is silence
+fn u8_to_f64(n: u8) -> f64 {
+    (n as f64 - 128.0) / 128.0
+}
+
+fn f64_to_u8(n: f64) -> u8 {
+    libm::round(n * 128.0 + 128.0).clamp(0.0, 255.0) as u8
 }
 
 macro_rules! le_sample_impl {
@@ -98,26 +199,19 @@ pub enum LpcmKind {
     F32LE,
     F64LE,
     I16LE,
+    I24LE,
+    I32LE,
+    U8,
 }
 
 impl LpcmKind {
     pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
         let value: u8 = reader.read_le()?;
-        Ok(match value {
-            0 => Self::F32LE,
-            1 => Self::F64LE,
-            3 => Self::I16LE,
-            _ => return Err(ErrorKind::InvalidData.into()),
-        })
+        Self::from_u8(value)
     }
     pub fn read_and_calc_bytes<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<Self> {
         let value: u8 = reader.read_le_and_calc_bytes(crc)?;
-        Ok(match value {
-            0 => Self::F32LE,
-            1 => Self::F64LE,
-            3 => Self::I16LE,
-            _ => return Err(ErrorKind::InvalidData.into()),
-        })
+        Self::from_u8(value)
     }
 
     pub fn write<W: Write>(self, writer: &mut W) -> Result<()> {
@@ -127,13 +221,21 @@ impl LpcmKind {
         writer.write_le_and_calc_bytes(self.to_u8(), crc)
     }
 
-    pub fn from_u8(value: u8) -> Self {
-        match value {
+    pub fn from_u8(value: u8) -> Result<Self> {
+        Ok(match value {
             0 => Self::F32LE,
             1 => Self::F64LE,
+            2 => Self::U8,
             3 => Self::I16LE,
-            _ => unimplemented!(),
-        }
+            4 => Self::I24LE,
+            5 => Self::I32LE,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown LpcmKind {}", value),
+                ))
+            }
+        })
     }
 
     pub const fn to_u8(self) -> u8 {
@@ -141,6 +243,9 @@ impl LpcmKind {
             Self::F32LE => 0,
             Self::F64LE => 1,
             Self::I16LE => 3,
+            Self::I24LE => 4,
+            Self::I32LE => 5,
+            Self::U8 => 2,
         }
     }
 
@@ -148,7 +253,7 @@ impl LpcmKind {
         match self {
             Self::F32LE => 3,
             Self::F64LE => 3,
-            Self::I16LE => 1,
+            Self::I16LE | Self::I24LE | Self::I32LE | Self::U8 => 1,
         }
     }
 
@@ -157,21 +262,152 @@ impl LpcmKind {
             Self::F32LE => 32,
             Self::F64LE => 64,
             Self::I16LE => 16,
+            Self::I24LE => 24,
+            Self::I32LE => 32,
+            Self::U8 => 8,
         }
     }
 
-    pub fn from_format_tag_and_bits_per_sample(format_tag: u16, bits_per_sample: u16) -> Self {
-        match format_tag {
-            1 => match bits_per_sample {
-                16 => Self::I16LE,
-                _ => unimplemented!(),
-            },
-            3 => match bits_per_sample {
-                32 => Self::F32LE,
-                64 => Self::F64LE,
-                _ => unimplemented!(),
-            },
-            _ => unimplemented!(),
+    /// Integer which `sample` is stored as.
+    /// Floats are mapped to integers in the same order.
+    #[cfg(feature = "std")]
+    pub(crate) fn sample_to_i64<S: Sample>(self, sample: S) -> i64 {
+        match self {
+            Self::F32LE => {
+                let bits = sample.to_f32().to_bits() as i32;
+                (if bits < 0 { bits ^ i32::MAX } else { bits }) as i64
+            }
+            Self::F64LE => {
+                let bits = sample.to_f64().to_bits() as i64;
+                if bits < 0 {
+                    bits ^ i64::MAX
+                } else {
+                    bits
+                }
+            }
+            Self::I16LE => f64_to_i16(sample.to_f64()) as i64,
+            Self::I24LE => {
+                let bytes = f64_to_i24(sample.to_f64());
+                (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as i64
+            }
+            Self::I32LE => f64_to_i32(sample.to_f64()) as i64,
+            Self::U8 => f64_to_u8(sample.to_f64()) as i64 - 128,
+        }
+    }
+
+    /// Inverse of `sample_to_i64`.
+    pub(crate) fn i64_to_sample<S: Sample>(self, n: i64) -> S {
+        match self {
+            Self::F32LE => {
+                let bits = n as i32;
+                let bits = if bits < 0 { bits ^ i32::MAX } else { bits };
+                S::from_f32(f32::from_bits(bits as u32))
+            }
+            Self::F64LE => {
+                let bits = if n < 0 { n ^ i64::MAX } else { n };
+                S::from_f64(f64::from_bits(bits as u64))
+            }
+            Self::I16LE => S::from_f64(i16_to_f64(n as i16)),
+            Self::I24LE => {
+                let bytes = (n as i32).to_le_bytes();
+                S::from_f64(i24_to_f64([bytes[0], bytes[1], bytes[2]]))
+            }
+            Self::I32LE => S::from_f64(i32_to_f64(n as i32)),
+            Self::U8 => S::from_f64(u8_to_f64((n + 128) as u8)),
+        }
+    }
+
+    pub fn from_format_tag_and_bits_per_sample(
+        format_tag: u16,
+        bits_per_sample: u16,
+    ) -> Result<Self> {
+        Ok(match (format_tag, bits_per_sample) {
+            (1, 8) => Self::U8,
+            (1, 16) => Self::I16LE,
+            (1, 24) => Self::I24LE,
+            (1, 32) => Self::I32LE,
+            (3, 32) => Self::F32LE,
+            (3, 64) => Self::F64LE,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "unsupported format tag {} with {} bits per sample",
+                        format_tag, bits_per_sample
+                    ),
+                ))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_samples() -> Result<()> {
+        let samples = [0.0f64, 0.5, -1.0, 1.0, 2.0, -0.25];
+        let lpcm_kinds = [
+            (LpcmKind::U8, 1, 127.0 / 128.0),
+            (LpcmKind::I16LE, 2, 32767.0 / 32768.0),
+            (LpcmKind::I24LE, 3, 8388607.0 / 8388608.0),
+            (LpcmKind::I32LE, 4, 2147483647.0 / 2147483648.0),
+        ];
+
+        for (lpcm_kind, bytes, max) in lpcm_kinds {
+            let mut v = Vec::new();
+            let mut crc = crate::CRC_32K_4_2;
+            for sample in samples {
+                sample.write_lpcm_and_calc_bytes(&mut v, lpcm_kind, &mut crc)?;
+            }
+            assert_eq!(v.len(), samples.len() * bytes);
+            let mut expect_crc = crate::CRC_32K_4_2;
+            assert_eq!(crc.value, expect_crc.calc_bytes(&v).value);
+
+            let mut v = &v[..];
+            let expects = [0.0, 0.5, -1.0, max, max, -0.25];
+            for expect in expects {
+                assert_eq!(f64::read_lpcm(&mut v, lpcm_kind)?, expect);
+            }
+        }
+
+        // Sign extension
+        let mut v: &[u8] = &[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x80];
+        assert_eq!(f64::read_lpcm(&mut v, LpcmKind::I24LE)?, -1.0 / 8388608.0);
+        assert_eq!(f64::read_lpcm(&mut v, LpcmKind::I24LE)?, -1.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sample_to_i64_and_back() {
+        let samples = [-1.5f64, -1.0, -0.25, -0.0, 0.0, 1e-9, 0.5, 1.0];
+        let lpcm_kinds = [
+            LpcmKind::F32LE,
+            LpcmKind::F64LE,
+            LpcmKind::U8,
+            LpcmKind::I16LE,
+            LpcmKind::I24LE,
+            LpcmKind::I32LE,
+        ];
+
+        for lpcm_kind in lpcm_kinds {
+            let mut prev = i64::MIN;
+            for sample in samples {
+                let n = lpcm_kind.sample_to_i64(sample);
+                // Same order as samples
+                assert!(prev <= n);
+                prev = n;
+
+                let mut v = Vec::new();
+                sample.write_lpcm(&mut v, lpcm_kind).unwrap();
+                let expect = f64::read_lpcm(&mut &v[..], lpcm_kind).unwrap();
+                assert_eq!(
+                    lpcm_kind.i64_to_sample::<f64>(n).to_bits(),
+                    expect.to_bits()
+                );
+            }
         }
     }
 }
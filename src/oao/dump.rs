@@ -0,0 +1,146 @@
+use crate::bub::dump::{push_crc, tags_json};
+use crate::json::Json;
+use crate::oao::{BubInOao, OaoMetadata, TRANSFORMS_SPEC_VERSION};
+use crate::tags::{Tags, TAGS_SPEC_VERSION};
+use crate::utils::check_crc;
+use crate::verify::OffsetReader;
+use crate::CRC_32K_4_2;
+use std::io::{Read, Result};
+
+/// Walk all bubbles. Returns an error if the rest can't be read.
+fn dump_bubs<R: Read>(reader: &mut OffsetReader<R>, json: &mut Json) -> Result<()> {
+    let mut crc = CRC_32K_4_2;
+    let (metadata, num_of_bubs) = OaoMetadata::read_header_and_calc_bytes(reader, &mut crc)?;
+    json.push("spec_version", metadata.spec_version as u64);
+    json.push("oao_id", metadata.oao_id.id.to_string());
+    json.push("oao_version", metadata.oao_version as u64);
+    json.push("frames", metadata.frames);
+    json.push("samples_per_sec", metadata.samples_per_sec);
+    json.push("lpcm_kind", format!("{:?}", metadata.lpcm_kind));
+    json.push("title", metadata.title.as_str());
+    json.push("artist", metadata.artist.as_str());
+    json.push("header_crc_ok", check_crc(reader, &mut crc)?);
+    if metadata.spec_version >= TAGS_SPEC_VERSION {
+        let tags = Tags::read_and_calc_bytes(reader, &mut crc)?;
+        json.push("tags", tags_json(&tags));
+        json.push("tags_crc_ok", check_crc(reader, &mut crc)?);
+    }
+
+    let mut bubs = Vec::new();
+    for _ in 0..num_of_bubs {
+        let mut bub_json = Json::object();
+        bub_json.push("byte_offset", reader.offset);
+        let result = BubInOao::read_and_calc_bytes(reader, metadata.spec_version, &mut crc)
+            .and_then(|bub| {
+                bub_json.push("file_name", bub.file_name);
+                bub_json.push("starting_frames", Vec::from(bub.starting_frames));
+                if metadata.spec_version >= TRANSFORMS_SPEC_VERSION {
+                    let transforms: Vec<Json> = bub
+                        .transforms
+                        .iter()
+                        .map(|transform| {
+                            let offset = transform.position_offset;
+                            let mut transform_json = Json::object();
+                            transform_json
+                                .push("position_offset", vec![offset.x, offset.y, offset.z]);
+                            transform_json.push("gain", transform.gain);
+                            transform_json.push("time_offset", transform.time_offset);
+                            transform_json
+                        })
+                        .collect();
+                    bub_json.push("transforms", transforms);
+                }
+                push_crc(&mut bub_json, reader, &mut crc)
+            });
+        bubs.push(bub_json);
+        if let Err(e) = result {
+            json.push("bubs", bubs);
+            return Err(e);
+        }
+    }
+    json.push("bubs", bubs);
+
+    Ok(())
+}
+
+pub(crate) fn dump<R: Read>(inner: R) -> Result<String> {
+    let mut reader = OffsetReader::new(inner);
+    let mut json = Json::object();
+
+    match dump_bubs(&mut reader, &mut json) {
+        Ok(()) => {
+            let trailing_bytes = reader.trailing_bytes()?;
+            json.push("trailing_bytes", trailing_bytes);
+        }
+        Err(e) => json.push_error(reader.offset, e)?,
+    }
+
+    Ok(json.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oao::{BubTransform, OaoReader};
+    use crate::LpcmKind;
+
+    #[test]
+    fn dump() -> Result<()> {
+        let bubs = vec![
+            BubInOao::new("a".into(), vec![1, 5].into()),
+            BubInOao::new("b\"".into(), vec![3].into()),
+        ];
+        let metadata = OaoMetadata::new(
+            10,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("untitled"),
+            String::from("undefined"),
+            bubs,
+        );
+        let mut v = Vec::new();
+        metadata.write(&mut v)?;
+        let second_offset = v.len() - (1 + 2 + 2 + 8 + 4);
+        let first_offset = second_offset - (1 + 1 + 2 + 8 * 2 + 4);
+
+        assert_eq!(
+            OaoReader::dump_json(&v[..])?,
+            format!(
+                concat!(
+                    r#"{{"spec_version":0,"oao_id":"0","oao_version":0,"frames":10,"samples_per_sec":48000.0,"lpcm_kind":"F32LE","title":"untitled","artist":"undefined","header_crc_ok":true,"#,
+                    r#""bubs":[{{"byte_offset":{},"file_name":"a","starting_frames":[1,5],"crc_byte_offset":{},"crc_ok":true}},"#,
+                    r#"{{"byte_offset":{},"file_name":"b\"","starting_frames":[3],"crc_byte_offset":{},"crc_ok":true}}],"trailing_bytes":0}}"#
+                ),
+                first_offset,
+                second_offset - 4,
+                second_offset,
+                v.len() - 4
+            )
+        );
+
+        // Ends in the middle of the last bubble
+        let json = OaoReader::dump_json(&v[..v.len() - 6])?;
+        assert!(json.ends_with(&format!(
+            r#"{{"byte_offset":{}}}],"error":"failed to fill whole buffer","error_byte_offset":{}}}"#,
+            second_offset,
+            v.len() - 6
+        )));
+
+        // Transforms
+        let mut metadata = metadata;
+        metadata.spec_version = TRANSFORMS_SPEC_VERSION;
+        metadata.bubs[1].transforms = vec![BubTransform {
+            position_offset: (1.0, 0.0, 0.0).into(),
+            gain: 0.5,
+            time_offset: 2,
+        }]
+        .into();
+        let mut v = Vec::new();
+        metadata.write(&mut v)?;
+        let json = OaoReader::dump_json(&v[..])?;
+        assert!(json.contains(r#""starting_frames":[1,5],"transforms":[],"#));
+        assert!(json.contains(r#""starting_frames":[3],"transforms":[{"position_offset":[1.0,0.0,0.0],"gain":0.5,"time_offset":2}],"#));
+
+        Ok(())
+    }
+}
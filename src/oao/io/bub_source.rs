@@ -0,0 +1,343 @@
+use crate::io::ReadExt;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Take};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Bubble file in memory, which is cheap to clone for each starting frame
+pub type BubBytes = Cursor<Arc<[u8]>>;
+
+/// Opens bubble files by their file name in [`BubInOao`](crate::oao::BubInOao).
+///
+/// [`OaoFrameReader`](crate::oao::OaoFrameReader) opens a new reader at each starting frame.
+pub trait BubSource {
+    type Reader: Read;
+
+    /// Open `file_name` from its first byte.
+    fn open_bub(&mut self, file_name: &str) -> Result<Self::Reader>;
+}
+
+fn not_found(file_name: &str) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("bubble `{}` is not found", file_name),
+    )
+}
+
+/// Bubbles are `{file_name}.bub` in a directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirBubSource {
+    pub dir: PathBuf,
+}
+
+impl DirBubSource {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn path(&self, file_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.bub", file_name))
+    }
+}
+
+impl BubSource for DirBubSource {
+    type Reader = BufReader<File>;
+
+    fn open_bub(&mut self, file_name: &str) -> Result<Self::Reader> {
+        let path = self.path(file_name);
+        let file = File::open(&path)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        Ok(BufReader::new(file))
+    }
+}
+
+/// Bubbles in memory by file name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryBubSource {
+    pub bubs: HashMap<String, Arc<[u8]>>,
+}
+
+impl MemoryBubSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the old bytes if any.
+    pub fn insert<T: Into<Arc<[u8]>>>(&mut self, file_name: String, bytes: T) -> Option<Arc<[u8]>> {
+        self.bubs.insert(file_name, bytes.into())
+    }
+}
+
+impl BubSource for MemoryBubSource {
+    type Reader = BubBytes;
+
+    fn open_bub(&mut self, file_name: &str) -> Result<Self::Reader> {
+        self.bubs
+            .get(file_name)
+            .map(|bytes| Cursor::new(Arc::clone(bytes)))
+            .ok_or_else(|| not_found(file_name))
+    }
+}
+
+/// Entry of a zip archive
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ZipEntry {
+    /// 0 if stored
+    compression_method: u16,
+    size: u64,
+    local_header_offset: u64,
+}
+
+/// Bubbles are `{file_name}.bub` in a zip archive.
+///
+/// Only stored (uncompressed) entries can be opened, since bubbles are already compressed if needed.
+/// Each bubble is read from a new file handle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZipBubSource {
+    pub path: PathBuf,
+    entries: HashMap<String, ZipEntry>,
+}
+
+impl ZipBubSource {
+    const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+    const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+    const END_SIGNATURE: u32 = 0x0605_4b50;
+    /// Size of the end of central directory record without its comment
+    const END_SIZE: u64 = 22;
+
+    /// Read the central directory of a zip archive.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let mut reader = BufReader::new(File::open(&path)?);
+        let entries = Self::read_entries(&mut reader)?;
+
+        Ok(Self { path, entries })
+    }
+
+    /// Names of bubbles in the archive
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .keys()
+            .filter_map(|name| name.strip_suffix(".bub"))
+    }
+
+    fn invalid_data(message: &str) -> Error {
+        Error::new(ErrorKind::InvalidData, message)
+    }
+
+    fn read_entries<R: Read + Seek>(reader: &mut R) -> Result<HashMap<String, ZipEntry>> {
+        // The end of central directory record is followed by a comment up to 65535 bytes.
+        let len = reader.seek(SeekFrom::End(0))?;
+        if len < Self::END_SIZE {
+            return Err(Self::invalid_data("not a zip archive"));
+        }
+        let tail_len = len.min(Self::END_SIZE + u16::MAX as u64);
+        reader.seek(SeekFrom::Start(len - tail_len))?;
+        let tail = reader.read_vec_for(tail_len as usize)?;
+        let end = (0..=tail.len() - Self::END_SIZE as usize)
+            .rev()
+            .find(|&i| tail[i..i + 4] == Self::END_SIGNATURE.to_le_bytes())
+            .ok_or_else(|| Self::invalid_data("end of central directory is not found"))?;
+
+        let mut end_record = &tail[end + 10..];
+        let number_of_entries: u16 = end_record.read_le()?;
+        let _central_directory_size: u32 = end_record.read_le()?;
+        let central_directory_offset: u32 = end_record.read_le()?;
+        if number_of_entries == u16::MAX || central_directory_offset == u32::MAX {
+            return Err(Error::new(ErrorKind::Other, "zip64 is not supported"));
+        }
+
+        reader.seek(SeekFrom::Start(central_directory_offset as u64))?;
+        let mut entries = HashMap::with_capacity(number_of_entries as usize);
+        for _ in 0..number_of_entries {
+            let signature: u32 = reader.read_le()?;
+            if signature != Self::CENTRAL_HEADER_SIGNATURE {
+                return Err(Self::invalid_data("wrong central directory header"));
+            }
+            let header = reader.read_vec_for(42)?;
+            let mut header = &header[6..];
+            let compression_method: u16 = header.read_le()?;
+            // Time, date and CRC-32
+            header = &header[8..];
+            let compressed_size: u32 = header.read_le()?;
+            let _uncompressed_size: u32 = header.read_le()?;
+            let name_size: u16 = header.read_le()?;
+            let extra_size: u16 = header.read_le()?;
+            let comment_size: u16 = header.read_le()?;
+            // Disk number and attributes
+            header = &header[8..];
+            let local_header_offset: u32 = header.read_le()?;
+            if compressed_size == u32::MAX || local_header_offset == u32::MAX {
+                return Err(Error::new(ErrorKind::Other, "zip64 is not supported"));
+            }
+
+            let name = reader.read_string_for(name_size as usize)?;
+            reader.seek(SeekFrom::Current(extra_size as i64 + comment_size as i64))?;
+            entries.insert(
+                name,
+                ZipEntry {
+                    compression_method,
+                    size: compressed_size as u64,
+                    local_header_offset: local_header_offset as u64,
+                },
+            );
+        }
+
+        Ok(entries)
+    }
+}
+
+impl BubSource for ZipBubSource {
+    type Reader = Take<BufReader<File>>;
+
+    fn open_bub(&mut self, file_name: &str) -> Result<Self::Reader> {
+        let entry = self
+            .entries
+            .get(&format!("{}.bub", file_name))
+            .ok_or_else(|| not_found(file_name))?;
+        if entry.compression_method != 0 {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("bubble `{}` is compressed in the archive", file_name),
+            ));
+        }
+
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        reader.seek(SeekFrom::Start(entry.local_header_offset))?;
+        let signature: u32 = reader.read_le()?;
+        if signature != Self::LOCAL_HEADER_SIGNATURE {
+            return Err(Self::invalid_data("wrong local file header"));
+        }
+        let header = reader.read_vec_for(26)?;
+        let mut sizes = &header[22..];
+        let name_size: u16 = sizes.read_le()?;
+        let extra_size: u16 = sizes.read_le()?;
+        reader.seek(SeekFrom::Current(name_size as i64 + extra_size as i64))?;
+
+        Ok(reader.take(entry.size))
+    }
+}
+
+/// Bubbles from a closure
+impl<F: FnMut(&str) -> Result<R>, R: Read> BubSource for F {
+    type Reader = R;
+
+    fn open_bub(&mut self, file_name: &str) -> Result<Self::Reader> {
+        self(file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::WriteExt;
+    use crate::tests::TempDir;
+    use std::io::Write;
+
+    /// Stored zip archive
+    fn zip(files: &[(&str, &[u8])], compression_method: u16) -> Result<Vec<u8>> {
+        let mut v = Vec::new();
+        let mut central_directory = Vec::new();
+        for (name, bytes) in files {
+            let offset = v.len() as u32;
+            v.write_le(ZipBubSource::LOCAL_HEADER_SIGNATURE)?;
+            v.write_all(&[0; 22])?;
+            v.write_le(name.len() as u16)?;
+            v.write_le(0u16)?;
+            v.write_str(name)?;
+            v.write_all(bytes)?;
+
+            central_directory.write_le(ZipBubSource::CENTRAL_HEADER_SIGNATURE)?;
+            central_directory.write_all(&[0; 6])?;
+            central_directory.write_le(compression_method)?;
+            central_directory.write_all(&[0; 8])?;
+            central_directory.write_le(bytes.len() as u32)?;
+            central_directory.write_le(bytes.len() as u32)?;
+            central_directory.write_le(name.len() as u16)?;
+            central_directory.write_all(&[0; 12])?;
+            central_directory.write_le(offset)?;
+            central_directory.write_str(name)?;
+        }
+        let offset = v.len() as u32;
+        v.write_all(&central_directory)?;
+        v.write_le(ZipBubSource::END_SIGNATURE)?;
+        v.write_all(&[0; 4])?;
+        v.write_le(files.len() as u16)?;
+        v.write_le(files.len() as u16)?;
+        v.write_le(central_directory.len() as u32)?;
+        v.write_le(offset)?;
+        v.write_le(0u16)?;
+        Ok(v)
+    }
+
+    fn read_all<R: Read>(mut reader: R) -> Result<Vec<u8>> {
+        let mut v = Vec::new();
+        reader.read_to_end(&mut v)?;
+        Ok(v)
+    }
+
+    #[test]
+    fn sources() -> Result<()> {
+        let temp_dir = TempDir::new("bub-source")?;
+        let dir = temp_dir.path();
+
+        // Directory
+        std::fs::write(dir.join("vocal.bub"), b"vocal")?;
+        let mut source = DirBubSource::new(dir);
+        assert_eq!(read_all(source.open_bub("vocal")?)?, b"vocal");
+        assert_eq!(read_all(source.open_bub("vocal")?)?, b"vocal");
+        assert_eq!(
+            source.open_bub("drum").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        // Memory
+        let mut source = MemoryBubSource::new();
+        source.insert("vocal".into(), &b"vocal"[..]);
+        assert_eq!(read_all(source.open_bub("vocal")?)?, b"vocal");
+        assert_eq!(
+            source.open_bub("drum").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        // Zip
+        let zip_path = dir.join("bubs.zip");
+        std::fs::write(
+            &zip_path,
+            zip(
+                &[
+                    ("vocal.bub", b"vocal"),
+                    ("drum.bub", b"drum!"),
+                    ("a.txt", b""),
+                ],
+                0,
+            )?,
+        )?;
+        let mut source = ZipBubSource::open(&zip_path)?;
+        let mut file_names = source.file_names().collect::<Vec<_>>();
+        file_names.sort_unstable();
+        assert_eq!(file_names, ["drum", "vocal"]);
+        let vocal = source.open_bub("vocal")?;
+        assert_eq!(read_all(source.open_bub("drum")?)?, b"drum!");
+        assert_eq!(read_all(vocal)?, b"vocal");
+        assert_eq!(
+            source.open_bub("a").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        std::fs::write(&zip_path, zip(&[("vocal.bub", b"vocal")], 8)?)?;
+        assert!(ZipBubSource::open(&zip_path)?.open_bub("vocal").is_err());
+        std::fs::write(&zip_path, b"vocal")?;
+        assert_eq!(
+            ZipBubSource::open(&zip_path).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        // Closure
+        let mut source = |file_name: &str| -> Result<_> { Ok(Cursor::new(file_name.to_owned())) };
+        assert_eq!(read_all(source.open_bub("vocal")?)?, b"vocal");
+
+        Ok(())
+    }
+}
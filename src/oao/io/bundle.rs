@@ -0,0 +1,398 @@
+use crate::bub::{read_payload_and_calc_bytes, write_payload_and_calc_bytes};
+use crate::io::{ReadExt, WriteExt};
+use crate::oao::{BubSource, MemoryBubSource, OaoFrameReaderKind, OaoMetadata, OaoReader};
+use crate::utils::{
+    read_crc, read_sized_string_and_calc_bytes, u16_len, write_crc, write_sized_str_and_calc_bytes,
+};
+use crate::{BubFnsCoord, CRC_32K_4_2};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::{Component, Path};
+
+/// Extension of a Floaout bundle, which embeds its bubbles
+pub const OAO_BUNDLE_EXTENSION: &str = "oaob";
+
+/// Bytes in front of a Floaout bundle, so it is not read as a Floaout file
+const OAO_BUNDLE_SIGNATURE: [u8; 4] = *b"OAOB";
+
+/// Version of the bundle format, which is written after the signature
+pub const OAO_BUNDLE_VERSION: u8 = 0;
+
+/// File names of `metadata.bubs` without duplicates, in the order of the index
+fn bub_file_names(metadata: &OaoMetadata) -> Vec<String> {
+    let mut file_names: Vec<String> = Vec::with_capacity(metadata.bubs.len());
+    for bub in metadata.bubs.iter() {
+        if !file_names.contains(&bub.file_name) {
+            file_names.push(bub.file_name.clone());
+        }
+    }
+    file_names
+}
+
+/// Writes a Floaout bundle, which is the signature and the version,
+/// followed by the Floaout metadata, the index and the bubbles.
+pub struct OaoBundleWriter<W: Write> {
+    pub inner: W,
+    pub metadata: OaoMetadata,
+}
+
+impl<W: Write> OaoBundleWriter<W> {
+    /// Write the whole bundle. Bubbles of `metadata.bubs` are read from `bub_source` into memory.
+    pub fn new<B: BubSource>(
+        mut inner: W,
+        metadata: OaoMetadata,
+        mut bub_source: B,
+    ) -> Result<Self> {
+        let mut bubs = Vec::new();
+        for file_name in bub_file_names(&metadata) {
+            let mut bytes = Vec::new();
+            bub_source
+                .open_bub(&file_name)
+                .and_then(|mut reader| reader.read_to_end(&mut bytes))
+                .map_err(|e| {
+                    Error::new(
+                        e.kind(),
+                        format!("cannot read bubble `{}`: {}", file_name, e),
+                    )
+                })?;
+            bubs.push((file_name, bytes));
+        }
+
+        // Sizes are checked before anything is written.
+        let num_of_bubs = u16_len("number of bubbles", bubs.len())?;
+        metadata.check_writable()?;
+
+        inner.write_all(&OAO_BUNDLE_SIGNATURE)?;
+        inner.write_le(OAO_BUNDLE_VERSION)?;
+
+        let mut crc = CRC_32K_4_2;
+        metadata.write_and_calc_bytes(&mut inner, &mut crc)?;
+
+        // Index
+        inner.write_le_and_calc_bytes(num_of_bubs, &mut crc)?;
+        for (file_name, bytes) in bubs.iter() {
+            write_sized_str_and_calc_bytes(
+                &mut inner,
+                "file name",
+                file_name,
+                metadata.spec_version,
+                &mut crc,
+            )?;
+            inner.write_le_and_calc_bytes(bytes.len() as u64, &mut crc)?;
+        }
+        write_crc(&mut inner, &mut crc)?;
+
+        // Bubbles
+        for (_, bytes) in bubs.iter() {
+            write_payload_and_calc_bytes(&mut inner, bytes, &mut crc)?;
+            write_crc(&mut inner, &mut crc)?;
+        }
+
+        Ok(Self { inner, metadata })
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl OaoBundleWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>, B: BubSource>(
+        filename: P,
+        metadata: OaoMetadata,
+        bub_source: B,
+    ) -> Result<Self> {
+        let file = File::create(filename)?;
+        let buf_writer = BufWriter::new(file);
+        let mut oao_bundle_writer = Self::new(buf_writer, metadata, bub_source)?;
+        oao_bundle_writer.flush()?;
+
+        Ok(oao_bundle_writer)
+    }
+}
+
+/// Reads a Floaout bundle. Bubbles are read into memory and checked by their CRCs.
+pub struct OaoBundleReader<R: Read> {
+    pub inner: R,
+    pub metadata: OaoMetadata,
+    /// Bubbles in the bundle
+    pub bub_source: MemoryBubSource,
+    /// Speakers absolute coordinates
+    pub speakers_absolute_coord: Vec<BubFnsCoord>,
+}
+
+impl<R: Read> OaoBundleReader<R> {
+    pub fn new(mut inner: R, speakers_absolute_coord: Vec<BubFnsCoord>) -> Result<Self> {
+        if inner.read_vec_for(OAO_BUNDLE_SIGNATURE.len())? != OAO_BUNDLE_SIGNATURE {
+            return Err(Error::new(ErrorKind::InvalidData, "not a Floaout bundle"));
+        }
+        let version: u8 = inner.read_le()?;
+        if version > OAO_BUNDLE_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "bundle version is {}, but must be less than or equal to {}",
+                    version, OAO_BUNDLE_VERSION
+                ),
+            ));
+        }
+
+        let mut crc = CRC_32K_4_2;
+        let metadata = OaoMetadata::read_and_calc_bytes(&mut inner, &mut crc)?;
+
+        // Index
+        let num_of_bubs: u16 = inner.read_le_and_calc_bytes(&mut crc)?;
+        let mut index = Vec::with_capacity(num_of_bubs as usize);
+        for _ in 0..num_of_bubs {
+            let file_name =
+                read_sized_string_and_calc_bytes(&mut inner, metadata.spec_version, &mut crc)?;
+            let size: u64 = inner.read_le_and_calc_bytes(&mut crc)?;
+            index.push((file_name, size));
+        }
+        read_crc(&mut inner, &mut crc)?;
+
+        // Bubbles
+        let mut bub_source = MemoryBubSource::new();
+        for (file_name, size) in index {
+            // The size is not trusted until the bytes are read.
+            let bytes = read_payload_and_calc_bytes(&mut inner, size, &mut crc)?;
+            read_crc(&mut inner, &mut crc)?;
+            bub_source.insert(file_name, bytes);
+        }
+
+        Ok(Self {
+            inner,
+            metadata,
+            bub_source,
+            speakers_absolute_coord,
+        })
+    }
+
+    pub fn into_oao_reader_and_bub_source(self) -> (OaoReader<R>, MemoryBubSource) {
+        (
+            OaoReader {
+                inner: self.inner,
+                metadata: self.metadata,
+                speakers_absolute_coord: self.speakers_absolute_coord,
+            },
+            self.bub_source,
+        )
+    }
+
+    /// Play the bundle with its bubbles.
+    pub fn into_oao_frame_reader_kind(self) -> Result<OaoFrameReaderKind<R, MemoryBubSource>> {
+        let (oao_reader, bub_source) = self.into_oao_reader_and_bub_source();
+        oao_reader.into_oao_frame_reader_kind(bub_source)
+    }
+
+    /// Write the Floaout file to `filename`, and its bubbles as `{file_name}.bub` in the same directory.
+    pub fn unpack<P: AsRef<Path>>(&self, filename: P) -> Result<()> {
+        let dir = filename.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        for (file_name, bytes) in self.bub_source.bubs.iter() {
+            // Bubbles must not be written out of the directory.
+            let mut components = Path::new(file_name).components();
+            if !matches!(
+                (components.next(), components.next()),
+                (Some(Component::Normal(_)), None)
+            ) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("`{}` is not a bubble file name", file_name),
+                ));
+            }
+            std::fs::write(dir.join(format!("{}.bub", file_name)), bytes)?;
+        }
+
+        let mut v = Vec::new();
+        self.metadata.write(&mut v)?;
+        std::fs::write(filename, v)
+    }
+}
+
+impl OaoBundleReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(
+        filename: P,
+        speakers_absolute_coord: Vec<BubFnsCoord>,
+    ) -> Result<Self> {
+        let file = File::open(filename)?;
+        let buf_reader = BufReader::new(file);
+        Self::new(buf_reader, speakers_absolute_coord)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubMetadata, BubSampleKind, BubWriter};
+    use crate::oao::BubInOao;
+    use crate::tests::TempDir;
+    use crate::LpcmKind;
+
+    fn bub(expression: &[u8]) -> Result<Vec<u8>> {
+        let metadata = BubMetadata::new(
+            4,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::default_expr(),
+            String::from("Bubble"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 X<3 1",
+            foot_relative_frame: 2,
+            next_head_relative_frame: None,
+            expression,
+        })?;
+        Ok(bub_frame_writer.inner)
+    }
+
+    #[test]
+    fn write_and_read() -> Result<()> {
+        let bubs = vec![
+            BubInOao::new("vocal".into(), vec![1, 3].into()),
+            BubInOao::new("drum".into(), vec![2].into()),
+        ];
+        let metadata = OaoMetadata::new(
+            6,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("Bundle"),
+            String::from("Artist"),
+            bubs,
+        );
+        let mut bub_source = MemoryBubSource::new();
+        bub_source.insert("vocal".into(), bub(b"0.5")?);
+        bub_source.insert("drum".into(), bub(b"0.25")?);
+        let v = OaoBundleWriter::new(Vec::new(), metadata.clone(), bub_source.clone())?.inner;
+
+        let oao_bundle_reader = OaoBundleReader::new(&v[..], vec![(0.0, 0.0, 0.0).into()])?;
+        assert_eq!(oao_bundle_reader.metadata, metadata);
+        assert_eq!(oao_bundle_reader.bub_source, bub_source);
+        let frames = oao_bundle_reader
+            .into_oao_frame_reader_kind()?
+            .into_f32_le()?
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames, [0.5, 0.75, 0.75, 0.5, 0.0, 0.0]);
+
+        // Broken bubble
+        let mut broken = v.clone();
+        let len = broken.len();
+        broken[len - 5] ^= 1;
+        assert_eq!(
+            OaoBundleReader::new(&broken[..], Vec::new())
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+
+        // Not a bundle
+        assert_eq!(
+            OaoBundleReader::new(&v[5..], Vec::new())
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(OaoReader::new(&v[..], Vec::new()).is_err());
+        let mut newer = v.clone();
+        newer[4] = OAO_BUNDLE_VERSION + 1;
+        assert_eq!(
+            OaoBundleReader::new(&newer[..], Vec::new())
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+
+        // Bubble larger than the bundle
+        let mut huge = OAO_BUNDLE_SIGNATURE.to_vec();
+        huge.write_le(OAO_BUNDLE_VERSION)?;
+        let mut crc = CRC_32K_4_2;
+        metadata.write_and_calc_bytes(&mut huge, &mut crc)?;
+        huge.write_le_and_calc_bytes(1u16, &mut crc)?;
+        write_sized_str_and_calc_bytes(
+            &mut huge,
+            "file name",
+            "vocal",
+            metadata.spec_version,
+            &mut crc,
+        )?;
+        huge.write_le_and_calc_bytes(u64::MAX, &mut crc)?;
+        write_crc(&mut huge, &mut crc)?;
+        assert_eq!(
+            OaoBundleReader::new(&huge[..], Vec::new())
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        // Missing bubble
+        bub_source.bubs.remove("drum");
+        let e = OaoBundleWriter::new(Vec::new(), metadata, bub_source)
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), ErrorKind::NotFound);
+        assert!(e.to_string().contains("drum"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pack_and_unpack() -> Result<()> {
+        let temp_dir = TempDir::new("bundle")?;
+        let dir = temp_dir.path();
+        let unpacked_dir = dir.join("unpacked");
+        std::fs::create_dir_all(&unpacked_dir)?;
+        let metadata = OaoMetadata::new(
+            4,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("Bundle"),
+            String::from("Artist"),
+            vec![BubInOao::new("vocal".into(), vec![1].into())],
+        );
+        let mut v = Vec::new();
+        metadata.write(&mut v)?;
+        let oao_path = dir.join("song.oao");
+        std::fs::write(&oao_path, &v)?;
+        let vocal = bub(b"0.5")?;
+        std::fs::write(dir.join("vocal.bub"), &vocal)?;
+
+        let bundle_path = dir.join(format!("song.{}", OAO_BUNDLE_EXTENSION));
+        OaoReader::pack_bundle(&oao_path, &bundle_path)?;
+        let unpacked_oao_path = unpacked_dir.join("song.oao");
+        OaoReader::unpack_bundle(&bundle_path, &unpacked_oao_path)?;
+        assert_eq!(std::fs::read(&unpacked_oao_path)?, v);
+        assert_eq!(std::fs::read(unpacked_dir.join("vocal.bub"))?, vocal);
+
+        let frames = OaoReader::open_bundle_and_into_oao_frame_reader_kind(
+            &bundle_path,
+            vec![(0.0, 0.0, 0.0).into()],
+        )?
+        .into_f32_le()?
+        .map(|frame| frame.map(|frame| frame.0[0]))
+        .collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames, [0.5, 0.5, 0.0, 0.0]);
+
+        // File names out of the directory
+        let mut oao_bundle_reader = OaoBundleReader::open(&bundle_path, Vec::new())?;
+        let bytes = oao_bundle_reader.bub_source.bubs.remove("vocal").unwrap();
+        oao_bundle_reader
+            .bub_source
+            .insert("../vocal".into(), bytes);
+        assert_eq!(
+            oao_bundle_reader
+                .unpack(&unpacked_oao_path)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+
+        Ok(())
+    }
+}
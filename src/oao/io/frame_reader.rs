@@ -1,28 +1,41 @@
-use crate::bub::BubFrameReader;
+use crate::bub::{BubReader, ResampledBubFrameReader};
 use crate::colors::soft_light;
-use crate::oao::{BubInOao, OaoMetadata};
+use crate::oao::{
+    BubInOao, BubIssue, BubIssueKind, BubSource, BubTransform, OaoMetadata, ValidationReport,
+};
+use crate::resample::ResampleQuality;
 use crate::{BubFnsCoord, Frame, FrameIOKind, FrameReader, OaoSpace, OaoSpaces, Sample};
-use std::io::{Read, Result};
+use std::io::{Error, ErrorKind, Read, Result};
 use std::marker::PhantomData;
 
-pub struct OaoFrameReader<R: Read, B: Read + Clone, S: Sample> {
+pub struct OaoFrameReader<R: Read, B: BubSource, S: Sample> {
     pub inner: R,
     pub pos: u64,
     _phantom_sample: PhantomData<S>,
     pub metadata: OaoMetadata,
     /// Speakers absolute coordinates
     pub speakers_absolute_coord: Vec<BubFnsCoord>,
+    /// Opens a bubble at each starting frame.
+    pub bub_source: B,
 
     // Buffers
-    pub bubs: Vec<(BubInOao, BubFrameReader<B, S>)>,
-    /// Bubble Frame Readers
-    pub bub_frame_readers: Vec<BubFrameReader<B, S>>,
+    /// Bubbles with the rest of starting frames
+    pub bubs: Vec<BubInOao>,
+    /// Bubble Frame Readers with their transforms.
+    /// Bubbles are resampled if their samples per sec are different.
+    pub bub_frame_readers: Vec<(BubTransform, ResampledBubFrameReader<B::Reader, S>)>,
+    /// Quality of resampling bubbles. `Medium` by default.
+    pub resample_quality: ResampleQuality,
+    /// `smoothing_frames` of each bubble opened after it is set. 0 by default.
+    pub smoothing_frames: u64,
 
     /// Floaout Spaces
     pub oao_spaces: Option<OaoSpaces>,
+    /// Warnings found when the bubbles were checked, if they were
+    pub validation_report: ValidationReport,
 }
 
-impl<R: Read, B: Read + Clone, S: Sample> FrameReader<R, S> for OaoFrameReader<R, B, S> {
+impl<R: Read, B: BubSource, S: Sample> FrameReader<R, S> for OaoFrameReader<R, B, S> {
     fn get_ref(&self) -> &R {
         &self.inner
     }
@@ -46,40 +59,120 @@ impl<R: Read, B: Read + Clone, S: Sample> FrameReader<R, S> for OaoFrameReader<R
     }
 }
 
-impl<R: Read, B: Read + Clone, S: Sample> OaoFrameReader<R, B, S> {
+/// Error about the bubble `file_name`
+fn bub_error(file_name: &str, e: Error) -> Error {
+    Error::new(
+        e.kind(),
+        format!("cannot open bubble `{}`: {}", file_name, e),
+    )
+}
+
+/// Open a bubble from `bub_source`.
+pub(crate) fn open_bub_reader<B: BubSource>(
+    bub_source: &mut B,
+    file_name: &str,
+    speakers_absolute_coord: Vec<BubFnsCoord>,
+) -> Result<BubReader<B::Reader>> {
+    let inner = bub_source
+        .open_bub(file_name)
+        .map_err(|e| bub_error(file_name, e))?;
+    BubReader::new(inner, speakers_absolute_coord).map_err(|e| bub_error(file_name, e))
+}
+
+impl<R: Read, B: BubSource, S: Sample> OaoFrameReader<R, B, S> {
+    /// Type of sample must follow the `LpcmKind` of `metadata`.
+    /// Bubbles are checked by [`OaoMetadata::validate`] first,
+    /// and warnings are in `validation_report`.
     pub fn new(
         inner: R,
         metadata: OaoMetadata,
         speakers_absolute_coord: Vec<BubFnsCoord>,
-        bub_frame_readers: Vec<BubFrameReader<B, S>>,
+        mut bub_source: B,
         oao_spaces: Option<OaoSpaces>,
-    ) -> Self {
-        // TODO: Is same bubs length?
-        let mut bubs = Vec::with_capacity(metadata.bubs.len());
-        for (i, bub_frame_reader) in bub_frame_readers.into_iter().enumerate() {
-            bubs.push((metadata.bubs[i].clone(), bub_frame_reader));
-        }
-        Self {
+    ) -> Result<Self> {
+        let validation_report = metadata.validate(&mut bub_source).into_result()?;
+        let bubs = metadata.bubs.clone();
+
+        Ok(Self {
             inner,
             pos: 0,
             _phantom_sample: PhantomData,
             metadata,
             speakers_absolute_coord,
+            bub_source,
             // Buffers
             bubs,
             bub_frame_readers: Vec::new(),
+            resample_quality: ResampleQuality::default(),
+            smoothing_frames: 0,
             oao_spaces,
-        }
+            validation_report,
+        })
+    }
+
+    /// Open a new instance of a bubble.
+    /// The bubble is moved by `transform`, resampled, and starts from its time offset.
+    fn open_bub_frame_reader(
+        &mut self,
+        file_name: &str,
+        transform: &BubTransform,
+    ) -> Result<ResampledBubFrameReader<B::Reader, S>> {
+        // Moving the bubble is the same as moving speakers the other way.
+        let speakers_absolute_coord = self
+            .speakers_absolute_coord
+            .iter()
+            .map(|&speaker_absolute_coord| speaker_absolute_coord - transform.position_offset)
+            .collect();
+        let bub_reader = open_bub_reader(&mut self.bub_source, file_name, speakers_absolute_coord)?;
+        // Each bubble has its own spaces with the same settings.
+        let oao_spaces = self.oao_spaces.as_ref().map(|oao_spaces| OaoSpaces {
+            frames_between_spaces: oao_spaces.frames_between_spaces,
+            vertex_spacing: oao_spaces.vertex_spacing,
+            start: oao_spaces.start,
+            range: oao_spaces.range,
+            spaces: Vec::new(),
+        });
+
+        let mut bub_frame_reader = bub_reader
+            .try_into_bub_frame_reader(self.metadata.lpcm_kind(), oao_spaces)
+            .map_err(|e| bub_error(file_name, e))?;
+        bub_frame_reader.smoothing_frames = self.smoothing_frames;
+        let mut resampled_bub_frame_reader = ResampledBubFrameReader::new(
+            bub_frame_reader,
+            self.metadata.samples_per_sec,
+            self.resample_quality,
+        );
+        resampled_bub_frame_reader.skip_bub_frames(transform.time_offset)?;
+
+        Ok(resampled_bub_frame_reader)
     }
 
-    fn set_new_bub_frame_readers(&mut self) -> Result<()> {
+    pub(super) fn set_new_bub_frame_readers(&mut self) -> Result<()> {
         let mut i = 0;
         while i < self.bubs.len() {
-            if let Some(starting_frame) = self.bubs[i].0.starting_frames.front() {
+            if let Some(starting_frame) = self.bubs[i].starting_frames.front() {
                 if starting_frame == &self.pos {
-                    self.bubs[i].0.starting_frames.pop_front();
+                    let bub = &mut self.bubs[i];
+                    let transform = if bub.transforms.is_empty() {
+                        BubTransform::default()
+                    } else if bub.transforms.len() == bub.starting_frames.len() {
+                        bub.transforms.pop_front().unwrap()
+                    } else {
+                        let issue = BubIssue {
+                            file_name: bub.file_name.clone(),
+                            starting_frame: None,
+                            kind: BubIssueKind::TransformsMismatch {
+                                starting_frames: bub.starting_frames.len(),
+                                transforms: bub.transforms.len(),
+                            },
+                        };
+                        return Err(Error::new(ErrorKind::InvalidData, issue.to_string()));
+                    };
+                    bub.starting_frames.pop_front();
+                    let file_name = bub.file_name.clone();
+                    let bub_frame_reader = self.open_bub_frame_reader(&file_name, &transform)?;
                     // Push BubFrameReader
-                    self.bub_frame_readers.push(self.bubs[i].1.clone());
+                    self.bub_frame_readers.push((transform, bub_frame_reader));
                 }
                 i += 1;
             } else {
@@ -94,7 +187,8 @@ impl<R: Read, B: Read + Clone, S: Sample> OaoFrameReader<R, B, S> {
     fn read_bub_frame_readers_frame(&mut self, frame: &mut Frame<S>) -> Result<()> {
         let mut i = 0;
         while i < self.bub_frame_readers.len() {
-            match self.bub_frame_readers[i].next() {
+            let (transform, bub_frame_reader) = &mut self.bub_frame_readers[i];
+            match next_bub_frame(transform, bub_frame_reader) {
                 Some(result) => {
                     frame.add(result?)?;
                     i += 1;
@@ -107,30 +201,9 @@ impl<R: Read, B: Read + Clone, S: Sample> OaoFrameReader<R, B, S> {
 
         Ok(())
     }
-}
-
-impl<R: Read, B: Read + Clone, S: Sample> Iterator for OaoFrameReader<R, B, S> {
-    type Item = Result<Frame<S>>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.metadata.frames() <= self.pos {
-            return None;
-        } else {
-            self.pos += 1;
-        }
-
-        if let Err(e) = self.set_new_bub_frame_readers() {
-            return Some(Err(e));
-        }
-
-        let channels = self.speakers_absolute_coord.len();
-        let mut frame: Frame<S> = vec![S::default(); channels].into();
-
-        if let Err(e) = self.read_bub_frame_readers_frame(&mut frame) {
-            return Some(Err(e));
-        }
 
-        // Volume Space
+    /// Push a space at `pos` if it is time to.
+    pub(super) fn push_oao_space(&mut self) {
         if let Some(oao_spaces) = &mut self.oao_spaces {
             if self.pos % oao_spaces.frames_between_spaces == 0 {
                 let num_of_vertices = oao_spaces.range.pow(3);
@@ -142,7 +215,7 @@ impl<R: Read, B: Read + Clone, S: Sample> Iterator for OaoFrameReader<R, B, S> {
 
                 // Each current bub_frame_reader
                 for i in 0..num_of_bub_frame_reader {
-                    if let Some(spaces) = &self.bub_frame_readers[i].oao_spaces {
+                    if let Some(spaces) = &self.bub_frame_readers[i].1.bub_frame_reader.oao_spaces {
                         if let Some(last_space) = spaces.spaces.last() {
                             for pos in 0..num_of_vertices {
                                 let last_alpha = last_space.vertices[pos].alpha;
@@ -168,15 +241,321 @@ impl<R: Read, B: Read + Clone, S: Sample> Iterator for OaoFrameReader<R, B, S> {
                 oao_spaces.spaces.push(oao_space);
             }
         }
+    }
+}
+
+/// Read the next frame of a bubble with its gain.
+pub(super) fn next_bub_frame<R: Read, S: Sample>(
+    transform: &BubTransform,
+    bub_frame_reader: &mut ResampledBubFrameReader<R, S>,
+) -> Option<Result<Frame<S>>> {
+    let result = bub_frame_reader.next()?;
+    Some(result.map(|mut bub_frame| {
+        if transform.gain != 1.0 {
+            let gain = S::from_f64(transform.gain);
+            for sample in bub_frame.0.iter_mut() {
+                *sample = *sample * gain;
+            }
+        }
+        bub_frame
+    }))
+}
+
+impl<R: Read, B: BubSource, S: Sample> Iterator for OaoFrameReader<R, B, S> {
+    type Item = Result<Frame<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.metadata.frames() <= self.pos {
+            return None;
+        } else {
+            self.pos += 1;
+        }
+
+        if let Err(e) = self.set_new_bub_frame_readers() {
+            return Some(Err(e));
+        }
+
+        let channels = self.speakers_absolute_coord.len();
+        let mut frame: Frame<S> = vec![S::default(); channels].into();
+
+        if let Err(e) = self.read_bub_frame_readers_frame(&mut frame) {
+            return Some(Err(e));
+        }
+
+        // Volume Space
+        self.push_oao_space();
 
         Some(Ok(frame))
     }
 }
 
-pub type OaoFrameReaderKind<R, B> =
-    FrameIOKind<OaoFrameReader<R, B, f32>, OaoFrameReader<R, B, f64>>;
+pub type OaoFrameReaderKind<R, B> = FrameIOKind<
+    OaoFrameReader<R, B, f32>,
+    OaoFrameReader<R, B, f64>,
+    OaoFrameReader<R, B, f32>,
+    OaoFrameReader<R, B, f32>,
+    OaoFrameReader<R, B, f64>,
+    OaoFrameReader<R, B, f32>,
+>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubMetadata, BubSampleKind, BubWriter};
+    use crate::oao::MemoryBubSource;
+    use crate::LpcmKind;
+
+    #[test]
+    fn resample_bubbles() -> Result<()> {
+        let bub = |samples_per_sec| -> Result<Vec<u8>> {
+            let metadata = BubMetadata::new(
+                4,
+                1,
+                samples_per_sec,
+                LpcmKind::F32LE,
+                BubSampleKind::default_expr(),
+                String::from("Bubble"),
+            );
+            let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+            let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+                bub_fns: b"0 0 0 X<3 1",
+                foot_relative_frame: 4,
+                next_head_relative_frame: None,
+                expression: b"0.5",
+            })?;
+            Ok(bub_frame_writer.inner)
+        };
+        let (bub_96k, bub_48k) = (bub(96000.0)?, bub(48000.0)?);
+
+        let bub_in_oao = |file_name: &str| BubInOao::new(file_name.into(), vec![1].into());
+        let metadata = OaoMetadata::new(
+            10,
+            96000.0,
+            LpcmKind::F32LE,
+            String::from("Resample"),
+            String::from("Artist"),
+            vec![bub_in_oao("96k"), bub_in_oao("48k")],
+        );
+        let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into()];
+        let mut bub_source = MemoryBubSource::new();
+        bub_source.insert("96k".into(), bub_96k);
+        bub_source.insert("48k".into(), bub_48k);
+        let oao_frame_reader = OaoFrameReader::<_, _, f32>::new(
+            &[][..],
+            metadata,
+            speakers_absolute_coord,
+            bub_source,
+            None,
+        )?;
+        assert_eq!(oao_frame_reader.validation_report.warnings().count(), 1);
+
+        let frames = oao_frame_reader
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+        // The 48 kHz bubble lasts 8 frames at 96 kHz.
+        assert_eq!(frames, [1.0, 1.0, 1.0, 1.0, 0.5, 0.5, 0.5, 0.5, 0.0, 0.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn transforms() -> Result<()> {
+        let metadata = BubMetadata::new(
+            4,
+            1,
+            48000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::default_expr(),
+            String::from("Bubble"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 x<1 1",
+            foot_relative_frame: 2,
+            next_head_relative_frame: None,
+            expression: b"0.5",
+        })?;
+        let mut bub_source = MemoryBubSource::new();
+        bub_source.insert("bubble".into(), bub_frame_writer.inner);
+
+        let mut bub_in_oao = BubInOao::new("bubble".into(), vec![1, 3, 5, 6].into());
+        bub_in_oao.transforms = vec![
+            BubTransform::new(),
+            BubTransform {
+                gain: 0.5,
+                time_offset: 1,
+                ..BubTransform::new()
+            },
+            // Out of the domain
+            BubTransform {
+                position_offset: (-2.0, 0.0, 0.0).into(),
+                ..BubTransform::new()
+            },
+            BubTransform {
+                position_offset: (2.0, 0.0, 0.0).into(),
+                ..BubTransform::new()
+            },
+        ]
+        .into();
+        let mut metadata = OaoMetadata::new(
+            6,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("Transforms"),
+            String::from("Artist"),
+            vec![bub_in_oao],
+        );
+        let oao_frame_reader = OaoFrameReader::<_, _, f32>::new(
+            &[][..],
+            metadata.clone(),
+            vec![(0.0, 0.0, 0.0).into()],
+            bub_source.clone(),
+            None,
+        )?;
+
+        let frames = oao_frame_reader
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames, [0.5, 0.5, 0.25, 0.0, 0.0, 0.5]);
+
+        // Transforms which don't match starting frames
+        metadata.bubs[0].transforms.pop_back();
+        let e = OaoFrameReader::<_, _, f32>::new(
+            &[][..],
+            metadata.clone(),
+            vec![(0.0, 0.0, 0.0).into()],
+            bub_source.clone(),
+            None,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        metadata.bubs[0].transforms.clear();
+        let mut oao_frame_reader = OaoFrameReader::<_, _, f32>::new(
+            &[][..],
+            metadata,
+            vec![(0.0, 0.0, 0.0).into()],
+            bub_source,
+            None,
+        )?;
+        oao_frame_reader.bubs[0]
+            .transforms
+            .push_back(BubTransform::new());
+        assert_eq!(
+            oao_frame_reader.next().unwrap().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn smoothing_frames() -> Result<()> {
+        // The 96 kHz bubble is resampled, and the domain is on the timeline of the Floaout file.
+        for &(frames, samples_per_sec) in [(4, 48000.0), (8, 96000.0)].iter() {
+            let metadata = BubMetadata::new(
+                frames,
+                1,
+                samples_per_sec,
+                LpcmKind::F32LE,
+                BubSampleKind::default_expr(),
+                String::from("Bubble"),
+            );
+            let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+            let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+                bub_fns: b"0 0 0 n<=2 1",
+                foot_relative_frame: frames,
+                next_head_relative_frame: None,
+                expression: b"0.5",
+            })?;
+            let mut bub_source = MemoryBubSource::new();
+            bub_source.insert("bubble".into(), bub_frame_writer.inner);
+            let metadata = OaoMetadata::new(
+                4,
+                48000.0,
+                LpcmKind::F32LE,
+                String::from("Smoothing"),
+                String::from("Artist"),
+                vec![BubInOao::new("bubble".into(), vec![1].into())],
+            );
+            let mut oao_frame_reader = OaoFrameReader::<_, _, f32>::new(
+                &[][..],
+                metadata,
+                vec![(0.0, 0.0, 0.0).into()],
+                bub_source,
+                None,
+            )?;
+            oao_frame_reader.smoothing_frames = 2;
+
+            let frames = oao_frame_reader
+                .map(|frame| frame.map(|frame| frame.0[0]))
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(frames, [0.5, 0.5, 0.25, 0.0]);
+        }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-// }
+        Ok(())
+    }
+
+    #[test]
+    fn time_offset_of_resampled_bubble() -> Result<()> {
+        let metadata = BubMetadata::new(
+            8,
+            1,
+            96000.0,
+            LpcmKind::F32LE,
+            BubSampleKind::default_expr(),
+            String::from("Bubble"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 X<3 1",
+            foot_relative_frame: 8,
+            next_head_relative_frame: None,
+            expression: b"0.1*n",
+        })?;
+        let mut bub_source = MemoryBubSource::new();
+        bub_source.insert("96k".into(), bub_frame_writer.inner);
+
+        let mut bub_in_oao = BubInOao::new("96k".into(), vec![1, 4].into());
+        bub_in_oao.transforms = vec![
+            BubTransform::new(),
+            // 4 frames at 96 kHz are 2 frames at 48 kHz.
+            BubTransform {
+                time_offset: 4,
+                ..BubTransform::new()
+            },
+        ]
+        .into();
+        let metadata = OaoMetadata::new(
+            8,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("Time Offset"),
+            String::from("Artist"),
+            vec![bub_in_oao],
+        );
+        let oao_frame_reader = OaoFrameReader::<_, _, f32>::new(
+            &[][..],
+            metadata,
+            vec![(0.0, 0.0, 0.0).into()],
+            bub_source,
+            None,
+        )?;
+
+        let frames = oao_frame_reader
+            .map(|frame| frame.map(|frame| frame.0[0]))
+            .collect::<Result<Vec<_>>>()?;
+        // The second instance starts from `n` = 3 at 48 kHz, and lasts 2 frames.
+        let expected = [0.1, 0.2, 0.3, 0.4 + 0.3, 0.4, 0.0, 0.0, 0.0];
+        assert_eq!(frames.len(), expected.len());
+        for (frame, expected) in frames.iter().zip(expected.iter()) {
+            assert!((frame - expected).abs() < 1e-6, "{:?}", frames);
+        }
+
+        Ok(())
+    }
+}
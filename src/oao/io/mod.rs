@@ -1,7 +1,17 @@
+pub use self::bub_source::{BubBytes, BubSource, DirBubSource, MemoryBubSource, ZipBubSource};
+pub use self::bundle::{
+    OaoBundleReader, OaoBundleWriter, OAO_BUNDLE_EXTENSION, OAO_BUNDLE_VERSION,
+};
 pub use self::frame_reader::{OaoFrameReader, OaoFrameReaderKind};
+#[cfg(feature = "parallel")]
+pub use self::parallel_frame_reader::ParallelOaoFrameReader;
 pub use self::reader::OaoReader;
 pub use self::writer::OaoWriter;
 
+mod bub_source;
+mod bundle;
 mod frame_reader;
+#[cfg(feature = "parallel")]
+mod parallel_frame_reader;
 mod reader;
 mod writer;
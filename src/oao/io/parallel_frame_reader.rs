@@ -0,0 +1,358 @@
+use super::frame_reader::next_bub_frame;
+use crate::bub::ResampledBubFrameReader;
+use crate::oao::{BubSource, BubTransform, OaoFrameReader};
+use crate::{Frame, FrameReader, Sample};
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use std::io::{Error, Read, Result};
+
+/// Frames of a bubble in a block
+struct BubBlock<S: Sample> {
+    frames: Vec<Frame<S>>,
+    /// The bubble is ended after `frames`.
+    is_ended: bool,
+    /// Error after `frames`
+    error: Option<Error>,
+}
+
+fn read_bub_block<R: Read, S: Sample>(
+    transform: &BubTransform,
+    bub_frame_reader: &mut ResampledBubFrameReader<R, S>,
+    block_frames: usize,
+) -> BubBlock<S> {
+    let mut frames = Vec::with_capacity(block_frames);
+    while frames.len() < block_frames {
+        match next_bub_frame(transform, bub_frame_reader) {
+            Some(Ok(frame)) => frames.push(frame),
+            Some(Err(e)) => {
+                return BubBlock {
+                    frames,
+                    is_ended: false,
+                    error: Some(e),
+                }
+            }
+            None => {
+                return BubBlock {
+                    frames,
+                    is_ended: true,
+                    error: None,
+                }
+            }
+        }
+    }
+
+    BubBlock {
+        frames,
+        is_ended: false,
+        error: None,
+    }
+}
+
+/// Frame reader of a Floaout file which reads its bubbles on multiple threads.
+///
+/// Bubbles are read in parallel for each block of frames, and summed in the same order as
+/// [`OaoFrameReader`], so frames and spaces are the same bit for bit.
+/// After an error, later frames may be different.
+pub struct ParallelOaoFrameReader<R: Read, B: BubSource, S: Sample> {
+    pub oao_frame_reader: OaoFrameReader<R, B, S>,
+    /// Maximum frames of a block.
+    /// Blocks also end before new bubbles start and at each space.
+    pub block_frames: u64,
+
+    // Buffer
+    frames: VecDeque<Result<Frame<S>>>,
+}
+
+impl<R: Read, B: BubSource, S: Sample + Send> ParallelOaoFrameReader<R, B, S>
+where
+    B::Reader: Send,
+{
+    pub fn new(oao_frame_reader: OaoFrameReader<R, B, S>, block_frames: u64) -> Self {
+        Self {
+            oao_frame_reader,
+            block_frames,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Read the next block into the buffer.
+    fn read_block(&mut self) {
+        let oao_frame_reader = &mut self.oao_frame_reader;
+        let frames = oao_frame_reader.metadata.frames();
+        if frames <= oao_frame_reader.pos {
+            return;
+        }
+        oao_frame_reader.pos += 1;
+        let first_frame = oao_frame_reader.pos;
+
+        if let Err(e) = oao_frame_reader.set_new_bub_frame_readers() {
+            self.frames.push_back(Err(e));
+            return;
+        }
+
+        let mut last_frame = frames.min(first_frame.saturating_add(self.block_frames.max(1) - 1));
+        for bub in oao_frame_reader.bubs.iter() {
+            if let Some(&starting_frame) = bub.starting_frames.front() {
+                if first_frame < starting_frame {
+                    last_frame = last_frame.min(starting_frame - 1);
+                }
+            }
+        }
+        if let Some(oao_spaces) = &oao_frame_reader.oao_spaces {
+            let frames_between_spaces = oao_spaces.frames_between_spaces;
+            let next_space = first_frame
+                + (frames_between_spaces - first_frame % frames_between_spaces)
+                    % frames_between_spaces;
+            last_frame = last_frame.min(next_space);
+        }
+        let block_frames = (last_frame - first_frame + 1) as usize;
+
+        let bub_blocks: Vec<BubBlock<S>> = oao_frame_reader
+            .bub_frame_readers
+            .par_iter_mut()
+            .map(|(transform, bub_frame_reader)| {
+                read_bub_block(transform, bub_frame_reader, block_frames)
+            })
+            .collect();
+
+        // Sum in the order of `bub_frame_readers`.
+        let channels = oao_frame_reader.speakers_absolute_coord.len();
+        let mut is_ended = Vec::with_capacity(bub_blocks.len());
+        let mut bub_blocks: Vec<_> = bub_blocks
+            .into_iter()
+            .map(|bub_block| {
+                is_ended.push(bub_block.is_ended);
+                (bub_block.frames.into_iter(), bub_block.error)
+            })
+            .collect();
+        let mut error = None;
+        'frames: for i in 0..block_frames {
+            let mut frame: Frame<S> = vec![S::default(); channels].into();
+            for (bub_frames, bub_error) in bub_blocks.iter_mut() {
+                match bub_frames.next() {
+                    Some(bub_frame) => {
+                        if let Err(e) = frame.add(bub_frame) {
+                            error = Some((i, e));
+                            break 'frames;
+                        }
+                    }
+                    None => {
+                        if let Some(e) = bub_error.take() {
+                            error = Some((i, e));
+                            break 'frames;
+                        }
+                    }
+                }
+            }
+            self.frames.push_back(Ok(frame));
+        }
+
+        let mut is_ended = is_ended.into_iter();
+        oao_frame_reader
+            .bub_frame_readers
+            .retain(|_| !is_ended.next().unwrap_or_default());
+
+        if let Some((i, e)) = error {
+            oao_frame_reader.pos = first_frame + i as u64;
+            self.frames.push_back(Err(e));
+        } else {
+            oao_frame_reader.pos = last_frame;
+            // Volume Space
+            oao_frame_reader.push_oao_space();
+        }
+    }
+}
+
+impl<R: Read, B: BubSource, S: Sample + Send> Iterator for ParallelOaoFrameReader<R, B, S>
+where
+    B::Reader: Send,
+{
+    type Item = Result<Frame<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frames.is_empty() {
+            self.read_block();
+        }
+
+        self.frames.pop_front()
+    }
+}
+
+impl<R: Read, B: BubSource, S: Sample + Send> FrameReader<R, S> for ParallelOaoFrameReader<R, B, S>
+where
+    B::Reader: Send,
+{
+    fn get_ref(&self) -> &R {
+        self.oao_frame_reader.get_ref()
+    }
+    fn get_mut(&mut self) -> &mut R {
+        self.oao_frame_reader.get_mut()
+    }
+    fn into_inner(self) -> R {
+        self.oao_frame_reader.into_inner()
+    }
+
+    fn frames(&self) -> u64 {
+        self.oao_frame_reader.frames()
+    }
+
+    fn samples_per_sec(&self) -> f64 {
+        self.oao_frame_reader.samples_per_sec()
+    }
+
+    fn number_of_channels(&self) -> u32 {
+        self.oao_frame_reader.number_of_channels()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubMetadata, BubSampleKind, BubWriter};
+    use crate::oao::{BubInOao, MemoryBubSource, OaoMetadata};
+    use crate::{LpcmKind, OaoSpaces};
+
+    fn bub(
+        frames: u64,
+        samples_per_sec: f64,
+        bub_sample_kind: BubSampleKind,
+        blocks: Vec<BubFnsBlock<f32>>,
+    ) -> Result<Vec<u8>> {
+        let metadata = BubMetadata::new(
+            frames,
+            1,
+            samples_per_sec,
+            LpcmKind::F32LE,
+            bub_sample_kind,
+            String::from("Bubble"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = bub_writer.into_bub_frame_writer_kind().into_f32_le()?;
+        for block in blocks {
+            bub_frame_writer.write_head_to_less_than_next_head_or_ended(block)?;
+        }
+        Ok(bub_frame_writer.inner)
+    }
+
+    fn oao_frame_reader() -> Result<OaoFrameReader<&'static [u8], MemoryBubSource, f32>> {
+        // Two expression blocks, the second of which is 1 frame
+        let expr_bub = |samples_per_sec, bub_fns, expression| {
+            bub(
+                7,
+                samples_per_sec,
+                BubSampleKind::default_expr(),
+                vec![
+                    BubFnsBlock::Expr {
+                        bub_fns,
+                        foot_relative_frame: 5,
+                        next_head_relative_frame: Some(7),
+                        expression,
+                    },
+                    BubFnsBlock::Expr {
+                        bub_fns: b"0 0 0 X<3 1",
+                        foot_relative_frame: 1,
+                        next_head_relative_frame: None,
+                        expression: b"0.3",
+                    },
+                ],
+            )
+        };
+        let mut bub_source = MemoryBubSource::new();
+        bub_source.insert("a".into(), expr_bub(48000.0, b"0 0 0 X<3 1", b"0.1*n")?);
+        bub_source.insert("b".into(), expr_bub(44100.0, b"0 0 0 x<1 x+1", b"0.3*N")?);
+        bub_source.insert(
+            "c".into(),
+            bub(
+                5,
+                48000.0,
+                BubSampleKind::Lpcm,
+                vec![BubFnsBlock::Lpcm {
+                    bub_fns: b"0 0 0 x<0.5 1-y",
+                    next_head_relative_frame: None,
+                    samples: vec![0.7, -0.1, 0.3, 0.9, -0.6],
+                }],
+            )?,
+        );
+
+        let mut a = BubInOao::new("a".into(), vec![1, 4, 9, 20].into());
+        a.transforms = vec![
+            BubTransform::new(),
+            BubTransform {
+                gain: 0.7,
+                time_offset: 2,
+                ..BubTransform::new()
+            },
+            BubTransform {
+                position_offset: (0.5, 0.0, 0.0).into(),
+                ..BubTransform::new()
+            },
+            BubTransform::new(),
+        ]
+        .into();
+        let metadata = OaoMetadata::new(
+            24,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("Parallel"),
+            String::from("Artist"),
+            vec![
+                a,
+                BubInOao::new("b".into(), vec![2, 3, 13].into()),
+                BubInOao::new("c".into(), vec![5, 6, 7, 8].into()),
+            ],
+        );
+        let oao_spaces = OaoSpaces {
+            frames_between_spaces: 5,
+            range: 2,
+            ..OaoSpaces::new()
+        };
+        OaoFrameReader::new(
+            &[][..],
+            metadata,
+            vec![(0.0, 0.0, 0.0).into(), (1.0, 0.0, 0.0).into()],
+            bub_source,
+            Some(oao_spaces),
+        )
+    }
+
+    #[test]
+    fn same_as_oao_frame_reader() -> Result<()> {
+        let mut serial_oao_frame_reader = oao_frame_reader()?;
+        let frames = serial_oao_frame_reader
+            .by_ref()
+            .map(|frame| frame.map(|frame| frame.0))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames.len(), 24);
+        assert!(frames.iter().flatten().any(|&sample| sample != 0.0));
+        assert_eq!(
+            serial_oao_frame_reader
+                .oao_spaces
+                .as_ref()
+                .map(|oao_spaces| oao_spaces.spaces.len()),
+            Some(4)
+        );
+
+        for &block_frames in [0, 1, 3, 64].iter() {
+            let mut parallel_oao_frame_reader =
+                ParallelOaoFrameReader::new(oao_frame_reader()?, block_frames);
+            let parallel_frames = parallel_oao_frame_reader
+                .by_ref()
+                .map(|frame| frame.map(|frame| frame.0))
+                .collect::<Result<Vec<_>>>()?;
+            let to_bits = |frames: &[Vec<f32>]| -> Vec<Vec<u32>> {
+                frames
+                    .iter()
+                    .map(|frame| frame.iter().map(|sample| sample.to_bits()).collect())
+                    .collect()
+            };
+            assert_eq!(to_bits(&parallel_frames), to_bits(&frames));
+            assert_eq!(
+                parallel_oao_frame_reader.oao_frame_reader.oao_spaces,
+                serial_oao_frame_reader.oao_spaces
+            );
+            assert_eq!(parallel_oao_frame_reader.oao_frame_reader.pos, 24);
+        }
+
+        Ok(())
+    }
+}
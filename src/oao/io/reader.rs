@@ -1,6 +1,9 @@
-use crate::bub::BubFrameReader;
-use crate::oao::{OaoFrameReader, OaoMetadata};
-use crate::{BubFnsCoord, OaoSpaces, Sample};
+use crate::oao::{
+    BubSource, DirBubSource, MemoryBubSource, OaoBundleReader, OaoBundleWriter, OaoFrameReader,
+    OaoFrameReaderKind, OaoMetadata,
+};
+use crate::verify::VerifyReport;
+use crate::{BubFnsCoord, LpcmKind, OaoSpaces, Sample};
 use std::fs::File;
 use std::io::{BufReader, Read, Result};
 use std::path::Path;
@@ -23,39 +26,64 @@ impl<R: Read> OaoReader<R> {
         })
     }
 
+    /// Walk the whole Floaout file and report every problem, such as a wrong CRC.
+    /// Returns an error only if `inner` can't be read.
+    pub fn verify(inner: R) -> Result<VerifyReport> {
+        crate::oao::verify::verify(inner)
+    }
+
+    /// Walk the whole Floaout file and return its metadata and bubbles as JSON,
+    /// with byte offsets and CRC status.
+    /// Broken files are dumped as far as they can be read, with `error` and `error_byte_offset`.
+    pub fn dump_json(inner: R) -> Result<String> {
+        crate::oao::dump::dump(inner)
+    }
+
+    /// Bubbles of `metadata.bubs` are opened from `bub_source` at each starting frame.
+    /// They are checked by [`OaoMetadata::validate`] first,
+    /// and warnings are in `validation_report` of the frame reader.
+    ///
     /// # Safety
     ///
     /// This is unsafe, due to the type of sample isn’t checked:
     /// - type of sample must follow [`LpcmKind`]
-    // TODO: Is metadata.bubs.len() equals to bub_frame_readers.len()?
-    pub unsafe fn into_oao_frame_reader<B: Read + Clone, S: Sample>(
+    pub unsafe fn into_oao_frame_reader<B: BubSource, S: Sample>(
         self,
-        bub_frame_readers: Vec<BubFrameReader<B, S>>,
+        bub_source: B,
         oao_spaces: Option<OaoSpaces>,
-    ) -> OaoFrameReader<R, B, S> {
+    ) -> Result<OaoFrameReader<R, B, S>> {
         OaoFrameReader::new(
             self.inner,
             self.metadata,
             self.speakers_absolute_coord,
-            bub_frame_readers,
+            bub_source,
             oao_spaces,
         )
     }
 
-    // pub fn into_oao_frame_reader_kind<B: Read + Clone, S: Sample>(self, bub_frame_readers: Vec<BubFrameReader<B, S>>) -> OaoFrameReaderKind<R, B> {
-    //     match self.metadata.lpcm_kind() {
-    //         LpcmKind::F32LE => OaoFrameReaderKind::F32LE(OaoFrameReader::<R, f32>::new(
-    //             self.inner,
-    //             self.metadata,
-    //             self.speakers_absolute_coord,
-    //         )),
-    //         LpcmKind::F64LE => OaoFrameReaderKind::F64LE(OaoFrameReader::<R, f64>::new(
-    //             self.inner,
-    //             self.metadata,
-    //             self.speakers_absolute_coord,
-    //         )),
-    //     }
-    // }
+    /// Bubbles of `metadata.bubs` are opened from `bub_source` at each starting frame.
+    /// They are checked by [`OaoMetadata::validate`] first,
+    /// and warnings are in `validation_report` of the frame reader.
+    pub fn into_oao_frame_reader_kind<B: BubSource>(
+        self,
+        bub_source: B,
+    ) -> Result<OaoFrameReaderKind<R, B>> {
+        macro_rules! oao_frame_reader {
+            ($sample:ty) => {
+                // Sample types follow `LpcmKind`.
+                unsafe { self.into_oao_frame_reader::<B, $sample>(bub_source, None) }?
+            };
+        }
+
+        Ok(match self.metadata.lpcm_kind() {
+            LpcmKind::F32LE => OaoFrameReaderKind::F32LE(oao_frame_reader!(f32)),
+            LpcmKind::F64LE => OaoFrameReaderKind::F64LE(oao_frame_reader!(f64)),
+            LpcmKind::I16LE => OaoFrameReaderKind::I16LE(oao_frame_reader!(f32)),
+            LpcmKind::I24LE => OaoFrameReaderKind::I24LE(oao_frame_reader!(f32)),
+            LpcmKind::I32LE => OaoFrameReaderKind::I32LE(oao_frame_reader!(f64)),
+            LpcmKind::U8 => OaoFrameReaderKind::U8(oao_frame_reader!(f32)),
+        })
+    }
 }
 
 impl OaoReader<BufReader<File>> {
@@ -67,10 +95,122 @@ impl OaoReader<BufReader<File>> {
         let buf_reader = BufReader::new(file);
         Self::new(buf_reader, speakers_absolute_coord)
     }
-    // TODO: Add open_and_into_oao_frame_reader_kind method
+
+    /// Open a Floaout file and its bubbles.
+    /// Bubbles are `{file_name}.bub` in the same directory.
+    pub fn open_and_into_oao_frame_reader_kind<P: AsRef<Path>>(
+        filename: P,
+        speakers_absolute_coord: Vec<BubFnsCoord>,
+    ) -> Result<OaoFrameReaderKind<BufReader<File>, DirBubSource>> {
+        let oao_reader = Self::open(&filename, speakers_absolute_coord)?;
+        let dir = filename.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        oao_reader.into_oao_frame_reader_kind(DirBubSource::new(dir))
+    }
+
+    /// Open a Floaout bundle and play it with its bubbles.
+    pub fn open_bundle_and_into_oao_frame_reader_kind<P: AsRef<Path>>(
+        filename: P,
+        speakers_absolute_coord: Vec<BubFnsCoord>,
+    ) -> Result<OaoFrameReaderKind<BufReader<File>, MemoryBubSource>> {
+        OaoBundleReader::open(filename, speakers_absolute_coord)?.into_oao_frame_reader_kind()
+    }
+
+    /// Pack a Floaout file and its bubbles in the same directory into a bundle.
+    pub fn pack_bundle<P: AsRef<Path>, Q: AsRef<Path>>(
+        filename: P,
+        bundle_filename: Q,
+    ) -> Result<()> {
+        let oao_reader = Self::open(&filename, Vec::new())?;
+        let dir = filename.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        OaoBundleWriter::create(bundle_filename, oao_reader.metadata, DirBubSource::new(dir))?;
+
+        Ok(())
+    }
+
+    /// Unpack a bundle into a Floaout file and its bubbles in the same directory.
+    pub fn unpack_bundle<P: AsRef<Path>, Q: AsRef<Path>>(
+        bundle_filename: P,
+        filename: Q,
+    ) -> Result<()> {
+        OaoBundleReader::open(bundle_filename, Vec::new())?.unpack(filename)
+    }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubFnsBlock, BubMetadata, BubSampleKind, BubWriter};
+    use crate::oao::BubInOao;
+    use crate::tests::TempDir;
+    use std::io::ErrorKind;
+
+    fn write_bub(path: &Path, lpcm_kind: LpcmKind) -> Result<()> {
+        let metadata = BubMetadata::new(
+            4,
+            1,
+            48000.0,
+            lpcm_kind,
+            BubSampleKind::default_expr(),
+            String::from("Bubble"),
+        );
+        let bub_writer = BubWriter::new(Vec::new(), metadata)?;
+        let mut bub_frame_writer = unsafe { bub_writer.into_bub_frame_writer::<f32>() };
+        bub_frame_writer.write_head_to_less_than_next_head_or_ended(BubFnsBlock::Expr {
+            bub_fns: b"0 0 0 X<3 1",
+            foot_relative_frame: 2,
+            next_head_relative_frame: None,
+            expression: b"0.5",
+        })?;
+        std::fs::write(path, bub_frame_writer.inner)
+    }
+
+    #[test]
+    fn open_and_into_oao_frame_reader_kind() -> Result<()> {
+        let temp_dir = TempDir::new("oao-reader")?;
+        let dir = temp_dir.path();
+        let oao_path = dir.join("song.oao");
+        let bubs = vec![BubInOao::new("vocal".into(), vec![1, 2].into())];
+        let metadata = OaoMetadata::new(
+            6,
+            48000.0,
+            LpcmKind::I16LE,
+            String::from("Song"),
+            String::from("Artist"),
+            bubs,
+        );
+        let mut v = Vec::new();
+        metadata.write(&mut v)?;
+        std::fs::write(&oao_path, v)?;
+        let speakers_absolute_coord = vec![(0.0, 0.0, 0.0).into()];
+
+        // Missing bubble
+        let e = OaoReader::open_and_into_oao_frame_reader_kind(
+            &oao_path,
+            speakers_absolute_coord.clone(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(e.kind(), ErrorKind::NotFound);
+        assert!(e.to_string().contains("vocal"));
+
+        // Mismatched LpcmKind
+        write_bub(&dir.join("vocal.bub"), LpcmKind::F32LE)?;
+        let e = OaoReader::open_and_into_oao_frame_reader_kind(
+            &oao_path,
+            speakers_absolute_coord.clone(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+
+        write_bub(&dir.join("vocal.bub"), LpcmKind::I16LE)?;
+        let frames =
+            OaoReader::open_and_into_oao_frame_reader_kind(&oao_path, speakers_absolute_coord)?
+                .into_i16_le()?
+                .map(|frame| frame.map(|frame| frame.0[0]))
+                .collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames, [0.5, 1.0, 0.5, 0.0, 0.0, 0.0]);
+
+        Ok(())
+    }
+}
@@ -1,14 +1,196 @@
 use crate::io::{ReadExt, WriteExt};
-use crate::oao::OaoID;
-use crate::utils::{read_crc, write_crc};
-use crate::{LpcmKind, Metadata, CRC_32K_4_2};
+use crate::oao::validate::validate_bubs;
+use crate::oao::{BubSource, OaoID, ValidationReport};
+use crate::tags::{check_spec_version, Tags, TAGS_SPEC_VERSION};
+use crate::utils::{
+    check_string_size, read_crc, read_sized_string_and_calc_bytes, u16_len, write_crc,
+    write_sized_str_and_calc_bytes,
+};
+use crate::{BubFnsCoord, LpcmKind, Metadata, CRC_32K_4_2};
+use mycrc::CRC;
 use std::collections::VecDeque;
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+/// Spec version from which each starting frame can have a [`BubTransform`]
+pub const TRANSFORMS_SPEC_VERSION: u8 = 4;
+
+/// Transform of a bubble placed at a starting frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BubTransform {
+    /// Added to the bubble absolute coordinates. The whole bubble moves, including its domains.
+    pub position_offset: BubFnsCoord,
+    /// Multiplied to samples
+    pub gain: f64,
+    /// Number of frames skipped at the start of the bubble, at the samples per sec of the bubble
+    pub time_offset: u64,
+}
+
+impl BubTransform {
+    /// Transform which changes nothing
+    pub const fn new() -> Self {
+        Self {
+            position_offset: BubFnsCoord {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            gain: 1.0,
+            time_offset: 0,
+        }
+    }
+
+    fn read_and_calc_bytes<R: std::io::Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<Self> {
+        let x = reader.read_le_and_calc_bytes(crc)?;
+        let y = reader.read_le_and_calc_bytes(crc)?;
+        let z = reader.read_le_and_calc_bytes(crc)?;
+        let gain = reader.read_le_and_calc_bytes(crc)?;
+        let time_offset = reader.read_le_and_calc_bytes(crc)?;
+
+        Ok(Self {
+            position_offset: (x, y, z).into(),
+            gain,
+            time_offset,
+        })
+    }
+
+    fn write_and_calc_bytes<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        writer.write_le_and_calc_bytes(self.position_offset.x, crc)?;
+        writer.write_le_and_calc_bytes(self.position_offset.y, crc)?;
+        writer.write_le_and_calc_bytes(self.position_offset.z, crc)?;
+        writer.write_le_and_calc_bytes(self.gain, crc)?;
+        writer.write_le_and_calc_bytes(self.time_offset, crc)
+    }
+}
+
+impl Default for BubTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct BubInOao {
     pub file_name: String,
     pub starting_frames: VecDeque<u64>,
+    /// Empty, or the transform of each starting frame from spec version 4
+    pub transforms: VecDeque<BubTransform>,
+}
+
+impl BubInOao {
+    /// Bubble without transforms
+    pub const fn new(file_name: String, starting_frames: VecDeque<u64>) -> Self {
+        Self {
+            file_name,
+            starting_frames,
+            transforms: VecDeque::new(),
+        }
+    }
+
+    /// Read a bubble without its CRC.
+    pub(crate) fn read_and_calc_bytes<R: std::io::Read>(
+        reader: &mut R,
+        spec_version: u8,
+        crc: &mut CRC<u32>,
+    ) -> Result<Self> {
+        // File Name
+        let file_name = read_sized_string_and_calc_bytes(reader, spec_version, crc)?;
+        // Starting Frames
+        let num_of_starting_frames: u16 = reader.read_le_and_calc_bytes(crc)?;
+        let mut starting_frames = VecDeque::with_capacity(num_of_starting_frames as usize);
+        for _ in 0..num_of_starting_frames {
+            let starting_frame: u64 = reader.read_le_and_calc_bytes(crc)?;
+            starting_frames.push_back(starting_frame);
+        }
+        // Transforms
+        let mut transforms = VecDeque::new();
+        if spec_version >= TRANSFORMS_SPEC_VERSION {
+            let num_of_transforms: u16 = reader.read_le_and_calc_bytes(crc)?;
+            if num_of_transforms != 0 && num_of_transforms != num_of_starting_frames {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "bubble `{}` has {} starting frames, but {} transforms",
+                        file_name, num_of_starting_frames, num_of_transforms
+                    ),
+                ));
+            }
+            for _ in 0..num_of_transforms {
+                transforms.push_back(BubTransform::read_and_calc_bytes(reader, crc)?);
+            }
+        }
+
+        Ok(Self {
+            file_name,
+            starting_frames,
+            transforms,
+        })
+    }
+
+    /// Returns an error if the bubble can't be written in `spec_version`.
+    fn check_writable(&self, spec_version: u8) -> Result<()> {
+        check_string_size("file name", &self.file_name, spec_version)?;
+        u16_len("number of starting frames", self.starting_frames.len())?;
+        if !self.transforms.is_empty() {
+            if spec_version < TRANSFORMS_SPEC_VERSION {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "transforms need spec version {} or later, found {}",
+                        TRANSFORMS_SPEC_VERSION, spec_version
+                    ),
+                ));
+            }
+            if self.transforms.len() != self.starting_frames.len() {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "bubble `{}` has {} starting frames, but {} transforms",
+                        self.file_name,
+                        self.starting_frames.len(),
+                        self.transforms.len()
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a bubble without its CRC.
+    fn write_and_calc_bytes<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        spec_version: u8,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        self.check_writable(spec_version)?;
+        let num_of_starting_frames =
+            u16_len("number of starting frames", self.starting_frames.len())?;
+
+        // Name
+        write_sized_str_and_calc_bytes(writer, "file name", &self.file_name, spec_version, crc)?;
+        // Starting Frames
+        writer.write_le_and_calc_bytes(num_of_starting_frames, crc)?;
+        for starting_frame in self.starting_frames.iter() {
+            writer.write_le_and_calc_bytes(*starting_frame, crc)?;
+        }
+        // Transforms
+        if spec_version >= TRANSFORMS_SPEC_VERSION {
+            writer.write_le_and_calc_bytes(
+                u16_len("number of transforms", self.transforms.len())?,
+                crc,
+            )?;
+            for transform in self.transforms.iter() {
+                transform.write_and_calc_bytes(writer, crc)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -30,6 +212,8 @@ pub struct OaoMetadata {
     pub title: String,
     /// Artist of Floaout
     pub artist: String,
+    /// Tags from spec version 1
+    pub tags: Tags,
 
     // Each Bubble
     pub bubs: Vec<BubInOao>,
@@ -53,6 +237,7 @@ impl OaoMetadata {
             lpcm_kind,
             title,
             artist,
+            tags: Tags::new(),
             bubs,
         }
     }
@@ -85,100 +270,158 @@ impl OaoMetadata {
         self.frames() as u128 * 1_000_000_000 / self.samples_per_sec() as u128
     }
 
+    /// Check `bubs` and their bubbles opened from `bub_source`,
+    /// such as their `LpcmKind`, samples per sec and whether they fit in `frames`.
+    pub fn validate<B: BubSource>(&self, bub_source: &mut B) -> ValidationReport {
+        validate_bubs(self, bub_source)
+    }
+
     // IO
     pub fn read<R: std::io::Read>(reader: &mut R) -> Result<Self> {
         let mut crc = CRC_32K_4_2;
+        Self::read_and_calc_bytes(reader, &mut crc)
+    }
 
-        let spec_version = reader.read_le_and_calc_bytes(&mut crc)?;
-        let oao_id = OaoID::read_and_calc_bytes(reader, &mut crc)?;
-        let oao_version = reader.read_le_and_calc_bytes(&mut crc)?;
-
-        let num_of_bubs: u16 = reader.read_le_and_calc_bytes(&mut crc)?;
-        let frames = reader.read_le_and_calc_bytes(&mut crc)?;
-        let samples_per_sec = reader.read_le_and_calc_bytes(&mut crc)?;
-        let lpcm_kind = LpcmKind::read_and_calc_bytes(reader, &mut crc)?;
-        // Title
-        let title_size: u8 = reader.read_le_and_calc_bytes(&mut crc)?;
-        let title = reader.read_string_for_and_calc_bytes(title_size as usize, &mut crc)?;
-        // Artist
-        let artist_size: u8 = reader.read_le_and_calc_bytes(&mut crc)?;
-        let artist = reader.read_string_for_and_calc_bytes(artist_size as usize, &mut crc)?;
-
+    /// Read the metadata with its CRCs. `crc` is followed by the last CRC.
+    pub(crate) fn read_and_calc_bytes<R: std::io::Read>(
+        reader: &mut R,
+        crc: &mut CRC<u32>,
+    ) -> Result<Self> {
+        let (mut metadata, num_of_bubs) = Self::read_header_and_calc_bytes(reader, crc)?;
         // CRC
-        read_crc(reader, &mut crc)?;
+        read_crc(reader, crc)?;
+
+        // Tags
+        if metadata.spec_version >= TAGS_SPEC_VERSION {
+            metadata.tags = Tags::read_and_calc_bytes(reader, crc)?;
+            read_crc(reader, crc)?;
+        }
 
         // Bubbles
-        let mut bubs = Vec::new();
         for _ in 0..num_of_bubs {
-            // File Name
-            let file_name_size: u8 = reader.read_le_and_calc_bytes(&mut crc)?;
-            let file_name =
-                reader.read_string_for_and_calc_bytes(file_name_size as usize, &mut crc)?;
-            // Starting Frames
-            let mut starting_frames = VecDeque::new();
-            let num_of_starting_frames: u16 = reader.read_le_and_calc_bytes(&mut crc)?;
-            for _ in 0..num_of_starting_frames {
-                let starting_frame: u64 = reader.read_le_and_calc_bytes(&mut crc)?;
-                starting_frames.push_back(starting_frame);
-            }
-            bubs.push(BubInOao {
-                file_name,
-                starting_frames,
-            });
+            metadata.bubs.push(BubInOao::read_and_calc_bytes(
+                reader,
+                metadata.spec_version,
+                crc,
+            )?);
             // CRC
-            read_crc(reader, &mut crc)?;
+            read_crc(reader, crc)?;
         }
 
-        Ok(Self {
-            spec_version,
-            oao_id,
-            oao_version,
-            frames,
-            samples_per_sec,
-            lpcm_kind,
-            title,
-            artist,
-            bubs,
-        })
+        Ok(metadata)
+    }
+
+    /// Bytes after the metadata are left in the buffer of `reader`.
+    #[cfg(feature = "async")]
+    pub async fn read_async<R: futures_util::io::AsyncBufRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<Self> {
+        crate::io::async_ext::read_with(reader, |reader| Self::read(reader)).await
+    }
+
+    /// Read the file header without its CRC.
+    /// Returns the metadata without bubbles and the number of bubbles.
+    pub(crate) fn read_header_and_calc_bytes<R: std::io::Read>(
+        reader: &mut R,
+        crc: &mut CRC<u32>,
+    ) -> Result<(Self, u16)> {
+        let spec_version: u8 = reader.read_le_and_calc_bytes(crc)?;
+        let oao_id = OaoID::read_and_calc_bytes(reader, crc)?;
+        let oao_version = reader.read_le_and_calc_bytes(crc)?;
+
+        let num_of_bubs: u16 = reader.read_le_and_calc_bytes(crc)?;
+        let frames = reader.read_le_and_calc_bytes(crc)?;
+        let samples_per_sec = reader.read_le_and_calc_bytes(crc)?;
+        let lpcm_kind = LpcmKind::read_and_calc_bytes(reader, crc)?;
+        // Title
+        let title = read_sized_string_and_calc_bytes(reader, spec_version, crc)?;
+        // Artist
+        let artist = read_sized_string_and_calc_bytes(reader, spec_version, crc)?;
+
+        Ok((
+            Self {
+                spec_version,
+                oao_id,
+                oao_version,
+                frames,
+                samples_per_sec,
+                lpcm_kind,
+                title,
+                artist,
+                tags: Tags::new(),
+                bubs: Vec::with_capacity(num_of_bubs as usize),
+            },
+            num_of_bubs,
+        ))
+    }
+
+    /// Returns an error if the metadata can't be written, before anything is written.
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        check_spec_version(&self.tags, self.spec_version)?;
+        self.tags.check_writable()?;
+        u16_len("number of bubbles", self.bubs.len())?;
+        check_string_size("title", &self.title, self.spec_version)?;
+        check_string_size("artist", &self.artist, self.spec_version)?;
+        for bub in self.bubs.iter() {
+            bub.check_writable(self.spec_version)?;
+        }
+
+        Ok(())
     }
 
     pub fn write<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
         let mut crc = CRC_32K_4_2;
+        self.write_and_calc_bytes(writer, &mut crc)
+    }
 
-        writer.write_le_and_calc_bytes(self.spec_version, &mut crc)?;
-        self.oao_id.write_and_calc_bytes(writer, &mut crc)?;
-        writer.write_le_and_calc_bytes(self.oao_version, &mut crc)?;
+    /// Write the metadata with its CRCs. `crc` is followed by the last CRC.
+    pub(crate) fn write_and_calc_bytes<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        self.check_writable()?;
+        let num_of_bubs = u16_len("number of bubbles", self.bubs.len())?;
 
-        writer.write_le_and_calc_bytes(self.bubs.len() as u16, &mut crc)?;
-        writer.write_le_and_calc_bytes(self.frames, &mut crc)?;
-        writer.write_le_and_calc_bytes(self.samples_per_sec, &mut crc)?;
-        self.lpcm_kind.write_and_calc_bytes(writer, &mut crc)?;
+        writer.write_le_and_calc_bytes(self.spec_version, crc)?;
+        self.oao_id.write_and_calc_bytes(writer, crc)?;
+        writer.write_le_and_calc_bytes(self.oao_version, crc)?;
+
+        writer.write_le_and_calc_bytes(num_of_bubs, crc)?;
+        writer.write_le_and_calc_bytes(self.frames, crc)?;
+        writer.write_le_and_calc_bytes(self.samples_per_sec, crc)?;
+        self.lpcm_kind.write_and_calc_bytes(writer, crc)?;
         // Title
-        writer.write_le_and_calc_bytes(self.title.len() as u8, &mut crc)?;
-        writer.write_str_and_calc_bytes(&self.title, &mut crc)?;
+        write_sized_str_and_calc_bytes(writer, "title", &self.title, self.spec_version, crc)?;
         // Artist
-        writer.write_le_and_calc_bytes(self.artist.len() as u8, &mut crc)?;
-        writer.write_str_and_calc_bytes(&self.artist, &mut crc)?;
+        write_sized_str_and_calc_bytes(writer, "artist", &self.artist, self.spec_version, crc)?;
 
         // CRC
-        write_crc(writer, &mut crc)?;
+        write_crc(writer, crc)?;
+
+        // Tags
+        if self.spec_version >= TAGS_SPEC_VERSION {
+            self.tags.write_and_calc_bytes(writer, crc)?;
+            write_crc(writer, crc)?;
+        }
 
         // Bubbles
         for bub in self.bubs.iter() {
-            // Name
-            writer.write_le_and_calc_bytes(bub.file_name.len() as u8, &mut crc)?;
-            writer.write_str_and_calc_bytes(&bub.file_name, &mut crc)?;
-            // Starting Frames
-            writer.write_le_and_calc_bytes(bub.starting_frames.len() as u16, &mut crc)?;
-            for starting_frame in bub.starting_frames.iter() {
-                writer.write_le_and_calc_bytes(*starting_frame, &mut crc)?;
-            }
+            bub.write_and_calc_bytes(writer, self.spec_version, crc)?;
             // CRC
-            write_crc(writer, &mut crc)?;
+            write_crc(writer, crc)?;
         }
 
         Ok(())
     }
+
+    #[cfg(feature = "async")]
+    pub async fn write_async<W: futures_util::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<()> {
+        crate::io::async_ext::write_with(writer, |writer| self.write(writer)).await
+    }
 }
 
 impl Metadata for OaoMetadata {}
@@ -186,6 +429,8 @@ impl Metadata for OaoMetadata {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tags::TagKey;
+    use crate::utils::U16_STRING_SIZE_SPEC_VERSION;
 
     #[test]
     fn write_and_read() -> Result<()> {
@@ -197,18 +442,9 @@ mod tests {
             String::from("undefined"),
             Vec::new(),
         );
-        let bub0 = BubInOao {
-            file_name: "".into(),
-            starting_frames: vec![].into(),
-        };
-        let bub1 = BubInOao {
-            file_name: "a".into(),
-            starting_frames: vec![1].into(),
-        };
-        let bub2 = BubInOao {
-            file_name: "abc".into(),
-            starting_frames: vec![1, 2, 3].into(),
-        };
+        let bub0 = BubInOao::new("".into(), vec![].into());
+        let bub1 = BubInOao::new("a".into(), vec![1].into());
+        let bub2 = BubInOao::new("abc".into(), vec![1, 2, 3].into());
         let metadata_3_bubs = OaoMetadata::new(
             96000,
             96000.0,
@@ -217,7 +453,38 @@ mod tests {
             String::from("undefined"),
             vec![bub0, bub1, bub2],
         );
-        let metadatas = [metadata_0_bubs, metadata_3_bubs];
+        let mut metadata_tags = metadata_3_bubs.clone();
+        metadata_tags.spec_version = TAGS_SPEC_VERSION;
+        metadata_tags
+            .tags
+            .insert(TagKey::Copyright, String::from("(C) 2021"));
+        metadata_tags
+            .tags
+            .insert(TagKey::new("GENRE"), String::from("Ambient"));
+        let mut metadata_long_strings = metadata_tags.clone();
+        metadata_long_strings.spec_version = U16_STRING_SIZE_SPEC_VERSION;
+        metadata_long_strings.title = "t".repeat(300);
+        metadata_long_strings.artist = "a".repeat(u16::MAX as usize);
+        metadata_long_strings.bubs[0].file_name = "f".repeat(256);
+        let mut metadata_transforms = metadata_long_strings.clone();
+        metadata_transforms.spec_version = TRANSFORMS_SPEC_VERSION;
+        metadata_transforms.bubs[2].transforms = vec![
+            BubTransform::new(),
+            BubTransform {
+                position_offset: (1.0, -0.5, 0.25).into(),
+                gain: 0.5,
+                time_offset: 100,
+            },
+            BubTransform::new(),
+        ]
+        .into();
+        let metadatas = [
+            metadata_0_bubs,
+            metadata_3_bubs,
+            metadata_tags,
+            metadata_long_strings.clone(),
+            metadata_transforms.clone(),
+        ];
 
         for metadata in metadatas {
             let expected = metadata.clone();
@@ -226,8 +493,58 @@ mod tests {
             let val = OaoMetadata::read(&mut &v[..])?;
 
             assert_eq!(val, expected);
+
+            #[cfg(feature = "async")]
+            {
+                use futures_executor::block_on;
+
+                let mut w: Vec<u8> = Vec::new();
+                block_on(expected.write_async(&mut w))?;
+                assert_eq!(w, v);
+                // Bytes after the metadata must not be read.
+                w.push(0);
+                let mut reader = &w[..];
+                assert_eq!(block_on(OaoMetadata::read_async(&mut reader))?, expected);
+                assert_eq!(reader, [0]);
+            }
         }
 
+        // Too long
+        metadata_long_strings.spec_version = 1;
+        assert!(metadata_long_strings.write(&mut Vec::new()).is_err());
+        metadata_long_strings.spec_version = U16_STRING_SIZE_SPEC_VERSION;
+        metadata_long_strings.title = "t".repeat(u16::MAX as usize + 1);
+        // Nothing is written.
+        let mut v = Vec::new();
+        assert_eq!(
+            metadata_long_strings.write(&mut v).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(v.is_empty());
+        metadata_long_strings.title.clear();
+        metadata_long_strings.bubs[0].starting_frames = vec![1; u16::MAX as usize + 1].into();
+        assert_eq!(
+            metadata_long_strings.write(&mut v).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(v.is_empty());
+        metadata_long_strings.bubs =
+            vec![BubInOao::new(String::new(), VecDeque::new()); u16::MAX as usize + 1];
+        assert_eq!(
+            metadata_long_strings
+                .write(&mut Vec::new())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+
+        // Transforms
+        metadata_transforms.spec_version = U16_STRING_SIZE_SPEC_VERSION;
+        assert!(metadata_transforms.write(&mut Vec::new()).is_err());
+        metadata_transforms.spec_version = TRANSFORMS_SPEC_VERSION;
+        metadata_transforms.bubs[2].transforms.pop_back();
+        assert!(metadata_transforms.write(&mut Vec::new()).is_err());
+
         Ok(())
     }
 }
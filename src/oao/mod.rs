@@ -1,7 +1,17 @@
 pub use self::id::OaoID;
-pub use self::io::{OaoFrameReader, OaoFrameReaderKind, OaoReader, OaoWriter};
-pub use self::metadata::{BubInOao, OaoMetadata};
+#[cfg(feature = "parallel")]
+pub use self::io::ParallelOaoFrameReader;
+pub use self::io::{
+    BubBytes, BubSource, DirBubSource, MemoryBubSource, OaoBundleReader, OaoBundleWriter,
+    OaoFrameReader, OaoFrameReaderKind, OaoReader, OaoWriter, ZipBubSource, OAO_BUNDLE_EXTENSION,
+    OAO_BUNDLE_VERSION,
+};
+pub use self::metadata::{BubInOao, BubTransform, OaoMetadata, TRANSFORMS_SPEC_VERSION};
+pub use self::validate::{BubIssue, BubIssueKind, Severity, ValidationReport};
 
+mod dump;
 mod id;
 mod io;
 mod metadata;
+mod validate;
+mod verify;
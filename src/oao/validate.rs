@@ -0,0 +1,392 @@
+//! Consistency checks between a Floaout file and its bubbles
+
+use crate::bub::BubMetadata;
+use crate::oao::{BubSource, OaoMetadata};
+use crate::LpcmKind;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+
+/// Severity of an issue
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+    /// The Floaout file is played, but may not sound as intended.
+    Warning,
+    /// The Floaout file can't be played correctly.
+    Error,
+}
+
+/// Kind of an issue between a Floaout file and a bubble
+#[derive(Clone, Debug, PartialEq)]
+pub enum BubIssueKind {
+    /// Bubble can't be opened or its metadata can't be read.
+    CannotOpen { kind: ErrorKind, message: String },
+    /// `LpcmKind` of the bubble is not the same as the Floaout file.
+    LpcmKindMismatch { expected: LpcmKind, found: LpcmKind },
+    /// Samples per sec of the bubble is not the same as the Floaout file, so the bubble is resampled.
+    SamplesPerSecMismatch { expected: f64, found: f64 },
+    /// Number of transforms is neither 0 nor the number of starting frames.
+    TransformsMismatch {
+        starting_frames: usize,
+        transforms: usize,
+    },
+    /// Starting frames are not in ascending order, so the later ones are never played.
+    UnsortedStartingFrames,
+    /// Starting frame is out of `frames` of the Floaout file, so it is never played.
+    OutOfFrames { starting_frame: u64, frames: u64 },
+    /// Bubble ends after `frames` of the Floaout file, so it is cut.
+    ExceedsFrames { end_frame: u64, frames: u64 },
+}
+
+impl BubIssueKind {
+    pub const fn severity(&self) -> Severity {
+        match self {
+            Self::SamplesPerSecMismatch { .. }
+            | Self::OutOfFrames { .. }
+            | Self::ExceedsFrames { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// Issue of a bubble in the Floaout file
+#[derive(Clone, Debug, PartialEq)]
+pub struct BubIssue {
+    pub file_name: String,
+    /// Starting frame of the instance, if any
+    pub starting_frame: Option<u64>,
+    pub kind: BubIssueKind,
+}
+
+impl BubIssue {
+    pub const fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl fmt::Display for BubIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bubble `{}`", self.file_name)?;
+        if let Some(starting_frame) = self.starting_frame {
+            write!(f, " at frame {}", starting_frame)?;
+        }
+        match &self.kind {
+            BubIssueKind::CannotOpen { message, .. } => write!(f, " can't be opened: {}", message),
+            BubIssueKind::LpcmKindMismatch { expected, found } => write!(
+                f,
+                " is `{:?}`, but the Floaout file is `{:?}`",
+                found, expected
+            ),
+            BubIssueKind::SamplesPerSecMismatch { expected, found } => {
+                write!(f, " is {} Hz, and resampled to {} Hz", found, expected)
+            }
+            BubIssueKind::TransformsMismatch {
+                starting_frames,
+                transforms,
+            } => write!(
+                f,
+                " has {} starting frames, but {} transforms",
+                starting_frames, transforms
+            ),
+            BubIssueKind::UnsortedStartingFrames => {
+                write!(f, " has starting frames which are not sorted")
+            }
+            BubIssueKind::OutOfFrames { frames, .. } => write!(f, " is out of {} frames", frames),
+            BubIssueKind::ExceedsFrames { end_frame, frames } => {
+                write!(f, " ends at frame {}, after {} frames", end_frame, frames)
+            }
+        }
+    }
+}
+
+/// Report of consistency checks
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<BubIssue>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if there are no errors. There may be warnings.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &BubIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &BubIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Warning)
+    }
+
+    /// Returns an error with the first error, or the report with warnings.
+    /// The error keeps the `ErrorKind` of a bubble which can't be opened.
+    pub fn into_result(self) -> Result<Self> {
+        if let Some(issue) = self.errors().next() {
+            let kind = match issue.kind {
+                BubIssueKind::CannotOpen { kind, .. } => kind,
+                _ => ErrorKind::InvalidData,
+            };
+            return Err(Error::new(kind, issue.to_string()));
+        }
+
+        Ok(self)
+    }
+
+    fn push(&mut self, file_name: &str, starting_frame: Option<u64>, kind: BubIssueKind) {
+        self.issues.push(BubIssue {
+            file_name: file_name.into(),
+            starting_frame,
+            kind,
+        });
+    }
+}
+
+/// Check `metadata.bubs` and their bubbles opened from `bub_source`.
+pub(crate) fn validate_bubs<B: BubSource>(
+    metadata: &OaoMetadata,
+    bub_source: &mut B,
+) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    for bub in metadata.bubs.iter() {
+        let file_name = bub.file_name.as_str();
+        if !bub.transforms.is_empty() && bub.transforms.len() != bub.starting_frames.len() {
+            report.push(
+                file_name,
+                None,
+                BubIssueKind::TransformsMismatch {
+                    starting_frames: bub.starting_frames.len(),
+                    transforms: bub.transforms.len(),
+                },
+            );
+        }
+        if bub
+            .starting_frames
+            .iter()
+            .zip(bub.starting_frames.iter().skip(1))
+            .any(|(frame, next_frame)| next_frame < frame)
+        {
+            report.push(file_name, None, BubIssueKind::UnsortedStartingFrames);
+        }
+
+        let bub_metadata = match bub_source
+            .open_bub(file_name)
+            .and_then(|mut reader| BubMetadata::read(&mut reader))
+        {
+            Ok((bub_metadata, _)) => bub_metadata,
+            Err(e) => {
+                report.push(
+                    file_name,
+                    None,
+                    BubIssueKind::CannotOpen {
+                        kind: e.kind(),
+                        message: e.to_string(),
+                    },
+                );
+                continue;
+            }
+        };
+        if bub_metadata.lpcm_kind != metadata.lpcm_kind {
+            report.push(
+                file_name,
+                None,
+                BubIssueKind::LpcmKindMismatch {
+                    expected: metadata.lpcm_kind,
+                    found: bub_metadata.lpcm_kind,
+                },
+            );
+        }
+        if bub_metadata.samples_per_sec != metadata.samples_per_sec {
+            report.push(
+                file_name,
+                None,
+                BubIssueKind::SamplesPerSecMismatch {
+                    expected: metadata.samples_per_sec,
+                    found: bub_metadata.samples_per_sec,
+                },
+            );
+        }
+
+        for (i, &starting_frame) in bub.starting_frames.iter().enumerate() {
+            if starting_frame == 0 || metadata.frames < starting_frame {
+                report.push(
+                    file_name,
+                    Some(starting_frame),
+                    BubIssueKind::OutOfFrames {
+                        starting_frame,
+                        frames: metadata.frames,
+                    },
+                );
+                continue;
+            }
+            // Frames of the instance at the samples per sec of the Floaout file
+            let time_offset = bub
+                .transforms
+                .get(i)
+                .map_or(0, |transform| transform.time_offset);
+            let frames = if bub_metadata.samples_per_sec == metadata.samples_per_sec {
+                bub_metadata.frames.saturating_sub(time_offset)
+            } else {
+                // Same as `ResampledBubFrameReader`
+                let ratio = bub_metadata.samples_per_sec / metadata.samples_per_sec;
+                let output_frames = |frames: u64| (frames as f64 / ratio).ceil() as u64;
+                output_frames(bub_metadata.frames).saturating_sub(output_frames(time_offset))
+            };
+            let end_frame = (starting_frame + frames).saturating_sub(1);
+            if metadata.frames < end_frame {
+                report.push(
+                    file_name,
+                    Some(starting_frame),
+                    BubIssueKind::ExceedsFrames {
+                        end_frame,
+                        frames: metadata.frames,
+                    },
+                );
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bub::{BubSampleKind, BubWriter};
+    use crate::oao::{BubInOao, BubTransform, MemoryBubSource};
+
+    fn bub(frames: u64, samples_per_sec: f64, lpcm_kind: LpcmKind) -> Result<Vec<u8>> {
+        let metadata = BubMetadata::new(
+            frames,
+            1,
+            samples_per_sec,
+            lpcm_kind,
+            BubSampleKind::Lpcm,
+            String::from("Bubble"),
+        );
+        Ok(BubWriter::new(Vec::new(), metadata)?.inner)
+    }
+
+    #[test]
+    fn validate_bubs() -> Result<()> {
+        let mut bub_source = MemoryBubSource::new();
+        bub_source.insert("a".into(), bub(4, 48000.0, LpcmKind::F32LE)?);
+        bub_source.insert("b".into(), bub(4, 96000.0, LpcmKind::I16LE)?);
+
+        let mut transformed = BubInOao::new("a".into(), vec![7, 8].into());
+        transformed.transforms = vec![
+            BubTransform {
+                time_offset: 1,
+                ..BubTransform::new()
+            },
+            BubTransform::new(),
+        ]
+        .into();
+        let mut mismatched = BubInOao::new("a".into(), vec![1].into());
+        mismatched.transforms = vec![BubTransform::new(); 2].into();
+        let metadata = OaoMetadata::new(
+            10,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("Validate"),
+            String::from("Artist"),
+            vec![
+                BubInOao::new("a".into(), vec![1, 11, 0].into()),
+                transformed,
+                mismatched,
+                BubInOao::new("b".into(), vec![9].into()),
+                BubInOao::new("c".into(), vec![1].into()),
+            ],
+        );
+
+        let report = metadata.validate(&mut bub_source);
+        let kinds: Vec<_> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.file_name.as_str(), issue.starting_frame, &issue.kind))
+            .collect();
+        // `b` at 9 fits, since 4 frames at 96 kHz are 2 frames at 48 kHz.
+        assert_eq!(
+            kinds[..7],
+            [
+                ("a", None, &BubIssueKind::UnsortedStartingFrames),
+                (
+                    "a",
+                    Some(11),
+                    &BubIssueKind::OutOfFrames {
+                        starting_frame: 11,
+                        frames: 10
+                    }
+                ),
+                (
+                    "a",
+                    Some(0),
+                    &BubIssueKind::OutOfFrames {
+                        starting_frame: 0,
+                        frames: 10
+                    }
+                ),
+                // 7 with the time offset fits, but 8 doesn't.
+                (
+                    "a",
+                    Some(8),
+                    &BubIssueKind::ExceedsFrames {
+                        end_frame: 11,
+                        frames: 10
+                    }
+                ),
+                (
+                    "a",
+                    None,
+                    &BubIssueKind::TransformsMismatch {
+                        starting_frames: 1,
+                        transforms: 2
+                    }
+                ),
+                (
+                    "b",
+                    None,
+                    &BubIssueKind::LpcmKindMismatch {
+                        expected: LpcmKind::F32LE,
+                        found: LpcmKind::I16LE
+                    }
+                ),
+                (
+                    "b",
+                    None,
+                    &BubIssueKind::SamplesPerSecMismatch {
+                        expected: 48000.0,
+                        found: 96000.0
+                    }
+                ),
+            ]
+        );
+        assert!(matches!(
+            kinds[7],
+            (
+                "c",
+                None,
+                BubIssueKind::CannotOpen {
+                    kind: ErrorKind::NotFound,
+                    ..
+                }
+            )
+        ));
+        assert_eq!(kinds.len(), 8);
+
+        assert!(!report.is_valid());
+        assert_eq!(report.warnings().count(), 4);
+        let e = report.into_result().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            e.to_string(),
+            "bubble `a` has starting frames which are not sorted"
+        );
+
+        Ok(())
+    }
+}
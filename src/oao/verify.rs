@@ -0,0 +1,127 @@
+use crate::oao::{BubInOao, OaoMetadata};
+use crate::tags::{Tags, TAGS_SPEC_VERSION};
+use crate::verify::{OffsetReader, ProblemKind, VerifyReport};
+use crate::CRC_32K_4_2;
+use std::io::{Read, Result};
+
+/// Walk all bubbles. Returns an error if the rest can't be read.
+fn verify_bubs<R: Read>(reader: &mut OffsetReader<R>, report: &mut VerifyReport) -> Result<()> {
+    let mut crc = CRC_32K_4_2;
+    let (metadata, num_of_bubs) = OaoMetadata::read_header_and_calc_bytes(reader, &mut crc)?;
+    report.check_crc(reader, &mut crc, None)?;
+    if metadata.spec_version >= TAGS_SPEC_VERSION {
+        Tags::read_and_calc_bytes(reader, &mut crc)?;
+        report.check_crc(reader, &mut crc, None)?;
+    }
+
+    for _ in 0..num_of_bubs {
+        let bub_offset = reader.offset;
+        let bub = BubInOao::read_and_calc_bytes(reader, metadata.spec_version, &mut crc)?;
+        if let Some(&frame) = bub
+            .starting_frames
+            .iter()
+            .find(|&&frame| frame == 0 || metadata.frames < frame)
+        {
+            report.push(
+                Some(frame),
+                bub_offset,
+                ProblemKind::OutOfFrames {
+                    frame,
+                    frames: metadata.frames,
+                },
+            );
+        }
+        if bub
+            .starting_frames
+            .iter()
+            .zip(bub.starting_frames.iter().skip(1))
+            .any(|(frame, next_frame)| next_frame < frame)
+        {
+            report.push(None, bub_offset, ProblemKind::UnsortedStartingFrames);
+        }
+        report.check_crc(reader, &mut crc, None)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn verify<R: Read>(inner: R) -> Result<VerifyReport> {
+    let mut reader = OffsetReader::new(inner);
+    let mut report = VerifyReport::default();
+
+    match verify_bubs(&mut reader, &mut report) {
+        Ok(()) => {
+            let byte_offset = reader.offset;
+            let trailing_bytes = reader.trailing_bytes()?;
+            if trailing_bytes != 0 {
+                report.push(
+                    None,
+                    byte_offset,
+                    ProblemKind::TrailingBytes(trailing_bytes),
+                );
+            }
+        }
+        Err(e) => report.push_error(None, reader.offset, e)?,
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oao::OaoReader;
+    use crate::verify::Problem;
+    use crate::LpcmKind;
+
+    #[test]
+    fn verify() -> Result<()> {
+        let bubs = vec![
+            BubInOao::new("a".into(), vec![1, 5].into()),
+            BubInOao::new("b".into(), vec![3, 2, 11].into()),
+        ];
+        let metadata = OaoMetadata::new(
+            10,
+            48000.0,
+            LpcmKind::F32LE,
+            String::from("untitled"),
+            String::from("undefined"),
+            bubs,
+        );
+        let mut v = Vec::new();
+        metadata.write(&mut v)?;
+        let bub_offset = (v.len() - (1 + 1 + 2 + 8 * 3 + 4)) as u64;
+
+        assert_eq!(
+            OaoReader::verify(v.as_slice())?.problems,
+            [
+                Problem {
+                    frame: Some(11),
+                    byte_offset: bub_offset,
+                    kind: ProblemKind::OutOfFrames {
+                        frame: 11,
+                        frames: 10
+                    },
+                },
+                Problem {
+                    frame: None,
+                    byte_offset: bub_offset,
+                    kind: ProblemKind::UnsortedStartingFrames,
+                },
+            ]
+        );
+
+        let last = v.len() - 5;
+        v[last] ^= 1;
+        assert_eq!(
+            OaoReader::verify(v.as_slice())?.problems[2],
+            Problem {
+                frame: None,
+                byte_offset: last as u64 + 1,
+                kind: ProblemKind::CrcMismatch,
+            }
+        );
+
+        Ok(())
+    }
+}
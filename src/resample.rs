@@ -0,0 +1,248 @@
+//! Sample-rate conversion with windowed sinc interpolation
+
+use crate::{Frame, FrameReader, Sample};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+use std::io::{Read, Result};
+use std::marker::PhantomData;
+
+/// Quality of resampling. Higher quality uses a longer filter.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ResampleQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ResampleQuality {
+    /// Number of zero crossings of the sinc on each side
+    const fn zero_crossings(self) -> f64 {
+        match self {
+            Self::Low => 8.0,
+            Self::Medium => 16.0,
+            Self::High => 32.0,
+        }
+    }
+
+    /// Cutoff frequency relative to the lower Nyquist frequency
+    const fn rolloff(self) -> f64 {
+        match self {
+            Self::Low => 0.85,
+            Self::Medium => 0.9,
+            Self::High => 0.95,
+        }
+    }
+}
+
+/// Blackman-windowed sinc filter
+#[derive(Clone, Debug)]
+pub(crate) struct SincKernel {
+    /// Input frames per output frame
+    pub(crate) ratio: f64,
+    cutoff: f64,
+    /// Half width of the filter in input frames
+    half_width: f64,
+}
+
+impl SincKernel {
+    pub(crate) fn new(
+        input_samples_per_sec: f64,
+        output_samples_per_sec: f64,
+        quality: ResampleQuality,
+    ) -> Self {
+        let ratio = input_samples_per_sec / output_samples_per_sec;
+        let cutoff = quality.rolloff() * ratio.recip().min(1.0);
+
+        Self {
+            ratio,
+            cutoff,
+            half_width: quality.zero_crossings() / cutoff,
+        }
+    }
+
+    pub(crate) fn output_frames(&self, input_frames: u64) -> u64 {
+        (input_frames as f64 / self.ratio).ceil() as u64
+    }
+
+    /// Input position of the 0-based output `index`
+    pub(crate) fn position(&self, index: u64) -> f64 {
+        index as f64 * self.ratio
+    }
+
+    /// First and last 0-based input indices used at `position`
+    pub(crate) fn range(&self, position: f64) -> (i64, i64) {
+        (
+            (position - self.half_width).ceil() as i64,
+            (position + self.half_width).floor() as i64,
+        )
+    }
+
+    fn weight(&self, distance: f64) -> f64 {
+        let x = distance / self.half_width;
+        if 1.0 <= x.abs() {
+            return 0.0;
+        }
+        let window = 0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos();
+        let t = PI * self.cutoff * distance;
+        let sinc = if t == 0.0 { 1.0 } else { t.sin() / t };
+
+        self.cutoff * sinc * window
+    }
+
+    /// Interpolate at `position`. `input` returns the input at a 0-based index.
+    pub(crate) fn interpolate<F: FnMut(i64) -> f64>(&self, position: f64, mut input: F) -> f64 {
+        let (first, last) = self.range(position);
+        (first..=last)
+            .map(|i| input(i) * self.weight(i as f64 - position))
+            .sum()
+    }
+}
+
+/// `FrameReader` adapter which converts the samples per sec of `inner`.
+pub struct Resampler<F: FrameReader<R, S>, R: Read, S: Sample> {
+    pub inner: F,
+    pub pos: u64,
+    samples_per_sec: f64,
+    kernel: SincKernel,
+    /// Input frames from the 0-based index `start`
+    buf: VecDeque<Frame<S>>,
+    start: i64,
+    _phantom_reader: PhantomData<R>,
+}
+
+impl<F: FrameReader<R, S>, R: Read, S: Sample> FrameReader<R, S> for Resampler<F, R, S> {
+    fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+    fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+    fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+
+    fn frames(&self) -> u64 {
+        self.kernel.output_frames(self.inner.frames())
+    }
+
+    fn samples_per_sec(&self) -> f64 {
+        self.samples_per_sec
+    }
+
+    fn number_of_channels(&self) -> u32 {
+        self.inner.number_of_channels()
+    }
+}
+
+impl<F: FrameReader<R, S>, R: Read, S: Sample> Resampler<F, R, S> {
+    pub fn new(inner: F, samples_per_sec: f64, quality: ResampleQuality) -> Self {
+        let kernel = SincKernel::new(inner.samples_per_sec(), samples_per_sec, quality);
+
+        Self {
+            inner,
+            pos: 0,
+            samples_per_sec,
+            kernel,
+            buf: VecDeque::new(),
+            start: 0,
+            _phantom_reader: PhantomData,
+        }
+    }
+}
+
+impl<F: FrameReader<R, S>, R: Read, S: Sample> Iterator for Resampler<F, R, S> {
+    type Item = Result<Frame<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frames() <= self.pos {
+            return None;
+        }
+        let position = self.kernel.position(self.pos);
+        self.pos += 1;
+
+        let (first, last) = self.kernel.range(position);
+        while self.start + (self.buf.len() as i64) <= last {
+            match self.inner.next() {
+                Some(Ok(frame)) => self.buf.push_back(frame),
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+        while self.start < first && !self.buf.is_empty() {
+            self.buf.pop_front();
+            self.start += 1;
+        }
+
+        let channels = self.inner.number_of_channels() as usize;
+        let (buf, start) = (&self.buf, self.start);
+        let frame = (0..channels)
+            .map(|channel| {
+                S::from_f64(self.kernel.interpolate(position, |i| {
+                    match usize::try_from(i - start).ok().and_then(|i| buf.get(i)) {
+                        Some(frame) => frame.0[channel].to_f64(),
+                        None => 0.0,
+                    }
+                }))
+            })
+            .collect::<Vec<S>>();
+
+        Some(Ok(frame.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav::{WavFrameReader, WavMetadata};
+    use crate::LpcmKind;
+
+    fn sine(n: u64, samples_per_sec: f64) -> f64 {
+        (2.0 * PI * 1000.0 * n as f64 / samples_per_sec).sin() * 0.5
+    }
+
+    #[test]
+    fn resample_sine() -> Result<()> {
+        let frames = 4800;
+        let data: Vec<u8> = (0..frames)
+            .flat_map(|n| sine(n, 48000.0).to_le_bytes())
+            .collect();
+        let metadata = WavMetadata::new(frames, LpcmKind::F64LE, 1, 48000.0, Vec::new());
+
+        for (samples_per_sec, quality, max_error) in [
+            (96000.0, ResampleQuality::Low, 1e-2),
+            (96000.0, ResampleQuality::High, 1e-3),
+            (44100.0, ResampleQuality::Medium, 1e-3),
+            (16000.0, ResampleQuality::Medium, 1e-3),
+        ] {
+            let wav_frame_reader = WavFrameReader::<_, f64>::new(&data[..], metadata.clone());
+            let resampler = Resampler::new(wav_frame_reader, samples_per_sec, quality);
+            assert_eq!(resampler.samples_per_sec(), samples_per_sec);
+            let expected_frames = (frames as f64 * samples_per_sec / 48000.0).ceil() as u64;
+            assert_eq!(resampler.frames(), expected_frames);
+
+            let resampled = resampler.collect::<Result<Vec<_>>>()?;
+            assert_eq!(resampled.len() as u64, expected_frames);
+            // Skip the edges, where the input is padded with zeros.
+            let edge = expected_frames / 10;
+            for (n, frame) in resampled
+                .iter()
+                .enumerate()
+                .take((expected_frames - edge) as usize)
+                .skip(edge as usize)
+            {
+                let error = (frame.0[0] - sine(n as u64, samples_per_sec)).abs();
+                assert!(
+                    error < max_error,
+                    "{} Hz, frame {}: {}",
+                    samples_per_sec,
+                    n,
+                    error
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::Rgba;
+use alloc::vec::Vec;
 
 // TODO: Add feilds like frame_span, vertex_spacing, colors, and so on.
 // f32 -> f64 in the future
@@ -0,0 +1,287 @@
+//! Tags in the metadata, like Vorbis comments.
+
+use crate::bub::read_payload_and_calc_bytes;
+use crate::io::{Error, ErrorKind, Read, ReadExt, Result, Write, WriteExt};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use mycrc::CRC;
+
+/// Spec version from which the metadata has tags
+pub const TAGS_SPEC_VERSION: u8 = 1;
+/// Version of the tags section this crate writes
+const TAGS_VERSION: u8 = 0;
+
+/// Key of a tag
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum TagKey {
+    Composer,
+    Copyright,
+    License,
+    /// International Standard Recording Code
+    Isrc,
+    /// Creation date such as `2021-09-01`
+    CreationDate,
+    /// Software which made the file
+    Software,
+    /// Free-form key. Use `TagKey::new` to make it.
+    Other(OtherTagKey),
+}
+
+/// Free-form key which is none of the named `TagKey`s
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct OtherTagKey(String);
+
+impl OtherTagKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TagKey {
+    /// Returns the named key if `key` is one of them, so keys don't collide on disk.
+    pub fn new(key: &str) -> Self {
+        match key {
+            "COMPOSER" => Self::Composer,
+            "COPYRIGHT" => Self::Copyright,
+            "LICENSE" => Self::License,
+            "ISRC" => Self::Isrc,
+            "DATE" => Self::CreationDate,
+            "SOFTWARE" => Self::Software,
+            _ => Self::Other(OtherTagKey(key.into())),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Composer => "COMPOSER",
+            Self::Copyright => "COPYRIGHT",
+            Self::License => "LICENSE",
+            Self::Isrc => "ISRC",
+            Self::CreationDate => "DATE",
+            Self::Software => "SOFTWARE",
+            Self::Other(key) => key.as_str(),
+        }
+    }
+}
+
+impl From<&str> for TagKey {
+    fn from(key: &str) -> Self {
+        Self::new(key)
+    }
+}
+
+/// Tags
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Tags {
+    map: BTreeMap<TagKey, String>,
+    /// Version and bytes of a tags section of an unknown version
+    unknown: Option<(u8, Vec<u8>)>,
+}
+
+impl Tags {
+    pub const fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            unknown: None,
+        }
+    }
+
+    pub fn get(&self, key: &TagKey) -> Option<&str> {
+        self.map.get(key).map(String::as_str)
+    }
+
+    /// Returns the old value if any.
+    pub fn insert(&mut self, key: TagKey, value: String) -> Option<String> {
+        self.map.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &TagKey) -> Option<String> {
+        self.map.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&TagKey, &str)> {
+        self.map.iter().map(|(key, value)| (key, value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Whether a tags section of an unknown version is kept as is, which isn't counted in `len`.
+    pub fn has_unknown(&self) -> bool {
+        self.unknown.is_some()
+    }
+
+    /// Discard the tags section of an unknown version to write new tags instead.
+    pub fn discard_unknown(&mut self) {
+        self.unknown = None;
+    }
+
+    // IO
+    /// Read the tags section without its CRC.
+    /// Tags of an unknown version are kept as bytes and written back as is.
+    pub(crate) fn read_and_calc_bytes<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<Self> {
+        let tags_version: u8 = reader.read_le_and_calc_bytes(crc)?;
+        let tags_size: u32 = reader.read_le_and_calc_bytes(crc)?;
+        let bytes = read_payload_and_calc_bytes(reader, tags_size as u64, crc)?;
+
+        let mut tags = Self::new();
+        if tags_version != TAGS_VERSION {
+            tags.unknown = Some((tags_version, bytes));
+            return Ok(tags);
+        }
+
+        let mut bytes = &bytes[..];
+        while !bytes.is_empty() {
+            let key_size: u8 = bytes.read_le()?;
+            let key = bytes.read_string_for(key_size as usize)?;
+            let value_size: u32 = bytes.read_le()?;
+            let value = bytes.read_string_for(value_size as usize)?;
+            tags.insert(TagKey::new(&key), value);
+        }
+
+        Ok(tags)
+    }
+
+    /// Returns an error if the tags can't be written, before anything is written.
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        if let Some((tags_version, _)) = &self.unknown {
+            if !self.map.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "tags can't be added to tags of unknown version {}",
+                        tags_version
+                    ),
+                ));
+            }
+        }
+
+        let mut tags_size = 0;
+        for (key, value) in self.iter() {
+            let key = key.as_str();
+            if key.len() > u8::MAX as usize || value.len() > u32::MAX as usize {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("tag `{}` is too long", key),
+                ));
+            }
+            tags_size += 1 + key.len() as u64 + 4 + value.len() as u64;
+        }
+        if tags_size > u32::MAX as u64 {
+            return Err(Error::new(ErrorKind::InvalidInput, "tags are too long"));
+        }
+
+        Ok(())
+    }
+
+    /// Write the tags section without its CRC.
+    pub(crate) fn write_and_calc_bytes<W: Write>(
+        &self,
+        writer: &mut W,
+        crc: &mut CRC<u32>,
+    ) -> Result<()> {
+        self.check_writable()?;
+
+        let mut known_bytes = Vec::new();
+        let (tags_version, bytes) = match &self.unknown {
+            Some((tags_version, bytes)) => (*tags_version, bytes),
+            None => {
+                for (key, value) in self.iter() {
+                    let key = key.as_str();
+                    known_bytes.write_le(key.len() as u8)?;
+                    known_bytes.write_str(key)?;
+                    known_bytes.write_le(value.len() as u32)?;
+                    known_bytes.write_str(value)?;
+                }
+                (TAGS_VERSION, &known_bytes)
+            }
+        };
+
+        writer.write_le_and_calc_bytes(tags_version, crc)?;
+        writer.write_le_and_calc_bytes(bytes.len() as u32, crc)?;
+        writer.write_all(bytes)?;
+        crc.calc_bytes(bytes);
+
+        Ok(())
+    }
+}
+
+/// Returns an error if the tags can't be written in `spec_version`.
+pub(crate) fn check_spec_version(tags: &Tags, spec_version: u8) -> Result<()> {
+    if spec_version < TAGS_SPEC_VERSION && (!tags.is_empty() || tags.has_unknown()) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "tags need spec version {} or later, found {}",
+                TAGS_SPEC_VERSION, spec_version
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CRC_32K_4_2;
+
+    #[test]
+    fn write_and_read() -> Result<()> {
+        let mut tags = Tags::new();
+        tags.insert(TagKey::Composer, "Composer".into());
+        tags.insert(TagKey::Isrc, "JPXX02100001".into());
+        tags.insert("DATE".into(), "2021-09-01".into());
+        tags.insert(TagKey::new("MOOD"), "😀".into());
+        assert_eq!(tags.get(&TagKey::CreationDate), Some("2021-09-01"));
+        assert_eq!(TagKey::new("COMPOSER"), TagKey::Composer);
+        assert_eq!(TagKey::new("MOOD").as_str(), "MOOD");
+
+        let mut crc = CRC_32K_4_2;
+        let mut v = Vec::new();
+        tags.write_and_calc_bytes(&mut v, &mut crc)?;
+        assert_eq!(Tags::read_and_calc_bytes(&mut &v[..], &mut crc)?, tags);
+
+        // Unknown version
+        v[0] = TAGS_VERSION + 1;
+        let mut unknown = Tags::read_and_calc_bytes(&mut &v[..], &mut crc)?;
+        assert!(unknown.is_empty());
+        assert!(unknown.has_unknown());
+        let mut unknown_v = Vec::new();
+        unknown.write_and_calc_bytes(&mut unknown_v, &mut crc)?;
+        assert_eq!(unknown_v, v);
+        assert!(check_spec_version(&unknown, 0).is_err());
+
+        unknown.insert(TagKey::Composer, "Composer".into());
+        assert!(unknown
+            .write_and_calc_bytes(&mut Vec::new(), &mut crc)
+            .is_err());
+        unknown.discard_unknown();
+        unknown_v.clear();
+        unknown.write_and_calc_bytes(&mut unknown_v, &mut crc)?;
+        assert_eq!(unknown_v[0], TAGS_VERSION);
+
+        assert!(check_spec_version(&tags, 0).is_err());
+        assert!(check_spec_version(&Tags::new(), 0).is_ok());
+
+        // Tags size larger than the input
+        let mut v = vec![TAGS_VERSION];
+        v.extend_from_slice(&u32::MAX.to_le_bytes());
+        v.extend_from_slice(&[0; 8]);
+        assert_eq!(
+            Tags::read_and_calc_bytes(&mut &v[..], &mut crc)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::UnexpectedEof
+        );
+
+        Ok(())
+    }
+}
@@ -1,6 +1,106 @@
+use crate::io::{Error, ErrorKind, Read, ReadExt, Result, Write, WriteExt};
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+use core::fmt::Debug;
 use mycrc::CRC;
-use std::fmt::Debug;
-use std::io::{Error, ErrorKind, Read, Result, Write};
+
+/// Spec version from which sizes of names, titles, artists and file names are `u16`
+pub const U16_STRING_SIZE_SPEC_VERSION: u8 = 2;
+
+/// Returns the longest prefix of `s` within `max_len` bytes, which ends on a char boundary.
+pub fn truncate_str(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut len = max_len;
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    &s[..len]
+}
+
+/// Truncate `s` to `max_len` bytes on a char boundary.
+pub fn truncate_string(s: &mut String, max_len: usize) {
+    let len = truncate_str(s, max_len).len();
+    s.truncate(len);
+}
+
+/// Max size of a name, a title, an artist or a file name in `spec_version`
+pub const fn max_string_size(spec_version: u8) -> usize {
+    if spec_version >= U16_STRING_SIZE_SPEC_VERSION {
+        u16::MAX as usize
+    } else {
+        u8::MAX as usize
+    }
+}
+
+pub(crate) fn read_sized_string_and_calc_bytes<R: Read>(
+    reader: &mut R,
+    spec_version: u8,
+    crc: &mut CRC<u32>,
+) -> Result<String> {
+    let size = if spec_version >= U16_STRING_SIZE_SPEC_VERSION {
+        reader.read_le_and_calc_bytes::<u16>(crc)? as usize
+    } else {
+        reader.read_le_and_calc_bytes::<u8>(crc)? as usize
+    };
+    reader.read_string_for_and_calc_bytes(size, crc)
+}
+
+/// Returns an error if `s` is too long for `spec_version`.
+pub(crate) fn check_string_size(field_name: &str, s: &str, spec_version: u8) -> Result<()> {
+    let max_size = max_string_size(spec_version);
+    if s.len() > max_size {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{} is {} bytes, but must be less than or equal to {} bytes in spec version {}",
+                field_name,
+                s.len(),
+                max_size,
+                spec_version
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Write the size and `s`. Returns an error if `s` is too long for `spec_version`.
+pub(crate) fn write_sized_str_and_calc_bytes<W: Write>(
+    writer: &mut W,
+    field_name: &str,
+    s: &str,
+    spec_version: u8,
+    crc: &mut CRC<u32>,
+) -> Result<()> {
+    check_string_size(field_name, s, spec_version)?;
+
+    if spec_version >= U16_STRING_SIZE_SPEC_VERSION {
+        writer.write_le_and_calc_bytes(s.len() as u16, crc)?;
+    } else {
+        writer.write_le_and_calc_bytes(s.len() as u8, crc)?;
+    }
+    writer.write_str_and_calc_bytes(s, crc)
+}
+
+/// Returns `len` as `u16`, or an error if `field_name` is too large to be written.
+#[cfg(feature = "std")]
+pub(crate) fn u16_len(field_name: &str, len: usize) -> Result<u16> {
+    u16::try_from(len).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{} is {}, but must be less than or equal to {}",
+                field_name,
+                len,
+                u16::MAX
+            ),
+        )
+    })
+}
 
 pub(crate) fn expected_and_found_error<T: Debug>(
     error_kind: ErrorKind,
@@ -13,6 +113,7 @@ pub(crate) fn expected_and_found_error<T: Debug>(
     )
 }
 
+#[cfg(feature = "std")]
 pub(crate) fn is_equal<T: Debug + Eq>(error_kind: ErrorKind, expected: T, found: T) -> Result<()> {
     if found != expected {
         return Err(expected_and_found_error(error_kind, expected, found));
@@ -20,21 +121,31 @@ pub(crate) fn is_equal<T: Debug + Eq>(error_kind: ErrorKind, expected: T, found:
     Ok(())
 }
 
+#[cfg(feature = "std")]
 pub(crate) fn return_invalid_data_if_not_equal<T: Debug + Eq>(val: T, expect: T) -> Result<()> {
     is_equal(ErrorKind::InvalidData, expect, val)
 }
 
-pub(crate) fn read_crc<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<()> {
+/// Returns whether the checksum is correct.
+pub(crate) fn check_crc<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<bool> {
     let mut buf = [0; 4];
     reader.read_exact(&mut buf)?;
     crc.calc_bytes(&buf);
-    // TODO: Return Error
-    assert!(crc.is_error_free());
+    let is_error_free = crc.is_error_free();
 
     crc.initialize().calc_bytes(&buf);
 
-    Ok(())
+    Ok(is_error_free)
 }
+
+pub(crate) fn read_crc<R: Read>(reader: &mut R, crc: &mut CRC<u32>) -> Result<()> {
+    if check_crc(reader, crc)? {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::InvalidData, "CRC mismatch"))
+    }
+}
+
 pub(crate) fn write_crc<W: Write>(writer: &mut W, crc: &mut CRC<u32>) -> Result<()> {
     let checksum_bytes = crc.finalize_to_endian_bytes();
     writer.write_all(&checksum_bytes)?;
@@ -43,3 +154,21 @@ pub(crate) fn write_crc<W: Write>(writer: &mut W, crc: &mut CRC<u32>) -> Result<
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate() {
+        assert_eq!(truncate_str("abc", 5), "abc");
+        assert_eq!(truncate_str("abc", 2), "ab");
+        // "あ" is 3 bytes.
+        assert_eq!(truncate_str("aあい", 5), "aあ");
+        assert_eq!(truncate_str("aあい", 3), "a");
+
+        let mut s = "😀".repeat(100);
+        truncate_string(&mut s, max_string_size(0));
+        assert_eq!(s.len(), 252);
+    }
+}
@@ -0,0 +1,118 @@
+//! Reports of structural verification
+
+use crate::utils::check_crc;
+use mycrc::CRC;
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// Kind of a problem found by verification
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProblemKind {
+    /// Checksum doesn't match the data.
+    CrcMismatch,
+    /// File ends in the middle of the data. The byte offset is the end of the file.
+    UnexpectedEof,
+    /// Data can't be read, such as an unknown kind, invalid UTF-8 or broken samples.
+    InvalidData(String),
+    /// Bubble functions can't be parsed.
+    InvalidBubFns,
+    /// Expression can't be parsed.
+    InvalidExpr,
+    /// Block has no frames.
+    EmptyBlock,
+    /// Next head is not after the foot of the previous block.
+    OverlappingBlocks,
+    /// Frame is out of `frames` of the metadata.
+    OutOfFrames { frame: u64, frames: u64 },
+    /// Starting frames of a bubble are not in ascending order.
+    UnsortedStartingFrames,
+    /// Bytes remain after the end of the data.
+    TrailingBytes(u64),
+}
+
+/// Problem found by verification
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// Absolute frame of the head of the block, if any
+    pub frame: Option<u64>,
+    /// Byte offset from the start of the file
+    pub byte_offset: u64,
+    pub kind: ProblemKind,
+}
+
+/// Report of structural verification
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VerifyReport {
+    pub problems: Vec<Problem>,
+}
+
+impl VerifyReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, frame: Option<u64>, byte_offset: u64, kind: ProblemKind) {
+        self.problems.push(Problem {
+            frame,
+            byte_offset,
+            kind,
+        });
+    }
+
+    /// Push `e` if it is caused by the file, or return it.
+    pub(crate) fn push_error(
+        &mut self,
+        frame: Option<u64>,
+        byte_offset: u64,
+        e: Error,
+    ) -> Result<()> {
+        let kind = match e.kind() {
+            ErrorKind::UnexpectedEof => ProblemKind::UnexpectedEof,
+            ErrorKind::InvalidData => ProblemKind::InvalidData(e.to_string()),
+            _ => return Err(e),
+        };
+        self.push(frame, byte_offset, kind);
+
+        Ok(())
+    }
+
+    /// Read a CRC and push `CrcMismatch` if it is wrong.
+    pub(crate) fn check_crc<R: Read>(
+        &mut self,
+        reader: &mut OffsetReader<R>,
+        crc: &mut CRC<u32>,
+        frame: Option<u64>,
+    ) -> Result<bool> {
+        let byte_offset = reader.offset;
+        let is_error_free = check_crc(reader, crc)?;
+        if !is_error_free {
+            self.push(frame, byte_offset, ProblemKind::CrcMismatch);
+        }
+
+        Ok(is_error_free)
+    }
+}
+
+/// Reader which counts read bytes
+pub(crate) struct OffsetReader<R: Read> {
+    inner: R,
+    pub(crate) offset: u64,
+}
+
+impl<R: Read> OffsetReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self { inner, offset: 0 }
+    }
+
+    /// Returns the number of remaining bytes.
+    pub(crate) fn trailing_bytes(&mut self) -> Result<u64> {
+        std::io::copy(&mut self.inner, &mut std::io::sink())
+    }
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.offset += len as u64;
+        Ok(len)
+    }
+}
@@ -0,0 +1,76 @@
+use crate::io::async_ext::fill;
+use crate::wav::{WavFrameReader, WavMetadata};
+use crate::{Frame, Sample};
+use futures_util::io::AsyncRead;
+use std::collections::VecDeque;
+use std::io::Result;
+
+/// `WavFrameReader` over `AsyncRead`.
+/// Bytes of each frame are read into `wav_frame_reader.inner` before the frame is parsed.
+pub struct AsyncWavFrameReader<R: AsyncRead + Unpin, S: Sample> {
+    pub inner: R,
+    pub wav_frame_reader: WavFrameReader<VecDeque<u8>, S>,
+}
+
+impl<R: AsyncRead + Unpin, S: Sample> AsyncWavFrameReader<R, S> {
+    /// `metadata` is returned by `WavMetadata::read_async`.
+    pub fn new(inner: R, metadata: WavMetadata) -> Self {
+        Self {
+            inner,
+            wav_frame_reader: WavFrameReader::new(VecDeque::new(), metadata),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns `None` after the last frame like `Iterator::next`.
+    pub async fn next_frame(&mut self) -> Option<Result<Frame<S>>> {
+        let metadata = &self.wav_frame_reader.metadata;
+        if metadata.frames() <= self.wav_frame_reader.pos {
+            return None;
+        }
+
+        let block_align = metadata.block_align() as usize;
+        if let Err(e) = fill(
+            &mut self.inner,
+            &mut self.wav_frame_reader.inner,
+            block_align,
+        )
+        .await
+        {
+            return Some(Err(e));
+        }
+
+        self.wav_frame_reader.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav::WavReader;
+    use futures_executor::block_on;
+
+    #[test]
+    fn read_frames() -> Result<()> {
+        let v = std::fs::read("tests/test.wav")?;
+        let expects = WavReader::new(&v[..])?
+            .into_wav_frame_reader_kind()
+            .into_f32_le()?
+            .collect::<Result<Vec<_>>>()?;
+
+        block_on(async {
+            let mut inner = &v[..];
+            let metadata = WavMetadata::read_async(&mut inner).await?;
+            let mut async_wav_frame_reader = AsyncWavFrameReader::<_, f32>::new(inner, metadata);
+            for expect in expects {
+                assert_eq!(async_wav_frame_reader.next_frame().await.unwrap()?, expect);
+            }
+            assert!(async_wav_frame_reader.next_frame().await.is_none());
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,79 @@
+use crate::io::async_ext::flush_buf;
+use crate::wav::{WavFrameWriter, WavMetadata};
+use crate::{Frame, Sample};
+use futures_util::io::{AsyncWrite, AsyncWriteExt};
+use std::io::Result;
+
+/// `WavFrameWriter` over `AsyncWrite`.
+/// Each frame is written into `wav_frame_writer.inner` and then to `inner`.
+pub struct AsyncWavFrameWriter<W: AsyncWrite + Unpin, S: Sample> {
+    pub inner: W,
+    pub wav_frame_writer: WavFrameWriter<Vec<u8>, S>,
+}
+
+impl<W: AsyncWrite + Unpin, S: Sample> AsyncWavFrameWriter<W, S> {
+    /// `metadata` is written by `WavMetadata::write_async`.
+    pub fn new(inner: W, metadata: WavMetadata) -> Self {
+        Self {
+            inner,
+            wav_frame_writer: WavFrameWriter::new(Vec::new(), metadata),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    pub async fn write_frame(&mut self, wav_frame: Frame<S>) -> Result<()> {
+        self.wav_frame_writer.write_frame(wav_frame)?;
+        flush_buf(&mut self.inner, &mut self.wav_frame_writer.inner).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav::WavReader;
+    use crate::LpcmKind;
+    use futures_executor::block_on;
+
+    #[test]
+    fn write_and_read_frames() -> Result<()> {
+        let metadata = WavMetadata::new(3, LpcmKind::I16LE, 2, 44100.0, vec![]);
+        let frames: Vec<Frame<f32>> = vec![
+            vec![0.5, -0.5].into(),
+            vec![0.25, 0.0].into(),
+            vec![-1.0, 0.75].into(),
+        ];
+
+        let v = block_on(async {
+            let mut inner = Vec::new();
+            metadata.write_async(&mut inner).await?;
+            let mut async_wav_frame_writer = AsyncWavFrameWriter::new(inner, metadata.clone());
+            for frame in frames.clone() {
+                async_wav_frame_writer.write_frame(frame).await?;
+            }
+            assert!(async_wav_frame_writer
+                .write_frame(vec![0.0, 0.0].into())
+                .await
+                .is_err());
+            async_wav_frame_writer.flush().await?;
+
+            Result::Ok(async_wav_frame_writer.into_inner())
+        })?;
+
+        let wav_reader = WavReader::new(&v[..])?;
+        assert_eq!(wav_reader.metadata, metadata);
+        let read = wav_reader
+            .into_wav_frame_reader_kind()
+            .into_i16_le()?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(read, frames);
+
+        Ok(())
+    }
+}
@@ -58,7 +58,7 @@ impl<R: Read, S: Sample> Iterator for WavFrameReader<R, S> {
         let mut buf: Vec<S> = Vec::with_capacity(self.metadata.channels() as usize);
 
         for _ in 0..self.metadata.channels() as usize {
-            let wav_sample = S::read(self.get_mut());
+            let wav_sample = S::read_lpcm(&mut self.inner, self.metadata.lpcm_kind());
             match wav_sample {
                 Ok(s) => buf.push(s),
                 Err(e) => return Some(Err(e)),
@@ -69,7 +69,14 @@ impl<R: Read, S: Sample> Iterator for WavFrameReader<R, S> {
     }
 }
 
-pub type WavFrameReaderKind<R> = FrameIOKind<WavFrameReader<R, f32>, WavFrameReader<R, f64>>;
+pub type WavFrameReaderKind<R> = FrameIOKind<
+    WavFrameReader<R, f32>,
+    WavFrameReader<R, f64>,
+    WavFrameReader<R, f32>,
+    WavFrameReader<R, f32>,
+    WavFrameReader<R, f64>,
+    WavFrameReader<R, f32>,
+>;
 
 #[cfg(test)]
 mod tests {
@@ -80,7 +87,7 @@ mod tests {
     fn read() {
         macro_rules! test_read_wav {
             ( $( $t:ty ),* ) => ($(
-                let lpcm_kind =LpcmKind::from_format_tag_and_bits_per_sample(3, (std::mem::size_of::<$t>() * 8) as u16);
+                let lpcm_kind =LpcmKind::from_format_tag_and_bits_per_sample(3, (std::mem::size_of::<$t>() * 8) as u16).unwrap();
                 let channels = 1;
                 let samples_per_sec = 44100.0;
 
@@ -49,14 +49,21 @@ impl<W: Write, S: Sample> WavFrameWriter<W, S> {
         }
 
         for sample in wav_frame.0 {
-            sample.write(&mut self.inner)?;
+            sample.write_lpcm(&mut self.inner, self.metadata.lpcm_kind())?;
         }
 
         Ok(())
     }
 }
 
-pub type WavFrameWriterKind<W> = FrameIOKind<WavFrameWriter<W, f32>, WavFrameWriter<W, f64>>;
+pub type WavFrameWriterKind<W> = FrameIOKind<
+    WavFrameWriter<W, f32>,
+    WavFrameWriter<W, f64>,
+    WavFrameWriter<W, f32>,
+    WavFrameWriter<W, f32>,
+    WavFrameWriter<W, f64>,
+    WavFrameWriter<W, f32>,
+>;
 
 #[cfg(test)]
 mod tests {
@@ -1,8 +1,16 @@
+#[cfg(feature = "async")]
+pub use self::async_frame_reader::AsyncWavFrameReader;
+#[cfg(feature = "async")]
+pub use self::async_frame_writer::AsyncWavFrameWriter;
 pub use self::frame_reader::{WavFrameReader, WavFrameReaderKind};
 pub use self::frame_writer::{WavFrameWriter, WavFrameWriterKind};
 pub use self::reader::WavReader;
 pub use self::writer::WavWriter;
 
+#[cfg(feature = "async")]
+mod async_frame_reader;
+#[cfg(feature = "async")]
+mod async_frame_writer;
 mod frame_reader;
 mod frame_writer;
 mod reader;
@@ -32,7 +32,18 @@ impl<R: Read> WavReader<R> {
             LpcmKind::F64LE => {
                 WavFrameReaderKind::F64LE(WavFrameReader::<R, f64>::new(self.inner, self.metadata))
             }
-            _ => unimplemented!(),
+            LpcmKind::I16LE => {
+                WavFrameReaderKind::I16LE(WavFrameReader::<R, f32>::new(self.inner, self.metadata))
+            }
+            LpcmKind::I24LE => {
+                WavFrameReaderKind::I24LE(WavFrameReader::<R, f32>::new(self.inner, self.metadata))
+            }
+            LpcmKind::I32LE => {
+                WavFrameReaderKind::I32LE(WavFrameReader::<R, f64>::new(self.inner, self.metadata))
+            }
+            LpcmKind::U8 => {
+                WavFrameReaderKind::U8(WavFrameReader::<R, f32>::new(self.inner, self.metadata))
+            }
         }
     }
 }
@@ -36,7 +36,18 @@ impl<W: Write> WavWriter<W> {
             LpcmKind::F64LE => {
                 WavFrameWriterKind::F64LE(WavFrameWriter::<W, f64>::new(self.inner, self.metadata))
             }
-            _ => unimplemented!(),
+            LpcmKind::I16LE => {
+                WavFrameWriterKind::I16LE(WavFrameWriter::<W, f32>::new(self.inner, self.metadata))
+            }
+            LpcmKind::I24LE => {
+                WavFrameWriterKind::I24LE(WavFrameWriter::<W, f32>::new(self.inner, self.metadata))
+            }
+            LpcmKind::I32LE => {
+                WavFrameWriterKind::I32LE(WavFrameWriter::<W, f64>::new(self.inner, self.metadata))
+            }
+            LpcmKind::U8 => {
+                WavFrameWriterKind::U8(WavFrameWriter::<W, f32>::new(self.inner, self.metadata))
+            }
         }
     }
 }
@@ -53,6 +64,7 @@ impl WavWriter<BufWriter<File>> {
 mod tests {
     use super::*;
     use crate::wav::WavReader;
+    use crate::Frame;
 
     #[test]
     fn read_and_write() -> std::io::Result<()> {
@@ -75,4 +87,30 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn write_and_read_i24_le() -> std::io::Result<()> {
+        let metadata = WavMetadata::new(3, LpcmKind::I24LE, 2, 48000.0, vec![]);
+        let wav_writer = WavWriter::new(Vec::new(), metadata.clone())?;
+        let mut wav_frame_writer = wav_writer.into_wav_frame_writer_kind().into_i24_le()?;
+        let frames: Vec<Frame<f32>> = vec![
+            vec![0.0, 0.5].into(),
+            vec![-0.5, -1.0].into(),
+            vec![0.25, 1.0 / 8388608.0].into(),
+        ];
+        for frame in frames.iter() {
+            wav_frame_writer.write_frame(frame.clone())?;
+        }
+        let v = wav_frame_writer.inner;
+        assert_eq!(v.len(), 44 + 3 * 6);
+
+        let wav_reader = WavReader::new(&v[..])?;
+        assert_eq!(wav_reader.metadata, metadata);
+        let wav_frame_reader = wav_reader.into_wav_frame_reader_kind().into_i24_le()?;
+        for (frame, expect) in wav_frame_reader.zip(frames) {
+            assert_eq!(frame?, expect);
+        }
+
+        Ok(())
+    }
 }
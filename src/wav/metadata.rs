@@ -1,7 +1,7 @@
 use crate::io::{ReadExt, WriteBytes, WriteExt};
 use crate::utils::return_invalid_data_if_not_equal;
 use crate::{LpcmKind, Metadata};
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct WavMetadata {
@@ -180,8 +180,18 @@ impl WavMetadata {
                             let cb_size: u16 = reader.read_le()?;
                             let mut skip = vec![0; cb_size as usize];
                             reader.read_exact(&mut skip)?;
+                            // WAVE_FORMAT_EXTENSIBLE
+                            if format_tag == 0xFFFE && cb_size >= 22 {
+                                // The first 2 bytes of SubFormat are the format tag.
+                                format_tag = u16::from_le_bytes([skip[6], skip[7]]);
+                            }
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorKind::InvalidData,
+                                format!("fmt size {} is too small", fmt_size),
+                            ))
                         }
-                        _ => unimplemented!("fmt size"),
                     }
                 }
                 "data" => {
@@ -194,7 +204,7 @@ impl WavMetadata {
                         lpcm_kind: LpcmKind::from_format_tag_and_bits_per_sample(
                             format_tag,
                             bits_per_sample,
-                        ),
+                        )?,
                         channels,
                         samples_per_sec: samples_per_sec as f64,
                         list,
@@ -213,6 +223,14 @@ impl WavMetadata {
         }
     }
 
+    /// Bytes after the metadata are left in the buffer of `reader`.
+    #[cfg(feature = "async")]
+    pub async fn read_async<R: futures_util::io::AsyncBufRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<Self> {
+        crate::io::async_ext::read_with(reader, |reader| Self::read(reader)).await
+    }
+
     pub fn write<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
         // Riff chunk
         writer.write_str("RIFF")?;
@@ -233,6 +251,14 @@ impl WavMetadata {
 
         Ok(())
     }
+
+    #[cfg(feature = "async")]
+    pub async fn write_async<W: futures_util::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<()> {
+        crate::io::async_ext::write_with(writer, |writer| self.write(writer)).await
+    }
 }
 
 #[cfg(test)]
@@ -475,6 +501,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn read_extensible() -> Result<()> {
+        let mut data: &[u8] = &[
+            0x52, 0x49, 0x46, 0x46, 0x4C, 0x00, 0x00, 0x00, 0x57, 0x41, 0x56, 0x45, 0x66, 0x6D,
+            0x74, 0x20, 0x28, 0x00, 0x00, 0x00, 0xFE, 0xFF, 0x02, 0x00, 0x80, 0xBB, 0x00, 0x00,
+            0x00, 0x65, 0x04, 0x00, 0x06, 0x00, 0x18, 0x00, 0x16, 0x00, 0x18, 0x00, 0x03, 0x00,
+            0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA,
+            0x00, 0x38, 0x9B, 0x71, 0x64, 0x61, 0x74, 0x61, 0x24, 0x00, 0x00, 0x00,
+        ];
+        // 20 bits per sample is not supported.
+        let mut unsupported = data.to_vec();
+        unsupported[34] = 0x14;
+        assert_eq!(
+            WavMetadata::read(&mut &unsupported[..]).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        let val = WavMetadata::read(&mut data)?;
+        let expect = WavMetadata {
+            frames: 6,
+            lpcm_kind: LpcmKind::I24LE,
+            channels: 2,
+            samples_per_sec: 48000.0,
+            list: vec![],
+        };
+        assert_eq!(val, expect);
+
+        Ok(())
+    }
+
     #[test]
     fn write_and_read() -> Result<()> {
         let mut v = Vec::new();
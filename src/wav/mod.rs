@@ -1,3 +1,5 @@
+#[cfg(feature = "async")]
+pub use self::io::{AsyncWavFrameReader, AsyncWavFrameWriter};
 pub use self::io::{
     WavFrameReader, WavFrameReaderKind, WavFrameWriter, WavFrameWriterKind, WavReader, WavWriter,
 };